@@ -17,4 +17,7 @@ MOV A, D     ; "Get higher byte of the 1st number"
 ADC H        ; "Add higher byte of the 2nd number with CARRY"  
 MOV H, A     ; "Store result in H-register"  
 SHLD 4004H   ; "Store 16-bit result in memory locations 2505H and 2506H"  
-HLT          ; "Stop"   
\ No newline at end of file
+HLT          ; "Stop"
+
+;@ mem 2501 = 15 1C B7 5A
+;@ expect mem 4004 = CC 76
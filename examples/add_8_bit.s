@@ -11,4 +11,8 @@ INX H         ; "Increment content of H-L pair. Now, H-L points 2502H"
 ADD M         ; "Add first and second operand"  
 INX H         ; "H-L points 4002H"  
 MOV M, A      ; "Store result at 2503H"  
-HLT           ; "Stop"  
\ No newline at end of file
+HLT           ; "Stop"
+
+;@ mem 2501 = 99 39
+;@ expect A = D2
+;@ expect mem 2503 = D2
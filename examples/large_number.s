@@ -14,4 +14,7 @@ MOV A, M                ; Set the new value as largest.
 AHEAD: DCR  B         
 JNZ LOOP                ; Repeat comparisons till count = 0.
 STA 2300H               ; Store the largest value at 2300.
-HLT                     ; Terminate program execution.
\ No newline at end of file
+HLT                     ; Terminate program execution.
+
+;@ mem 2200 = 04 0A 3F 09 21
+;@ expect mem 2300 = 3F
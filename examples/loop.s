@@ -7,3 +7,6 @@ INR B
 DCR C 
 JNZ X
 HLT
+
+;@ expect mem 2050 = 01 02 03 04
+;@ expect C = 0
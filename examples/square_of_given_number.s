@@ -10,4 +10,8 @@ MOV L, A    ; "Get data in register L"
 MVI H, 26H  ; "Get 26 in register H"  
 MOV A, M    ; "Square of data in accumulator"  
 STA 2501 H  ; "Store Square in 2501 H"  
-HLT ; "Stop"  
\ No newline at end of file
+HLT ; "Stop"
+
+;@ mem 2500 = 07
+;@ mem 2600 = 00 01 04 09 10 19 24 31 40 51
+;@ expect mem 2501 = 31
@@ -7,4 +7,8 @@ INX H          ;  "Increment content of H-L pair. Now, H-L points 2502H"
 SUB M          ;  "Subtract first to second operand"  
 INX H          ;  "H-L points 4002H"  
 MOV M, A       ;  "Store result at 2503H"  
-HLT            ;  "Stop"  
\ No newline at end of file
+HLT            ;  "Stop"
+
+;@ mem 2501 = 49 32
+;@ expect A = 17
+;@ expect mem 2503 = 17
@@ -3,7 +3,7 @@
 ;2502 H = 15H
 ;2503 H = 13H
 ;2504 H = 22H
-Result = 2505 H = 20+22= 42H
+;Result = 2505 H = 20+22= 42H
 ;The numbers are placed in the memory locations 2501 to 2504H.
 ;
 ;The sum is to be stored in the memory location 2450H.
@@ -25,4 +25,7 @@ SKIP: INX H         ; "increment pointer"
 DCR C               ; "Decrement counter"  
 JNZ BACK            ; "if counter 0 repeat"  
 STA 2505H           ; "store sum"  
-HLT                 ; "Stop"  
\ No newline at end of file
+HLT                 ; "Stop"
+
+;@ mem 2500 = 04 20 15 13 22
+;@ expect mem 2505 = 42
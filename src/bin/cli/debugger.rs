@@ -0,0 +1,653 @@
+use std::collections::HashMap;
+
+use emulator_8085::core::{
+    breakpoints::{Breakpoint, Condition, Flag, WatchKind, Watched, Watchpoint},
+    cpu::{Cpu, Stop},
+    disassembler::{decode, linear_sweep, render, symbols_by_address},
+    image::Image,
+    parser::Ins,
+    snapshot::{restore_snapshot, to_snapshot},
+};
+
+use super::{describe_stop, format_range, parse_byte, parse_hex, set_register, REGISTER_NAMES};
+
+const HELP: &str = "\
+s, step [n]            execute one (or n) instructions
+n, next                step, treating CALL and RST as one instruction
+back [n]               undo one (or n) instructions
+c, continue            run until a breakpoint or the end of the program
+rc, rcontinue          undo instructions back to the previous breakpoint
+u, until <location>    run until <location>
+b, break [<location> [if <condition>]]
+                       set a breakpoint, or list them; conditions look like A == 42 or [2050] != 0
+d, delete <location>   clear the breakpoints at <location>
+w, watch [[port] <start>[-<end>] [r|w|rw]]
+                       stop after memory or ports are read or written (w), or list the watchpoints
+unwatch <n>            clear watchpoint n
+r, reg [<name>=<value>...]
+                       show registers and flags, or set them (A-L, BC, DE, HL, SP, PC, S, Z, AC, P, CY)
+m, mem <location> [n]  show n (16) bytes of memory
+m, mem <location>=<byte>[,<byte>...]
+                       write memory
+l, list [<location>]   disassemble around PC or from <location>
+source                 show the source around PC
+save <file>            write the machine state to a snapshot file
+load <file>            restore the machine state from a snapshot file
+reset                  start the program over
+q, quit                leave the debugger
+
+A location is a label, a hex address or :<line> for the first instruction of a source line.
+An empty line repeats the last command.";
+
+const LIST_BEFORE: usize = 3;
+const LIST_LENGTH: usize = 10;
+const SOURCE_CONTEXT: usize = 2;
+const EXAMINE_LENGTH: usize = 16;
+
+pub struct Debugger<'a> {
+    image: &'a Image,
+    symbols: HashMap<u16, &'a str>,
+    /// The lines of every source file of the image that could be read.
+    sources: HashMap<&'a str, Vec<String>>,
+    initial: Cpu,
+    pub cpu: Cpu,
+    max_steps: u64,
+    pub steps: u64,
+    /// How the program ended, once it has.
+    pub stop: Option<Stop>,
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(cpu: Cpu, image: &'a Image, max_steps: u64) -> Self {
+        let sources = image
+            .source_map
+            .files
+            .iter()
+            .filter_map(|file| {
+                let text = std::fs::read_to_string(file).ok()?;
+                Some((file.as_str(), text.lines().map(str::to_string).collect()))
+            })
+            .collect();
+        Self {
+            image,
+            symbols: symbols_by_address(&image.symbols),
+            sources,
+            initial: cpu.clone(),
+            cpu,
+            max_steps,
+            steps: 0,
+            stop: None,
+        }
+    }
+
+    /// Runs one debugger command and returns what to print, or `None` to quit.
+    pub fn command(&mut self, line: &str) -> Option<String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let output = match words.as_slice() {
+            ["s" | "step"] => self.step_times(1),
+            ["s" | "step", count] => match count.parse() {
+                Ok(count) => self.step_times(count),
+                Err(_) => format!("Invalid count: {}", count),
+            },
+            ["n" | "next"] => self.next(),
+            ["back"] => self.step_back(1),
+            ["back", count] => match count.parse() {
+                Ok(count) => self.step_back(count),
+                Err(_) => format!("Invalid count: {}", count),
+            },
+            ["rc" | "rcontinue"] => self.run_back(),
+            ["c" | "continue"] => self.resume(None),
+            ["u" | "until", location] => match self.address(location) {
+                Some(address) => self.resume(Some(address)),
+                None => unknown_location(location),
+            },
+            ["b" | "break"] => self.list_breakpoints(),
+            ["b" | "break", location, condition @ ..] => self.add_breakpoint(location, condition),
+            ["d" | "delete", location] => self.delete_breakpoints(location),
+            ["w" | "watch"] => self.list_watchpoints(),
+            ["w" | "watch", arguments @ ..] => self.add_watchpoint(arguments),
+            ["unwatch", number] => match number.parse::<usize>() {
+                Ok(number) if (1..=self.cpu.watchpoints.len()).contains(&number) => {
+                    let watchpoint = self.cpu.watchpoints.remove(number - 1);
+                    format!("Deleted watchpoint {}: {}", number, watchpoint)
+                }
+                _ => format!("No watchpoint {}", number),
+            },
+            ["r" | "reg"] => self.registers(),
+            ["r" | "reg", assignments @ ..] => self.set_registers(assignments),
+            ["m" | "mem", assignment] if assignment.contains('=') => self.write_memory(assignment),
+            ["m" | "mem", location] => self.examine(location, EXAMINE_LENGTH),
+            ["m" | "mem", location, count] => match count.parse() {
+                Ok(count) if count > 0 => self.examine(location, count),
+                _ => format!("Invalid count: {}", count),
+            },
+            ["l" | "list"] => self.list(None),
+            ["l" | "list", location] => match self.address(location) {
+                Some(address) => self.list(Some(address)),
+                None => unknown_location(location),
+            },
+            ["source"] => self.source(),
+            ["save", file] => match std::fs::write(file, to_snapshot(&self.cpu)) {
+                Ok(()) => format!("Saved the machine state to {}", file),
+                Err(err) => format!("Couldn't write {}: {}", file, err),
+            },
+            ["load", file] => self.load(file),
+            ["reset"] => {
+                self.cpu = self.initial.clone();
+                self.steps = 0;
+                self.stop = None;
+                self.location()
+            }
+            ["h" | "help"] => HELP.to_string(),
+            ["q" | "quit"] => return None,
+            _ => format!(
+                "Unknown command: {}. Type help for the list of commands.",
+                line.trim()
+            ),
+        };
+        Some(output)
+    }
+
+    /// A label, a hex address or `:<line>`.
+    fn address(&self, location: &str) -> Option<u16> {
+        if let Some(line) = location.strip_prefix(':') {
+            let line: usize = line.parse().ok()?;
+            return self
+                .image
+                .source_map
+                .entries
+                .iter()
+                .find(|(_, entry)| entry.location.line == line)
+                .map(|(address, _)| *address);
+        }
+        self.image
+            .symbols
+            .get(location)
+            .or_else(|| self.image.symbols.get(&location.to_uppercase()))
+            .copied()
+            .or_else(|| parse_hex(location))
+    }
+
+    fn disassemble(&self, address: u16) -> String {
+        match decode(&self.cpu.memory, address) {
+            Some((ins, _)) => render(&ins, &self.symbols),
+            None => format!("DB {:02X}H", self.cpu.read_memory(address as usize)),
+        }
+    }
+
+    fn source_line(&self, address: u16) -> Option<(usize, &str)> {
+        let (file, location) = self.image.source_map.lookup(address)?;
+        let text = self.sources.get(file)?.get(location.line - 1)?;
+        Some((location.line, text.as_str()))
+    }
+
+    /// Restores a snapshot; the breakpoints and watchpoints stay.
+    fn load(&mut self, file: &str) -> String {
+        let source = match std::fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(err) => return format!("Couldn't read {}: {}", file, err),
+        };
+        match restore_snapshot(&mut self.cpu, &source) {
+            Ok(()) => {
+                self.stop = None;
+                self.location()
+            }
+            Err(err) => format!("{}: {}", file, err),
+        }
+    }
+
+    /// Where the program is, with its source line when it is known.
+    pub fn location(&self) -> String {
+        let pc = self.cpu.pc;
+        let mut output = format!(
+            "{}  {}",
+            self.image.source_map.describe(pc),
+            self.disassemble(pc)
+        );
+        if let Some((line, text)) = self.source_line(pc) {
+            output.push_str(&format!("\n{:>5} | {}", line, text));
+        }
+        output
+    }
+
+    fn ended(&self) -> Option<String> {
+        self.stop.map(|stop| {
+            format!(
+                "{} after {} steps and {} T-states",
+                describe_stop(&self.cpu, self.image, stop).0,
+                self.steps,
+                self.cpu.cycles
+            )
+        })
+    }
+
+    /// Runs at most `max_steps` instructions and describes where they stopped, and whether that
+    /// was for anything other than running out of steps.
+    fn execute(&mut self, max_steps: u64, target: Option<u16>) -> (String, bool) {
+        if self.stop.is_some() {
+            return (self.ended().unwrap_or_default(), true);
+        }
+        let (stop, steps) = self.cpu.run(self.image, max_steps);
+        self.steps += steps;
+        let output = match stop {
+            Stop::StepLimit if max_steps == 1 => return (self.location(), false),
+            Stop::StepLimit => format!(
+                "Still running after {} steps\n{}",
+                max_steps,
+                self.location()
+            ),
+            Stop::Breakpoint(address) if Some(address) == target => self.location(),
+            Stop::Breakpoint(_) => format!("Breakpoint\n{}", self.location()),
+            Stop::Watchpoint(access) => format!("Watchpoint on {}\n{}", access, self.location()),
+            stop => {
+                self.stop = Some(stop);
+                self.ended().unwrap_or_default()
+            }
+        };
+        (output, true)
+    }
+
+    fn step_times(&mut self, count: u64) -> String {
+        let mut output = self.location();
+        for _ in 0..count {
+            let stopped;
+            (output, stopped) = self.execute(1, None);
+            if stopped {
+                break;
+            }
+        }
+        output
+    }
+
+    /// Runs until `target`, a breakpoint, a watchpoint, the end of the program or the step
+    /// limit.
+    fn resume(&mut self, target: Option<u16>) -> String {
+        if let Some(address) = target {
+            self.cpu.breakpoints.push(Breakpoint {
+                address,
+                condition: None,
+            });
+        }
+        let (output, _) = self.execute(self.max_steps, target);
+        if target.is_some() {
+            self.cpu.breakpoints.pop();
+        }
+        output
+    }
+
+    fn undone(&mut self, steps: u64) {
+        self.steps = self.steps.saturating_sub(steps);
+        if steps > 0 {
+            self.stop = None;
+        }
+    }
+
+    fn step_back(&mut self, count: u64) -> String {
+        for _ in 0..count {
+            if !self.cpu.step_back() {
+                return format!("No more history\n{}", self.location());
+            }
+            self.undone(1);
+        }
+        self.location()
+    }
+
+    /// Goes back to the previous breakpoint, or as far as the history reaches.
+    fn run_back(&mut self) -> String {
+        let (breakpoint, steps) = self.cpu.run_back();
+        self.undone(steps);
+        match breakpoint {
+            true => format!("Breakpoint\n{}", self.location()),
+            false => format!("No more history\n{}", self.location()),
+        }
+    }
+
+    fn next(&mut self) -> String {
+        match decode(&self.cpu.memory, self.cpu.pc) {
+            Some((
+                Ins::Call(_)
+                | Ins::Cnz(_)
+                | Ins::Cz(_)
+                | Ins::Cnc(_)
+                | Ins::Cc(_)
+                | Ins::Cpo(_)
+                | Ins::Cpe(_)
+                | Ins::Cp(_)
+                | Ins::Cm(_)
+                | Ins::Rst(_),
+                length,
+            )) if self.stop.is_none() => self.resume(Some(self.cpu.pc.wrapping_add(length as u16))),
+            _ => self.step_times(1),
+        }
+    }
+
+    fn add_breakpoint(&mut self, location: &str, condition: &[&str]) -> String {
+        let Some(address) = self.address(location) else {
+            return unknown_location(location);
+        };
+        let condition = match condition {
+            [] => None,
+            ["if", condition @ ..] => match Condition::parse(&condition.join(" ")) {
+                Some(condition) => Some(condition),
+                None => return format!("Invalid condition: {}", condition.join(" ")),
+            },
+            _ => return format!("Expected if <condition>: {}", condition.join(" ")),
+        };
+        let breakpoint = Breakpoint { address, condition };
+        let output = format!("Breakpoint at {}", self.describe_breakpoint(&breakpoint));
+        self.cpu.breakpoints.push(breakpoint);
+        output
+    }
+
+    fn describe_breakpoint(&self, breakpoint: &Breakpoint) -> String {
+        let place = self.image.source_map.describe(breakpoint.address);
+        match &breakpoint.condition {
+            Some(condition) => format!("{} if {}", place, condition),
+            None => place,
+        }
+    }
+
+    fn list_breakpoints(&self) -> String {
+        if self.cpu.breakpoints.is_empty() {
+            return "No breakpoints".to_string();
+        }
+        let lines: Vec<String> = self
+            .cpu
+            .breakpoints
+            .iter()
+            .map(|breakpoint| self.describe_breakpoint(breakpoint))
+            .collect();
+        lines.join("\n")
+    }
+
+    fn delete_breakpoints(&mut self, location: &str) -> String {
+        let Some(address) = self.address(location) else {
+            return unknown_location(location);
+        };
+        let count = self.cpu.breakpoints.len();
+        self.cpu
+            .breakpoints
+            .retain(|breakpoint| breakpoint.address != address);
+        match count - self.cpu.breakpoints.len() {
+            0 => format!("No breakpoint at {:04X}H", address),
+            _ => format!("Deleted the breakpoint at {:04X}H", address),
+        }
+    }
+
+    fn add_watchpoint(&mut self, arguments: &[&str]) -> String {
+        let (port, arguments) = match arguments {
+            ["port", rest @ ..] => (true, rest),
+            rest => (false, rest),
+        };
+        let (range, kind) = match arguments {
+            [range] => (*range, WatchKind::Write),
+            [range, "r"] => (*range, WatchKind::Read),
+            [range, "w"] => (*range, WatchKind::Write),
+            [range, "rw"] => (*range, WatchKind::ReadWrite),
+            _ => return "Expected watch [port] <start>[-<end>] [r|w|rw]".to_string(),
+        };
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        let watched = match (self.address(start), self.address(end)) {
+            (Some(start), Some(end)) if start <= end && !port => Watched::Memory(start, end),
+            (Some(start), Some(end)) if start <= end && end <= 0xFF => {
+                Watched::Port(start as u8, end as u8)
+            }
+            _ => return format!("Invalid range: {}", range),
+        };
+        let watchpoint = Watchpoint { watched, kind };
+        self.cpu.watchpoints.push(watchpoint);
+        format!("Watchpoint {}: {}", self.cpu.watchpoints.len(), watchpoint)
+    }
+
+    fn list_watchpoints(&self) -> String {
+        if self.cpu.watchpoints.is_empty() {
+            return "No watchpoints".to_string();
+        }
+        let lines: Vec<String> = self
+            .cpu
+            .watchpoints
+            .iter()
+            .enumerate()
+            .map(|(index, watchpoint)| format!("{}: {}", index + 1, watchpoint))
+            .collect();
+        lines.join("\n")
+    }
+
+    fn registers(&self) -> String {
+        let cpu = &self.cpu;
+        let flags = &cpu.flags;
+        format!(
+            "A={:02X} B={:02X} C={:02X} D={:02X} E={:02X} H={:02X} L={:02X} SP={:04X} PC={:04X}\n\
+             S={} Z={} AC={} P={} CY={}  T-states={}",
+            cpu.accumulator,
+            cpu.b,
+            cpu.c,
+            cpu.d,
+            cpu.e,
+            cpu.h,
+            cpu.l,
+            cpu.sp,
+            cpu.pc,
+            u8::from(flags.sign),
+            u8::from(flags.zero),
+            u8::from(flags.auxiliary_carry),
+            u8::from(flags.parity),
+            u8::from(flags.carry),
+            cpu.cycles
+        )
+    }
+
+    fn set_registers(&mut self, assignments: &[&str]) -> String {
+        for assignment in assignments {
+            let Some((name, value)) = assignment.split_once('=') else {
+                return format!("Expected <name>=<value>: {}", assignment);
+            };
+            let name = name.to_uppercase();
+            if let Some(flag) = Flag::from(&name) {
+                let value = match value {
+                    "0" => false,
+                    "1" => true,
+                    _ => return format!("Flags are 0 or 1: {}", assignment),
+                };
+                flag.set(&mut self.cpu, value);
+                continue;
+            }
+            match parse_hex(value) {
+                Some(value)
+                    if REGISTER_NAMES.contains(&name.as_str())
+                        && (name.len() > 1 || value <= 0xFF) =>
+                {
+                    set_register(&mut self.cpu, &name, value)
+                }
+                _ => return format!("Invalid register value: {}", assignment),
+            }
+        }
+        self.registers()
+    }
+
+    fn examine(&self, location: &str, count: usize) -> String {
+        let Some(start) = self.address(location) else {
+            return unknown_location(location);
+        };
+        let end = (start as usize + count - 1).min(0xFFFF) as u16;
+        format_range(&self.cpu, start, end).trim_end().to_string()
+    }
+
+    fn write_memory(&mut self, assignment: &str) -> String {
+        let (location, bytes) = assignment.split_once('=').unwrap_or_default();
+        let Some(address) = self.address(location) else {
+            return unknown_location(location);
+        };
+        let Some(bytes) = bytes
+            .split(',')
+            .map(parse_byte)
+            .collect::<Option<Vec<u8>>>()
+        else {
+            return format!("Invalid bytes: {}", bytes);
+        };
+        for (offset, byte) in bytes.iter().enumerate() {
+            self.cpu
+                .write_memory(address.wrapping_add(offset as u16) as usize, *byte);
+        }
+        self.examine(location, bytes.len())
+    }
+
+    /// Disassembles from `start`, or from a few instructions before PC.
+    fn list(&self, start: Option<u16>) -> String {
+        let pc = self.cpu.pc;
+        let start = start.unwrap_or_else(|| {
+            self.image
+                .source_map
+                .entries
+                .range(..pc)
+                .rev()
+                .take(LIST_BEFORE)
+                .last()
+                .map_or(pc, |(address, _)| *address)
+        });
+        let end = start.saturating_add(LIST_LENGTH as u16 * 3);
+        let lines: Vec<String> = linear_sweep(&self.cpu.memory, start..=end)
+            .iter()
+            .take(LIST_LENGTH)
+            .map(|line| {
+                let breakpoint = self
+                    .cpu
+                    .breakpoints
+                    .iter()
+                    .any(|breakpoint| breakpoint.address == line.address);
+                let marker = match (line.address == pc, breakpoint) {
+                    (true, _) => "=>",
+                    (false, true) => " *",
+                    _ => "  ",
+                };
+                let label = self
+                    .symbols
+                    .get(&line.address)
+                    .map_or(String::new(), |label| format!("{}:", label));
+                format!(
+                    "{} {:04X}: {:<8} {}",
+                    marker,
+                    line.address,
+                    label,
+                    self.disassemble(line.address)
+                )
+            })
+            .collect();
+        lines.join("\n")
+    }
+
+    fn source(&self) -> String {
+        let Some((file, location)) = self.image.source_map.lookup(self.cpu.pc) else {
+            return format!("No source for {:04X}H", self.cpu.pc);
+        };
+        let Some(lines) = self.sources.get(file) else {
+            return format!("Can't read {}", file);
+        };
+        let first = location.line.saturating_sub(SOURCE_CONTEXT).max(1);
+        let last = (location.line + SOURCE_CONTEXT).min(lines.len());
+        let lines: Vec<String> = (first..=last)
+            .map(|line| {
+                let marker = if line == location.line { "=>" } else { "  " };
+                format!("{} {:>5} | {}", marker, line, lines[line - 1])
+            })
+            .collect();
+        lines.join("\n")
+    }
+}
+
+fn unknown_location(location: &str) -> String {
+    format!("Unknown location: {}", location)
+}
+
+#[cfg(test)]
+mod tests {
+    use emulator_8085::core::{
+        assembler::assemble, cpu::Cpu, history::History, parser::parse_source,
+    };
+
+    use super::Debugger;
+
+    #[test]
+    fn test_debugger() {
+        let source = r#"
+            LXI H, 2050H
+            MVI C, 02H
+            LOOP: CALL STORE
+            DCR C
+            JNZ LOOP
+            HLT
+            STORE: MOV M, C
+            INX H
+            RET
+        "#;
+        let image = assemble(&parse_source(source).unwrap());
+        let mut cpu = Cpu::new();
+        cpu.load_image(&image);
+        cpu.sp = 0x2400;
+        cpu.history = Some(History::new(100));
+        let mut debugger = Debugger::new(cpu, &image, 1000);
+
+        let mut run = |command: &str| debugger.command(command).unwrap();
+        assert_eq!(run("b LOOP"), "Breakpoint at 0005H (line 4:13)");
+        assert_eq!(run("c"), "Breakpoint\n0005H (line 4:13)  CALL STORE");
+        assert!(run("reg").starts_with("A=00 B=00 C=02 D=00 E=00 H=20 L=50"));
+        assert_eq!(run("next"), "0008H (line 5:13)  DCR C");
+        assert_eq!(run("mem 2050 2"), "2050: 02 00");
+        assert!(run("list").contains("=> 0008:"));
+        assert_eq!(run("d LOOP"), "Deleted the breakpoint at 0005H");
+        assert_eq!(
+            run("c"),
+            "Halted at 000CH (line 7:13) after 15 steps and 129 T-states"
+        );
+        assert_eq!(run("mem 2050 2"), "2050: 02 01");
+        assert!(run("s").starts_with("Halted"));
+        assert_eq!(run("back 2"), "0009H (line 6:13)  JNZ LOOP");
+        assert_eq!(run("mem 2050 2"), "2050: 02 01");
+        assert_eq!(run("back 4"), "000DH (line 8:13)  MOV M, C");
+        assert_eq!(run("mem 2050 2"), "2050: 02 00");
+        run("b LOOP");
+        assert_eq!(run("rc"), "Breakpoint\n0005H (line 4:13)  CALL STORE");
+        assert!(run("reg").contains("C=01"));
+        assert!(run("rc").starts_with("Breakpoint\n0005H"));
+        assert!(run("reg").contains("C=02"));
+        assert!(run("rc").starts_with("No more history\n0000H"));
+        run("d LOOP");
+        assert!(run("c").starts_with("Halted"));
+        assert_eq!(run("reg A=1FF"), "Invalid register value: A=1FF");
+        let registers = run("reg A=1F Z=1 AC=1");
+        assert!(registers.contains("A=1F") && registers.contains("Z=1 AC=1"));
+        assert_eq!(run("reg CY=2"), "Flags are 0 or 1: CY=2");
+        assert_eq!(run("mem 2050=AA,BB"), "2050: AA BB");
+        assert_eq!(run("reset"), "0000H (line 2:13)  LXI H, 2050H");
+        assert_eq!(run("until :5"), "0008H (line 5:13)  DCR C");
+
+        run("reset");
+        assert_eq!(
+            run("b LOOP if C == 1"),
+            "Breakpoint at 0005H (line 4:13) if C == 1H"
+        );
+        assert!(run("c").starts_with("Breakpoint\n0005H"));
+        assert!(run("reg").contains("C=01"));
+        assert_eq!(run("d LOOP"), "Deleted the breakpoint at 0005H");
+        assert_eq!(run("watch 2051 w"), "Watchpoint 1: write 2051H");
+        assert_eq!(
+            run("c"),
+            "Watchpoint on write to 2051H\n000EH (line 9:13)  INX H"
+        );
+        assert_eq!(run("unwatch 1"), "Deleted watchpoint 1: write 2051H");
+
+        let snapshot = std::env::temp_dir().join("debugger-test.snap");
+        let snapshot = snapshot.to_str().unwrap();
+        assert!(run(&format!("save {}", snapshot)).starts_with("Saved"));
+        assert!(run("c").starts_with("Halted"));
+        assert_eq!(
+            run(&format!("load {}", snapshot)),
+            "000EH (line 9:13)  INX H"
+        );
+        assert_eq!(run("mem 2050 2"), "2050: 02 01");
+        assert!(run("load missing.snap").starts_with("Couldn't read missing.snap"));
+        std::fs::remove_file(snapshot).ok();
+        assert!(run("c").starts_with("Halted"));
+        assert!(debugger.command("quit").is_none());
+    }
+}
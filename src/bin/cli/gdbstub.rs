@@ -0,0 +1,515 @@
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::ops::Range;
+
+use emulator_8085::core::{
+    breakpoints::{Access, Breakpoint, WatchKind, Watched, Watchpoint},
+    cpu::{Cpu, FlagRegisters, Stop, MEMORY_SIZE},
+    image::Image,
+};
+
+/// How many instructions `continue` executes between looking for an interrupt from GDB.
+const RUN_CHUNK_STEPS: u64 = 10_000;
+const INTERRUPT: u8 = 0x03;
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+/// The registers in `g` packet order: eight bytes, then SP and PC little endian.
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.emulator8085.core">
+    <reg name="a" bitsize="8" regnum="0"/>
+    <reg name="f" bitsize="8"/>
+    <reg name="b" bitsize="8"/>
+    <reg name="c" bitsize="8"/>
+    <reg name="d" bitsize="8"/>
+    <reg name="e" bitsize="8"/>
+    <reg name="h" bitsize="8"/>
+    <reg name="l" bitsize="8"/>
+    <reg name="sp" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+const REGISTER_COUNT: usize = 10;
+const REGISTERS_LENGTH: usize = 12;
+
+/// Serves one GDB remote serial protocol connection for a program.
+pub struct GdbStub<'a> {
+    image: &'a Image,
+    pub cpu: Cpu,
+}
+
+impl<'a> GdbStub<'a> {
+    pub fn new(cpu: Cpu, image: &'a Image) -> Self {
+        Self { image, cpu }
+    }
+
+    /// Answers packets until GDB detaches, kills the program or disconnects.
+    pub fn serve(&mut self, mut stream: TcpStream) -> io::Result<()> {
+        // Replies are small and GDB waits for each one
+        stream.set_nodelay(true)?;
+        let mut last = String::new();
+        while let Some(byte) = read_byte(&mut stream)? {
+            match byte {
+                b'$' => {}
+                b'-' => {
+                    send(&mut stream, &last)?;
+                    continue;
+                }
+                INTERRUPT => {
+                    last = format!("S{:02x}", SIGINT);
+                    send(&mut stream, &last)?;
+                    continue;
+                }
+                // Acknowledgements and noise between packets
+                _ => continue,
+            }
+
+            let mut data = Vec::new();
+            loop {
+                match read_byte(&mut stream)? {
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                    None => return Ok(()),
+                }
+            }
+            let mut checksum = [0; 2];
+            stream.read_exact(&mut checksum)?;
+            let expected = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+            if expected != Some(sum(&data)) {
+                stream.write_all(b"-")?;
+                continue;
+            }
+            stream.write_all(b"+")?;
+
+            let packet = String::from_utf8_lossy(&data).to_string();
+            match packet.as_str() {
+                "D" => return send(&mut stream, "OK"),
+                "k" => return Ok(()),
+                _ => {}
+            }
+            last = self.packet(&packet, &mut stream)?;
+            send(&mut stream, &last)?;
+        }
+        Ok(())
+    }
+
+    /// The reply to one packet; unsupported packets get the empty reply.
+    fn packet(&mut self, packet: &str, stream: &mut TcpStream) -> io::Result<String> {
+        let split = match packet.get(..1) {
+            Some(kind) => (kind, &packet[1..]),
+            None => ("", packet),
+        };
+        let reply = match split {
+            ("?", _) => format!("S{:02x}", SIGTRAP),
+            ("g", "") => hex(&self.registers()),
+            ("G", values) => reply_ok(self.set_registers(values)),
+            ("p", number) => usize::from_str_radix(number, 16)
+                .ok()
+                .and_then(|number| self.register(number))
+                .map_or("E01".to_string(), |value| hex(&value)),
+            ("P", assignment) => reply_ok(self.set_register(assignment)),
+            ("m", range) => match parse_memory_range(range) {
+                Some((address, length)) => {
+                    let bytes: Vec<u8> = (0..length)
+                        .map(|offset| self.cpu.read_memory(address as usize + offset))
+                        .collect();
+                    hex(&bytes)
+                }
+                None => "E01".to_string(),
+            },
+            ("M", write) => reply_ok(self.write_memory(write)),
+            ("Z", point) => reply_ok(self.insert_point(point)),
+            ("z", point) => reply_ok(self.remove_point(point)),
+            ("s", address) => {
+                self.jump(address);
+                let (stop, _) = self.cpu.run(self.image, 1);
+                stop_reply(stop)
+            }
+            ("c", address) => {
+                self.jump(address);
+                self.resume(stream)?
+            }
+            ("H", _) => "OK".to_string(),
+            _ => match packet {
+                "qAttached" => "1".to_string(),
+                "qC" => "QC1".to_string(),
+                "qfThreadInfo" => "m1".to_string(),
+                "qsThreadInfo" => "l".to_string(),
+                _ if packet.starts_with("qSupported") => {
+                    "PacketSize=1000;qXfer:features:read+".to_string()
+                }
+                _ => match packet.strip_prefix("qXfer:features:read:target.xml:") {
+                    Some(range) => match parse_range(range) {
+                        Some((offset, length)) => {
+                            let offset = offset.min(TARGET_XML.len());
+                            let end = offset.saturating_add(length).min(TARGET_XML.len());
+                            let more = if end < TARGET_XML.len() { 'm' } else { 'l' };
+                            format!("{}{}", more, &TARGET_XML[offset..end])
+                        }
+                        None => "E01".to_string(),
+                    },
+                    None => String::new(),
+                },
+            },
+        };
+        Ok(reply)
+    }
+
+    fn registers(&self) -> Vec<u8> {
+        let cpu = &self.cpu;
+        let mut bytes = vec![
+            cpu.accumulator,
+            cpu.flags.to_byte(),
+            cpu.b,
+            cpu.c,
+            cpu.d,
+            cpu.e,
+            cpu.h,
+            cpu.l,
+        ];
+        bytes.extend(cpu.sp.to_le_bytes());
+        bytes.extend(cpu.pc.to_le_bytes());
+        bytes
+    }
+
+    fn register(&self, number: usize) -> Option<Vec<u8>> {
+        register_bytes(number).map(|range| self.registers()[range].to_vec())
+    }
+
+    fn set_registers(&mut self, values: &str) -> Option<()> {
+        let bytes = parse_hex_bytes(values)?;
+        if bytes.len() != REGISTERS_LENGTH {
+            return None;
+        }
+        for number in 0..REGISTER_COUNT {
+            self.store_register(number, &bytes[register_bytes(number)?]);
+        }
+        Some(())
+    }
+
+    fn set_register(&mut self, assignment: &str) -> Option<()> {
+        let (number, value) = assignment.split_once('=')?;
+        let number = usize::from_str_radix(number, 16).ok()?;
+        let value = parse_hex_bytes(value)?;
+        if value.len() != register_bytes(number)?.len() {
+            return None;
+        }
+        self.store_register(number, &value);
+        Some(())
+    }
+
+    fn store_register(&mut self, number: usize, value: &[u8]) {
+        let cpu = &mut self.cpu;
+        match number {
+            0 => cpu.accumulator = value[0],
+            1 => cpu.flags = FlagRegisters::from_byte(value[0]),
+            2 => cpu.b = value[0],
+            3 => cpu.c = value[0],
+            4 => cpu.d = value[0],
+            5 => cpu.e = value[0],
+            6 => cpu.h = value[0],
+            7 => cpu.l = value[0],
+            8 => cpu.sp = u16::from_le_bytes([value[0], value[1]]),
+            _ => cpu.pc = u16::from_le_bytes([value[0], value[1]]),
+        }
+    }
+
+    /// `M<address>,<length>:<bytes>`
+    fn write_memory(&mut self, write: &str) -> Option<()> {
+        let (range, bytes) = write.split_once(':')?;
+        let (address, length) = parse_memory_range(range)?;
+        let bytes = parse_hex_bytes(bytes)?;
+        if bytes.len() != length {
+            return None;
+        }
+        for (offset, byte) in bytes.into_iter().enumerate() {
+            self.cpu.write_memory(address as usize + offset, byte);
+        }
+        Some(())
+    }
+
+    /// `Z<type>,<address>,<kind>`: types 0 and 1 are breakpoints, 2 to 4 write, read and access
+    /// watchpoints of `kind` bytes.
+    fn insert_point(&mut self, point: &str) -> Option<()> {
+        let (kind, address, length) = parse_point(point)?;
+        match kind {
+            0 | 1 => {
+                if !self.cpu.breakpoints.iter().any(|breakpoint| {
+                    breakpoint.address == address && breakpoint.condition.is_none()
+                }) {
+                    self.cpu.breakpoints.push(Breakpoint {
+                        address,
+                        condition: None,
+                    });
+                }
+            }
+            _ => self
+                .cpu
+                .watchpoints
+                .push(watchpoint(kind, address, length)?),
+        }
+        Some(())
+    }
+
+    fn remove_point(&mut self, point: &str) -> Option<()> {
+        let (kind, address, length) = parse_point(point)?;
+        match kind {
+            0 | 1 => self.cpu.breakpoints.retain(|breakpoint| {
+                breakpoint.address != address || breakpoint.condition.is_some()
+            }),
+            _ => {
+                let removed = watchpoint(kind, address, length)?;
+                self.cpu
+                    .watchpoints
+                    .retain(|watchpoint| *watchpoint != removed);
+            }
+        }
+        Some(())
+    }
+
+    /// Moves `pc` to the optional address of `s` and `c`.
+    fn jump(&mut self, address: &str) {
+        if let Ok(address) = u16::from_str_radix(address, 16) {
+            self.cpu.pc = address;
+        }
+    }
+
+    /// Runs until the program stops or GDB interrupts it.
+    fn resume(&mut self, stream: &mut TcpStream) -> io::Result<String> {
+        loop {
+            let (stop, _) = self.cpu.run(self.image, RUN_CHUNK_STEPS);
+            if stop != Stop::StepLimit {
+                return Ok(stop_reply(stop));
+            }
+            if interrupted(stream)? {
+                return Ok(format!("S{:02x}", SIGINT));
+            }
+        }
+    }
+}
+
+/// Where register `number` is in the `g` packet.
+fn register_bytes(number: usize) -> Option<Range<usize>> {
+    match number {
+        0..=7 => Some(number..number + 1),
+        8 | 9 => {
+            let start = 8 + (number - 8) * 2;
+            Some(start..start + 2)
+        }
+        _ => None,
+    }
+}
+
+/// Halting ends the program with exit code 0; faults stop it with SIGILL or SIGSEGV.
+fn stop_reply(stop: Stop) -> String {
+    match stop {
+        Stop::Halted => "W00".to_string(),
+        Stop::UndefinedOpcode(_) => format!("S{:02x}", SIGILL),
+        Stop::LeftProgram(_) => format!("S{:02x}", SIGSEGV),
+        Stop::StepLimit | Stop::Breakpoint(_) => format!("S{:02x}", SIGTRAP),
+        Stop::Watchpoint(Access::Write(address)) => {
+            format!("T{:02x}watch:{:04x};", SIGTRAP, address)
+        }
+        Stop::Watchpoint(Access::Read(address)) => {
+            format!("T{:02x}rwatch:{:04x};", SIGTRAP, address)
+        }
+        Stop::Watchpoint(_) => format!("S{:02x}", SIGTRAP),
+    }
+}
+
+fn watchpoint(kind: u8, address: u16, length: usize) -> Option<Watchpoint> {
+    let kind = match kind {
+        2 => WatchKind::Write,
+        3 => WatchKind::Read,
+        4 => WatchKind::ReadWrite,
+        _ => return None,
+    };
+    let end = address.checked_add(u16::try_from(length.max(1) - 1).ok()?)?;
+    Some(Watchpoint {
+        watched: Watched::Memory(address, end),
+        kind,
+    })
+}
+
+fn parse_point(point: &str) -> Option<(u8, u16, usize)> {
+    let mut fields = point.split(',');
+    let kind = fields.next()?.parse().ok()?;
+    let address = u16::from_str_radix(fields.next()?, 16).ok()?;
+    let length = usize::from_str_radix(fields.next()?, 16).ok()?;
+    Some((kind, address, length))
+}
+
+/// `<address>,<length>` in hex.
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let (address, length) = range.split_once(',')?;
+    Some((
+        usize::from_str_radix(address, 16).ok()?,
+        usize::from_str_radix(length, 16).ok()?,
+    ))
+}
+
+/// `<address>,<length>` of memory in hex, which must end within 64K.
+fn parse_memory_range(range: &str) -> Option<(u16, usize)> {
+    let (address, length) = range.split_once(',')?;
+    let address = u16::from_str_radix(address, 16).ok()?;
+    let length = usize::from_str_radix(length, 16).ok()?;
+    (address as usize)
+        .checked_add(length)
+        .filter(|end| *end <= MEMORY_SIZE)
+        .map(|_| (address, length))
+}
+
+fn parse_hex_bytes(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn reply_ok(result: Option<()>) -> String {
+    match result {
+        Some(()) => "OK".to_string(),
+        None => "E01".to_string(),
+    }
+}
+
+fn sum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn send(stream: &mut TcpStream, reply: &str) -> io::Result<()> {
+    stream.write_all(format!("${}#{:02x}", reply, sum(reply.as_bytes())).as_bytes())
+}
+
+fn read_byte(stream: &mut TcpStream) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    match stream.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+/// Whether GDB sent an interrupt while the program was running, leaving anything else unread.
+fn interrupted(stream: &mut TcpStream) -> io::Result<bool> {
+    stream.set_nonblocking(true)?;
+    let mut byte = [0];
+    let pending = match stream.peek(&mut byte) {
+        Ok(1) => byte[0] == INTERRUPT,
+        Ok(_) => false,
+        Err(err) if err.kind() == io::ErrorKind::WouldBlock => false,
+        Err(err) => return Err(err),
+    };
+    stream.set_nonblocking(false)?;
+    if pending {
+        stream.read_exact(&mut byte)?;
+    }
+    Ok(pending)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    use emulator_8085::core::{assembler::assemble, cpu::Cpu, parser::parse_source};
+
+    use super::{sum, GdbStub};
+
+    /// Sends a packet and returns the reply, checking the acknowledgement and checksum.
+    fn request(stream: &mut TcpStream, packet: &str) -> String {
+        write!(stream, "${}#{:02x}", packet, sum(packet.as_bytes())).unwrap();
+        let mut reply = Vec::new();
+        let mut byte = [0];
+        stream.read_exact(&mut byte).unwrap();
+        assert_eq!(byte[0], b'+');
+        stream.read_exact(&mut byte).unwrap();
+        assert_eq!(byte[0], b'$');
+        loop {
+            stream.read_exact(&mut byte).unwrap();
+            if byte[0] == b'#' {
+                break;
+            }
+            reply.push(byte[0]);
+        }
+        let mut checksum = [0; 2];
+        stream.read_exact(&mut checksum).unwrap();
+        let checksum = u8::from_str_radix(std::str::from_utf8(&checksum).unwrap(), 16).unwrap();
+        assert_eq!(checksum, sum(&reply));
+        String::from_utf8(reply).unwrap()
+    }
+
+    #[test]
+    fn test_gdbstub() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let source = r#"
+                LXI SP, 2400H
+                MVI C, 02H
+                LOOP: DCR C
+                STA 2050H
+                JNZ LOOP
+                HLT
+            "#;
+            let image = assemble(&parse_source(source).unwrap());
+            let mut cpu = Cpu::new();
+            cpu.load_image(&image);
+            let mut stub = GdbStub::new(cpu, &image);
+            let (stream, _) = listener.accept().unwrap();
+            stub.serve(stream).unwrap();
+            stub.cpu.pc
+        });
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.set_nodelay(true).unwrap();
+        assert!(request(&mut stream, "qSupported:swbreak+").contains("qXfer:features:read+"));
+        assert!(request(&mut stream, "qXfer:features:read:target.xml:0,20").starts_with("m<?xml"));
+        assert_eq!(request(&mut stream, "?"), "S05");
+        assert_eq!(request(&mut stream, "g"), "000200000000000000000000");
+        assert_eq!(request(&mut stream, "s"), "S05");
+        assert_eq!(request(&mut stream, "p8"), "0024");
+        assert_eq!(request(&mut stream, "p9"), "0300");
+
+        // Break at the second DCR C
+        assert_eq!(request(&mut stream, "Z0,5,1"), "OK");
+        assert_eq!(request(&mut stream, "c"), "S05");
+        assert_eq!(request(&mut stream, "c"), "S05");
+        assert_eq!(request(&mut stream, "p3"), "01");
+        assert_eq!(request(&mut stream, "z0,5,1"), "OK");
+
+        assert_eq!(request(&mut stream, "Z2,2050,1"), "OK");
+        assert_eq!(request(&mut stream, "c"), "T05watch:2050;");
+        assert_eq!(request(&mut stream, "m2050,2"), "0000");
+        assert_eq!(request(&mut stream, "z2,2050,1"), "OK");
+
+        assert_eq!(request(&mut stream, "M2060,2:abcd"), "OK");
+        assert_eq!(request(&mut stream, "m2060,2"), "abcd");
+        assert_eq!(request(&mut stream, "mfffe,2"), "0000");
+        assert_eq!(request(&mut stream, "mffff,2"), "E01");
+        assert_eq!(request(&mut stream, "m10000,1"), "E01");
+        assert_eq!(request(&mut stream, "mffffffffffffffff,1"), "E01");
+        assert_eq!(request(&mut stream, "Mffff,2:abcd"), "E01");
+        assert_eq!(request(&mut stream, "P0=7f"), "OK");
+        assert_eq!(request(&mut stream, "p0"), "7f");
+        assert_eq!(request(&mut stream, "Pa=00"), "E01");
+        assert_eq!(request(&mut stream, "vMustReplyEmpty"), "");
+
+        assert_eq!(request(&mut stream, "c"), "W00");
+        assert_eq!(request(&mut stream, "D"), "OK");
+        assert_eq!(server.join().unwrap(), 0x000D);
+    }
+}
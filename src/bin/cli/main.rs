@@ -0,0 +1,887 @@
+mod debugger;
+mod gdbstub;
+
+use std::io::Write;
+use std::net::TcpListener;
+use std::process::exit;
+
+use emulator_8085::core::{
+    assembler::{assemble, assemble_object},
+    breakpoints::{Operand, REGISTER_NAMES},
+    coverage::{Coverage, CoverageFormat},
+    cpu::{Cpu, Stop, MEMORY_SIZE},
+    disassembler::{linear_sweep, listing, recursive_descent, symbols_by_address},
+    formats::{
+        binary::to_binary,
+        hex_dump::{self, to_hex_dump},
+        intel_hex::{self, read_intel_hex, to_intel_hex},
+        srec::{self, to_srec},
+        symbols::{read_symbol_file, to_noice, to_symbol_file},
+        OutputFormat,
+    },
+    formatter::format_source,
+    grader::{grade, read_spec},
+    harness::run_annotated,
+    history::{self, History},
+    image::Image,
+    linker::{link, to_map_file},
+    lint::{lint, LintCode},
+    object::{read_object_file, to_object_file, Object},
+    parser::{parse_source, Instruction},
+    profile::Profiler,
+    snapshot::{restore_snapshot, to_snapshot},
+    trace::{TraceFormat, Tracer},
+};
+
+use debugger::Debugger;
+use gdbstub::GdbStub;
+
+/// Exit codes of `run` and `debug`; any other error exits with 1.
+const EXIT_FAULT: i32 = 2;
+const EXIT_TIMEOUT: i32 = 3;
+
+const DEFAULT_MAX_STEPS: u64 = 1_000_000;
+const DUMP_BYTES_PER_LINE: usize = 16;
+/// How many instructions `run` executes between writing trace entries out.
+const TRACE_CHUNK_STEPS: u64 = 10_000;
+const DEFAULT_GDB_PORT: u16 = 1234;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Command {
+    Assemble,
+    Run,
+    Disasm,
+    Debug,
+    Gdb,
+}
+
+/// What `run` prints once the program stops.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Dump {
+    Registers,
+    /// Every non-zero byte of memory.
+    Memory,
+    Range(u16, u16),
+}
+
+struct Options {
+    files: Vec<String>,
+    format: Option<OutputFormat>,
+    output: Option<String>,
+    base: Option<u16>,
+    fill: u8,
+    record_length: Option<usize>,
+    sections: Vec<(String, u16)>,
+    map: Option<String>,
+    symbols: Option<String>,
+    noice: Option<String>,
+    allowed: Vec<LintCode>,
+    preloads: Vec<(u16, Vec<u8>)>,
+    registers: Vec<(String, u16)>,
+    max_steps: u64,
+    dumps: Option<Vec<Dump>>,
+    entries: Vec<u16>,
+    trace: Option<String>,
+    trace_format: TraceFormat,
+    trace_ranges: Vec<(u16, u16)>,
+    coverage: Option<String>,
+    coverage_format: CoverageFormat,
+    profile: Option<String>,
+    history: usize,
+    restore: Option<String>,
+    snapshot: Option<String>,
+    port: u16,
+}
+
+fn usage(program: &str) -> ! {
+    println!(
+        "Usage: {} assemble <file> [--format hex|bin|srec|dump|obj] [-o <output>] [--base <address>] [--fill <byte>] [--record-length <n>] [--symbols <file>] [--noice <file>] [--allow <lint>|all]",
+        program
+    );
+    println!(
+        "       {} run <file> [--mem <address>=<byte>[,<byte>...]] [--reg <register>=<value>] [--max-steps <n>] [--dump registers|memory|<start>-<end>|none] [--trace <file>] [--trace-format text|json] [--trace-range <start>-<end>] [--restore <file>] [--snapshot <file>] [--coverage <file>] [--coverage-format text|html|lcov] [--profile <file>]",
+        program
+    );
+    println!(
+        "       {} disasm <file.hex|file.bin> [--base <address>] [--entry <address>] [--symbols <file>] [-o <output>]",
+        program
+    );
+    println!(
+        "       {} debug <file> [--mem <address>=<byte>[,<byte>...]] [--reg <register>=<value>] [--history <n>] [--trace <file>] [--trace-format text|json] [--trace-range <start>-<end>] [--restore <file>]",
+        program
+    );
+    println!(
+        "       {} gdb <file> [--port <n>] [--mem <address>=<byte>[,<byte>...]] [--reg <register>=<value>] [--restore <file>]",
+        program
+    );
+    println!(
+        "       {} link <file>... [--section <name>=<address>] [--map <file>] [--symbols <file>] [--noice <file>] [--format hex|bin|srec|dump] [-o <output>]",
+        program
+    );
+    println!("       {} fmt <file>... [--write] [--check]", program);
+    println!("       {} test <file>...", program);
+    println!("       {} grade <spec.json> <file>...", program);
+    exit(1);
+}
+
+fn parse_hex(value: &str) -> Option<u16> {
+    u16::from_str_radix(value.trim_end_matches(['H', 'h']), 16).ok()
+}
+
+fn parse_byte(value: &str) -> Option<u8> {
+    parse_hex(value).and_then(|byte| u8::try_from(byte).ok())
+}
+
+fn parse_dump(value: &str) -> Option<Dump> {
+    match value {
+        "registers" | "regs" => Some(Dump::Registers),
+        "memory" | "mem" => Some(Dump::Memory),
+        range => {
+            let (start, end) = range.split_once('-')?;
+            let (start, end) = (parse_hex(start)?, parse_hex(end)?);
+            (start <= end).then_some(Dump::Range(start, end))
+        }
+    }
+}
+
+fn parse_options(args: &[String]) -> Options {
+    let mut options = Options {
+        files: Vec::new(),
+        format: None,
+        output: None,
+        base: None,
+        fill: 0xFF,
+        record_length: None,
+        sections: Vec::new(),
+        map: None,
+        symbols: None,
+        noice: None,
+        allowed: Vec::new(),
+        preloads: Vec::new(),
+        registers: Vec::new(),
+        max_steps: DEFAULT_MAX_STEPS,
+        dumps: None,
+        entries: Vec::new(),
+        trace: None,
+        trace_format: TraceFormat::Text,
+        trace_ranges: Vec::new(),
+        coverage: None,
+        coverage_format: CoverageFormat::Text,
+        profile: None,
+        history: history::DEFAULT_CAPACITY,
+        restore: None,
+        snapshot: None,
+        port: DEFAULT_GDB_PORT,
+    };
+    let program = &args[0];
+    let mut args = args.iter().skip(1);
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().unwrap_or_else(|| usage(program));
+        match arg.as_str() {
+            "--format" | "-f" => {
+                let name = value();
+                options.format = Some(OutputFormat::from(&name).unwrap_or_else(|| {
+                    eprintln!("Unknown output format: {}", name);
+                    exit(1);
+                }));
+            }
+            "--output" | "-o" => options.output = Some(value()),
+            "--base" => options.base = Some(parse_hex(&value()).unwrap_or_else(|| usage(program))),
+            "--fill" => options.fill = parse_byte(&value()).unwrap_or_else(|| usage(program)),
+            "--record-length" => {
+                options.record_length = Some(value().parse().unwrap_or_else(|_| usage(program)))
+            }
+            "--section" => {
+                let value = value();
+                let (name, address) = value.split_once('=').unwrap_or_else(|| usage(program));
+                let address = parse_hex(address).unwrap_or_else(|| usage(program));
+                options.sections.push((name.to_string(), address));
+            }
+            "--map" => options.map = Some(value()),
+            "--symbols" => options.symbols = Some(value()),
+            "--noice" => options.noice = Some(value()),
+            "--allow" => {
+                let name = value();
+                if name == "all" {
+                    options.allowed = LintCode::ALL.to_vec();
+                } else {
+                    options
+                        .allowed
+                        .push(LintCode::from(&name).unwrap_or_else(|| {
+                            eprintln!("Unknown lint: {}", name);
+                            exit(1);
+                        }));
+                }
+            }
+            "--mem" => {
+                let value = value();
+                let (address, bytes) = value.split_once('=').unwrap_or_else(|| usage(program));
+                let address = parse_hex(address).unwrap_or_else(|| usage(program));
+                let bytes = bytes
+                    .split(',')
+                    .map(parse_byte)
+                    .collect::<Option<Vec<u8>>>()
+                    .unwrap_or_else(|| usage(program));
+                options.preloads.push((address, bytes));
+            }
+            "--reg" => {
+                let value = value();
+                let (name, number) = value.split_once('=').unwrap_or_else(|| usage(program));
+                let name = name.to_uppercase();
+                let number = parse_hex(number).unwrap_or_else(|| usage(program));
+                if !REGISTER_NAMES.contains(&name.as_str()) || (name.len() == 1 && number > 0xFF) {
+                    eprintln!("Invalid register value: {}", value);
+                    exit(1);
+                }
+                options.registers.push((name, number));
+            }
+            "--max-steps" => options.max_steps = value().parse().unwrap_or_else(|_| usage(program)),
+            "--dump" => {
+                let value = value();
+                let dumps = options.dumps.get_or_insert_with(Vec::new);
+                if value != "none" {
+                    dumps.push(parse_dump(&value).unwrap_or_else(|| {
+                        eprintln!("Unknown dump: {}", value);
+                        exit(1);
+                    }));
+                }
+            }
+            "--history" => options.history = value().parse().unwrap_or_else(|_| usage(program)),
+            "--restore" => options.restore = Some(value()),
+            "--snapshot" => options.snapshot = Some(value()),
+            "--port" => options.port = value().parse().unwrap_or_else(|_| usage(program)),
+            "--trace" => options.trace = Some(value()),
+            "--trace-format" => {
+                let name = value();
+                options.trace_format = TraceFormat::from(&name).unwrap_or_else(|| {
+                    eprintln!("Unknown trace format: {}", name);
+                    exit(1);
+                });
+            }
+            "--trace-range" => {
+                let value = value();
+                let (start, end) = value.split_once('-').unwrap_or((&value, &value));
+                match (parse_hex(start), parse_hex(end)) {
+                    (Some(start), Some(end)) if start <= end => {
+                        options.trace_ranges.push((start, end))
+                    }
+                    _ => usage(program),
+                }
+            }
+            "--coverage" => options.coverage = Some(value()),
+            "--coverage-format" => {
+                let name = value();
+                options.coverage_format = CoverageFormat::from(&name).unwrap_or_else(|| {
+                    eprintln!("Unknown coverage format: {}", name);
+                    exit(1);
+                });
+            }
+            "--profile" => options.profile = Some(value()),
+            "--entry" => options
+                .entries
+                .push(parse_hex(&value()).unwrap_or_else(|| usage(program))),
+            file if !file.starts_with('-') => options.files.push(file.to_string()),
+            _ => usage(program),
+        }
+    }
+
+    if options.files.is_empty() {
+        usage(program);
+    }
+    options
+}
+
+fn write_file(path: &Option<String>, output: &[u8]) {
+    let result = match path {
+        Some(path) => std::fs::write(path, output),
+        None => std::io::stdout().write_all(output),
+    };
+    if let Err(err) = result {
+        eprintln!("Couldn't write output: {}", err);
+        exit(1);
+    }
+}
+
+fn write_output(options: &Options, image: &Image) {
+    let output = match options.format {
+        Some(OutputFormat::IntelHex) => to_intel_hex(
+            image,
+            options
+                .record_length
+                .unwrap_or(intel_hex::DEFAULT_RECORD_LENGTH),
+            Some(image.entry),
+        )
+        .into_bytes(),
+        Some(OutputFormat::Binary) => to_binary(
+            image,
+            options.base.unwrap_or(image.lowest_address().unwrap_or(0)),
+            options.fill,
+        ),
+        Some(OutputFormat::SRecord) => to_srec(
+            image,
+            options.record_length.unwrap_or(srec::DEFAULT_RECORD_LENGTH),
+            &options.files[0],
+        )
+        .into_bytes(),
+        Some(OutputFormat::HexDump) => to_hex_dump(
+            image,
+            options
+                .record_length
+                .unwrap_or(hex_dump::DEFAULT_BYTES_PER_LINE),
+        )
+        .into_bytes(),
+        Some(OutputFormat::Object) | None => return,
+    };
+    write_file(&options.output, &output);
+}
+
+/// Writes the symbol files asked for, naming `file` as the source of symbols with a line.
+fn write_symbols(options: &Options, image: &Image, file: Option<&str>) {
+    if let Some(path) = &options.symbols {
+        write_file(&Some(path.clone()), to_symbol_file(image, file).as_bytes());
+    }
+    if let Some(path) = &options.noice {
+        write_file(&Some(path.clone()), to_noice(image, file).as_bytes());
+    }
+}
+
+fn read_source(file: &str) -> String {
+    std::fs::read_to_string(file).unwrap_or_else(|err| {
+        eprintln!("Couldn't read {}: {}", file, err);
+        exit(1);
+    })
+}
+
+fn parse_file(source: &str) -> Vec<Instruction<'_>> {
+    parse_source(source).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        exit(1);
+    })
+}
+
+/// Reads an object file, or assembles a source file into an object.
+fn load_object(file: &str, allowed: &[LintCode]) -> Object {
+    let source = read_source(file);
+    let object = if file.ends_with(".obj") {
+        read_object_file(&source)
+    } else {
+        let instructions = parse_file(&source);
+        for warning in lint(&instructions, allowed) {
+            eprintln!("{}: {}", file, warning);
+        }
+        assemble_object(&instructions, file)
+    };
+    object.unwrap_or_else(|err| {
+        eprintln!("{}: {}", file, err);
+        exit(1);
+    })
+}
+
+fn link_objects(args: &[String]) {
+    let mut options = parse_options(args);
+    let objects: Vec<Object> = options
+        .files
+        .iter()
+        .map(|file| load_object(file, &options.allowed))
+        .collect();
+    let (image, map) = link(&objects, &options.sections).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        exit(1);
+    });
+
+    if let Some(path) = &options.map {
+        write_file(&Some(path.clone()), to_map_file(&map).as_bytes());
+    }
+    write_symbols(&options, &image, None);
+    if matches!(options.format, None | Some(OutputFormat::Object)) {
+        options.format = Some(OutputFormat::IntelHex);
+    }
+    write_output(&options, &image);
+}
+
+/// Prints the formatted files, rewrites them with `--write`, or with `--check` fails when any of
+/// them isn't formatted.
+fn format_files(args: &[String]) {
+    let (mut write, mut check, mut files) = (false, false, Vec::new());
+    for arg in &args[1..] {
+        match arg.as_str() {
+            "--write" | "-w" => write = true,
+            "--check" => check = true,
+            file if !file.starts_with('-') => files.push(file),
+            _ => usage(&args[0]),
+        }
+    }
+    if files.is_empty() {
+        usage(&args[0]);
+    }
+
+    let mut unformatted = false;
+    for file in files {
+        let source = read_source(file);
+        let formatted = format_source(&source).unwrap_or_else(|err| {
+            eprintln!("{}: {}", file, err);
+            exit(1);
+        });
+        if check {
+            if formatted != source {
+                println!("{} isn't formatted", file);
+                unformatted = true;
+            }
+        } else if write {
+            write_file(&Some(file.to_string()), formatted.as_bytes());
+        } else {
+            print!("{}", formatted);
+        }
+    }
+    if unformatted {
+        exit(1);
+    }
+}
+
+/// Runs programs against their `;@` annotations and reports each, failing when any of them
+/// doesn't pass.
+fn test_files(args: &[String]) {
+    let files = &args[1..];
+    if files.is_empty() || files.iter().any(|file| file.starts_with('-')) {
+        usage(&args[0]);
+    }
+
+    let mut failed = 0;
+    for file in files {
+        match run_annotated(&read_source(file)) {
+            Ok(report) if report.passed() => println!(
+                "PASS {} ({} checks, {} steps)",
+                file, report.checks, report.steps
+            ),
+            Ok(report) => {
+                failed += 1;
+                println!("FAIL {}", file);
+                for failure in &report.failures {
+                    println!("    {}", failure);
+                }
+            }
+            Err(err) => {
+                failed += 1;
+                println!("FAIL {}", file);
+                println!("    {}", err);
+            }
+        }
+    }
+    println!("{} passed, {} failed", files.len() - failed, failed);
+    if failed > 0 {
+        exit(1);
+    }
+}
+
+/// Grades submissions against a spec, printing each case and a score per submission. Fails when
+/// any submission misses a point.
+fn grade_files(args: &[String]) {
+    let (spec_file, files) = match &args[1..] {
+        [spec, files @ ..] if !files.is_empty() => (spec, files),
+        _ => usage(&args[0]),
+    };
+    let spec = read_spec(&read_source(spec_file)).unwrap_or_else(|err| {
+        eprintln!("{}: {}", spec_file, err);
+        exit(1);
+    });
+
+    let total = spec.total();
+    let mut perfect = true;
+    for file in files {
+        match &spec.name {
+            Some(name) => println!("{}: {}", file, name),
+            None => println!("{}", file),
+        }
+        let grade = grade(&read_source(file), &spec);
+        if let Some(err) = &grade.error {
+            println!("    {}", err);
+        }
+        for result in &grade.results {
+            if result.passed() {
+                println!(
+                    "    PASS {} ({}/{}, {} steps, {} T-states)",
+                    result.name, result.points, result.points, result.steps, result.cycles
+                );
+            } else {
+                println!("    FAIL {} (0/{})", result.name, result.points);
+                // A submission that doesn't assemble has its error printed once above.
+                if grade.error.is_none() {
+                    for failure in &result.failures {
+                        println!("        {}", failure);
+                    }
+                }
+            }
+        }
+        let score = grade.score();
+        perfect &= score == total;
+        println!("    Score: {}/{}", score, total);
+    }
+    if !perfect {
+        exit(1);
+    }
+}
+
+fn assemble_file(options: &mut Options) {
+    if options.files.len() > 1 {
+        eprintln!("Expected one file, got {}", options.files.len());
+        exit(1);
+    }
+    let file = &options.files[0];
+    let source = read_source(file);
+    let instructions = parse_file(&source);
+    for warning in lint(&instructions, &options.allowed) {
+        eprintln!("{}: {}", file, warning);
+    }
+
+    if options.format == Some(OutputFormat::Object) {
+        let object = assemble_object(&instructions, file).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            exit(1);
+        });
+        write_file(&options.output, to_object_file(&object).as_bytes());
+        return;
+    }
+
+    let mut image = assemble(&instructions);
+    image.source_map.name_source(file);
+    write_symbols(options, &image, Some(file));
+    if options.format.is_none() {
+        options.format = Some(OutputFormat::IntelHex);
+    }
+    write_output(options, &image);
+}
+
+/// Loads an Intel HEX file, a raw binary at `--base` or assembles a source file.
+fn load_program(options: &Options) -> Image {
+    if options.files.len() > 1 {
+        eprintln!("Expected one file, got {}", options.files.len());
+        exit(1);
+    }
+    let file = &options.files[0];
+    let lower = file.to_lowercase();
+
+    if lower.ends_with(".bin") {
+        let bytes = std::fs::read(file).unwrap_or_else(|err| {
+            eprintln!("Couldn't read {}: {}", file, err);
+            exit(1);
+        });
+        let base = options.base.unwrap_or(0);
+        if base as usize + bytes.len() > MEMORY_SIZE {
+            eprintln!("{} doesn't fit in memory at {:04X}H", file, base);
+            exit(1);
+        }
+        let mut image = Image::new();
+        image.push(base, &bytes);
+        image.entry = base;
+        return image;
+    }
+    let source = read_source(file);
+    if lower.ends_with(".hex") || lower.ends_with(".ihex") {
+        return read_intel_hex(&source).unwrap_or_else(|err| {
+            eprintln!("{}: {}", file, err);
+            exit(1);
+        });
+    }
+
+    let instructions = parse_file(&source);
+    for warning in lint(&instructions, &options.allowed) {
+        eprintln!("{}: {}", file, warning);
+    }
+    let mut image = assemble(&instructions);
+    image.source_map.name_source(file);
+    image
+}
+
+/// A CPU with `image` loaded, the `--restore` snapshot over it and the `--mem` and `--reg` values
+/// applied.
+fn prepare_cpu(options: &Options, image: &Image) -> Cpu {
+    let mut cpu = Cpu::new();
+    cpu.load_image(image);
+    if let Some(path) = &options.restore {
+        restore_snapshot(&mut cpu, &read_source(path)).unwrap_or_else(|err| {
+            eprintln!("{}: {}", path, err);
+            exit(1);
+        });
+    }
+    for (address, bytes) in &options.preloads {
+        for (offset, byte) in bytes.iter().enumerate() {
+            cpu.write_memory((*address as usize + offset) % MEMORY_SIZE, *byte);
+        }
+    }
+    for (name, value) in &options.registers {
+        set_register(&mut cpu, name, *value);
+    }
+    cpu
+}
+
+/// Sets one of `REGISTER_NAMES`; single registers take the low byte of `value`.
+fn set_register(cpu: &mut Cpu, name: &str, value: u16) {
+    if let Some(operand) = Operand::parse(name) {
+        operand.set(cpu, value);
+    }
+}
+
+/// The file `--trace` writes the entries of the CPU's tracer to.
+struct TraceOutput {
+    file: std::fs::File,
+    format: TraceFormat,
+}
+
+impl TraceOutput {
+    /// Creates the trace file, if one was asked for, and starts tracing on `cpu`.
+    fn open(options: &Options, cpu: &mut Cpu) -> Option<Self> {
+        let path = options.trace.as_ref()?;
+        let file = std::fs::File::create(path).unwrap_or_else(|err| {
+            eprintln!("Couldn't create {}: {}", path, err);
+            exit(1);
+        });
+        cpu.tracer = Some(Tracer::new(options.trace_ranges.clone()));
+        Some(Self {
+            file,
+            format: options.trace_format,
+        })
+    }
+
+    fn flush(&mut self, cpu: &mut Cpu) {
+        let Some(tracer) = cpu.tracer.as_mut() else {
+            return;
+        };
+        if let Err(err) = self.file.write_all(tracer.take(self.format).as_bytes()) {
+            eprintln!("Couldn't write the trace: {}", err);
+            exit(1);
+        }
+    }
+}
+
+fn format_range(cpu: &Cpu, start: u16, end: u16) -> String {
+    let mut output = String::new();
+    for line in (start as usize..=end as usize).step_by(DUMP_BYTES_PER_LINE) {
+        let bytes: Vec<String> = (line..=(line + DUMP_BYTES_PER_LINE - 1).min(end as usize))
+            .map(|address| format!("{:02X}", cpu.read_memory(address)))
+            .collect();
+        output.push_str(&format!("{:04X}: {}\n", line, bytes.join(" ")));
+    }
+    output
+}
+
+/// Describes why the run stopped along with the exit code for it.
+fn describe_stop(cpu: &Cpu, image: &Image, stop: Stop) -> (String, i32) {
+    let code = match stop {
+        Stop::UndefinedOpcode(_) | Stop::LeftProgram(_) => EXIT_FAULT,
+        Stop::StepLimit => EXIT_TIMEOUT,
+        Stop::Halted | Stop::Breakpoint(_) | Stop::Watchpoint(_) => 0,
+    };
+    (stop.describe(cpu, image), code)
+}
+
+fn report(cpu: &Cpu, image: &Image, stop: Stop, steps: u64) -> i32 {
+    let (message, code) = describe_stop(cpu, image, stop);
+    eprintln!(
+        "{} after {} steps and {} T-states",
+        message, steps, cpu.cycles
+    );
+    code
+}
+
+fn run_program(options: &Options) {
+    let image = load_program(options);
+    let mut cpu = prepare_cpu(options, &image);
+    let mut trace = TraceOutput::open(options, &mut cpu);
+    if options.coverage.is_some() {
+        cpu.coverage = Some(Coverage::new());
+    }
+    if options.profile.is_some() {
+        cpu.profiler = Some(Profiler::new());
+    }
+    let (mut stop, mut steps) = (Stop::StepLimit, 0);
+    while steps < options.max_steps {
+        let chunk = (options.max_steps - steps).min(TRACE_CHUNK_STEPS);
+        let count;
+        (stop, count) = cpu.run(&image, chunk);
+        steps += count;
+        if let Some(trace) = trace.as_mut() {
+            trace.flush(&mut cpu);
+        }
+        if stop != Stop::StepLimit {
+            break;
+        }
+    }
+    if options.snapshot.is_some() {
+        write_file(&options.snapshot, to_snapshot(&cpu).as_bytes());
+    }
+    if let Some(coverage) = cpu.coverage.take() {
+        let sources: Vec<String> = image
+            .source_map
+            .files
+            .iter()
+            .map(|file| read_source(file))
+            .collect();
+        let report = coverage.format(options.coverage_format, &image.source_map, &sources);
+        write_file(&options.coverage, report.as_bytes());
+    }
+    if let Some(profiler) = cpu.profiler.take() {
+        write_file(&options.profile, profiler.report(&image).as_bytes());
+    }
+
+    let dumps = options
+        .dumps
+        .clone()
+        .unwrap_or(vec![Dump::Memory, Dump::Registers]);
+    for dump in dumps {
+        match dump {
+            Dump::Registers => cpu.print(),
+            Dump::Memory => cpu.print_memory(),
+            Dump::Range(start, end) => print!("{}", format_range(&cpu, start, end)),
+        }
+    }
+    exit(report(&cpu, &image, stop, steps));
+}
+
+/// Reads debugger commands from stdin until `quit` or the end of the input, then exits with the
+/// code of how the program ended.
+fn debug_program(options: &Options) {
+    let image = load_program(options);
+    let mut cpu = prepare_cpu(options, &image);
+    cpu.history = Some(History::new(options.history));
+    let mut trace = TraceOutput::open(options, &mut cpu);
+    let mut debugger = Debugger::new(cpu, &image, options.max_steps);
+    println!("{}", debugger.location());
+
+    let mut last = String::new();
+    loop {
+        print!("(8085) ");
+        std::io::stdout().flush().ok();
+        let mut line = String::new();
+        if std::io::stdin().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        if line.trim().is_empty() {
+            line = last.clone();
+        }
+        let output = debugger.command(&line);
+        if let Some(trace) = trace.as_mut() {
+            trace.flush(&mut debugger.cpu);
+        }
+        match output {
+            Some(output) => println!("{}", output),
+            None => break,
+        }
+        last = line;
+    }
+    let code = debugger
+        .stop
+        .map_or(0, |stop| describe_stop(&debugger.cpu, &image, stop).1);
+    exit(code);
+}
+
+/// Waits for one GDB connection on localhost and lets it debug the program.
+fn serve_gdb(options: &Options) {
+    let image = load_program(options);
+    let cpu = prepare_cpu(options, &image);
+    let listener = TcpListener::bind(("127.0.0.1", options.port)).unwrap_or_else(|err| {
+        eprintln!("Couldn't listen on port {}: {}", options.port, err);
+        exit(1);
+    });
+    eprintln!("Waiting for GDB on 127.0.0.1:{}", options.port);
+    let result = listener
+        .accept()
+        .and_then(|(stream, _)| GdbStub::new(cpu, &image).serve(stream));
+    if let Err(err) = result {
+        eprintln!("GDB connection failed: {}", err);
+        exit(1);
+    }
+}
+
+fn disassemble(options: &Options) {
+    let image = load_program(options);
+    let mut memory = vec![0; MEMORY_SIZE];
+    for (address, byte) in image.bytes() {
+        memory[address as usize] = byte;
+    }
+
+    let mut names = image.symbols.clone();
+    if let Some(path) = &options.symbols {
+        let entries = read_symbol_file(&read_source(path)).unwrap_or_else(|err| {
+            eprintln!("{}: {}", path, err);
+            exit(1);
+        });
+        names.extend(entries.into_iter().map(|entry| (entry.name, entry.address)));
+    }
+    let symbols = symbols_by_address(&names);
+
+    let mut lines = Vec::new();
+    if options.entries.is_empty() {
+        for segment in &image.segments {
+            if segment.bytes.is_empty() {
+                continue;
+            }
+            let end = segment.address as usize + segment.bytes.len() - 1;
+            lines.extend(linear_sweep(&memory, segment.address..=end as u16));
+        }
+    } else {
+        let start = image.segments.iter().map(|segment| segment.address).min();
+        let end = image
+            .segments
+            .iter()
+            .map(|segment| segment.address as usize + segment.bytes.len().max(1) - 1)
+            .max();
+        if let (Some(start), Some(end)) = (start, end) {
+            lines = recursive_descent(&memory, &options.entries, start..=end as u16);
+        }
+    }
+    write_file(&options.output, listing(&lines, &symbols).as_bytes());
+}
+
+pub fn main() {
+    let mut args: Vec<String> = std::env::args().collect();
+
+    if args.len() < 2 {
+        usage(&args[0]);
+    }
+    let command = match args[1].as_str() {
+        "link" => {
+            args.remove(1);
+            link_objects(&args);
+            return;
+        }
+        "fmt" => {
+            args.remove(1);
+            format_files(&args);
+            return;
+        }
+        "grade" => {
+            args.remove(1);
+            grade_files(&args);
+            return;
+        }
+        "test" => {
+            args.remove(1);
+            test_files(&args);
+            return;
+        }
+        "assemble" => Some(Command::Assemble),
+        "run" => Some(Command::Run),
+        "disasm" => Some(Command::Disasm),
+        "debug" => Some(Command::Debug),
+        "gdb" => Some(Command::Gdb),
+        _ => None,
+    };
+    if command.is_some() {
+        args.remove(1);
+    }
+    let mut options = parse_options(&args);
+    // Without a command, a file is run unless an output format is asked for
+    let command = command.unwrap_or(match options.format {
+        Some(_) => Command::Assemble,
+        None => Command::Run,
+    });
+
+    match command {
+        Command::Assemble => assemble_file(&mut options),
+        Command::Run => run_program(&options),
+        Command::Disasm => disassemble(&options),
+        Command::Debug => debug_program(&options),
+        Command::Gdb => serve_gdb(&options),
+    }
+}
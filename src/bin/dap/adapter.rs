@@ -0,0 +1,850 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use emulator_8085::core::{
+    assembler::assemble,
+    breakpoints::{parse_number, Breakpoint, Condition, Flag, Operand, REGISTER_NAMES},
+    cpu::{Cpu, Stop},
+    disassembler::{decode, render, symbols_by_address},
+    history::{self, History},
+    image::Image,
+    json::Json,
+    lint::lint,
+    parser::{parse_source, Ins},
+};
+
+const THREAD_ID: u64 = 1;
+const DEFAULT_MAX_STEPS: u64 = 1_000_000;
+const REGISTERS_REFERENCE: u64 = 1;
+const FLAGS_REFERENCE: u64 = 2;
+const MEMORY_REFERENCE: u64 = 3;
+const MEMORY_ROW_LENGTH: usize = 16;
+
+/// A breakpoint the editor set on a source line.
+#[derive(Debug, Clone, PartialEq)]
+struct SourceBreakpoint {
+    line: usize,
+    condition: Option<String>,
+}
+
+/// The launched program.
+struct Session {
+    path: String,
+    image: Image,
+    cpu: Cpu,
+    max_steps: u64,
+    stop_on_entry: bool,
+    /// Set once the program halted; it can still be stepped back.
+    ended: bool,
+}
+
+/// Answers Debug Adapter Protocol requests for one `.s` file.
+pub struct Adapter {
+    seq: u64,
+    session: Option<Session>,
+    /// The source breakpoints by path, kept until the program is launched.
+    breakpoints: HashMap<String, Vec<SourceBreakpoint>>,
+    /// The name and body of the events to send after the current response.
+    events: Vec<(String, Json)>,
+    /// Set once the editor disconnects.
+    pub finished: bool,
+}
+
+impl Adapter {
+    pub fn new() -> Self {
+        Self {
+            seq: 0,
+            session: None,
+            breakpoints: HashMap::new(),
+            events: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// The response to `request`, followed by the events it caused.
+    pub fn handle(&mut self, request: &Json) -> Vec<Json> {
+        let command = request
+            .get("command")
+            .and_then(Json::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let arguments = request.get("arguments").cloned().unwrap_or(Json::Null);
+        let result = self.dispatch(&command, &arguments);
+
+        let mut response = vec![
+            ("seq", self.next_seq().into()),
+            ("type", "response".into()),
+            (
+                "request_seq",
+                request.get("seq").cloned().unwrap_or(Json::Null),
+            ),
+            ("command", command.into()),
+        ];
+        match result {
+            Ok(body) => {
+                response.push(("success", true.into()));
+                if body != Json::Null {
+                    response.push(("body", body));
+                }
+            }
+            Err(message) => {
+                response.push(("success", false.into()));
+                response.push(("message", message.into()));
+                self.events.clear();
+            }
+        }
+        let mut messages = vec![Json::object(response)];
+        for (event, body) in std::mem::take(&mut self.events) {
+            messages.push(Json::object(vec![
+                ("seq", self.next_seq().into()),
+                ("type", "event".into()),
+                ("event", event.into()),
+                ("body", body),
+            ]));
+        }
+        messages
+    }
+
+    fn next_seq(&mut self) -> u64 {
+        self.seq += 1;
+        self.seq
+    }
+
+    fn event(&mut self, event: &str, body: Json) {
+        self.events.push((event.to_string(), body));
+    }
+
+    fn output(&mut self, text: String) {
+        self.event(
+            "output",
+            Json::object(vec![
+                ("category", "console".into()),
+                ("output", format!("{}\n", text).into()),
+            ]),
+        );
+    }
+
+    fn stopped(&mut self, reason: &str, text: Option<String>) {
+        let mut body = vec![
+            ("reason", reason.into()),
+            ("threadId", THREAD_ID.into()),
+            ("allThreadsStopped", true.into()),
+        ];
+        if let Some(text) = text {
+            body.push(("text", text.into()));
+        }
+        self.event("stopped", Json::object(body));
+    }
+
+    fn dispatch(&mut self, command: &str, arguments: &Json) -> Result<Json, String> {
+        match command {
+            "initialize" => Ok(Json::object(vec![
+                ("supportsConfigurationDoneRequest", true.into()),
+                ("supportsConditionalBreakpoints", true.into()),
+                ("supportsStepBack", true.into()),
+                ("supportsSetVariable", true.into()),
+                ("supportsEvaluateForHovers", true.into()),
+                ("supportsTerminateRequest", true.into()),
+            ])),
+            "launch" => self.launch(arguments),
+            "setBreakpoints" => self.set_breakpoints(arguments),
+            "configurationDone" => {
+                if self.session()?.stop_on_entry {
+                    self.stopped("entry", None);
+                } else {
+                    self.resume(None, "breakpoint")?;
+                }
+                Ok(Json::Null)
+            }
+            "threads" => Ok(Json::object(vec![(
+                "threads",
+                vec![Json::object(vec![
+                    ("id", THREAD_ID.into()),
+                    ("name", "8085".into()),
+                ])]
+                .into(),
+            )])),
+            "stackTrace" => self.stack_trace(),
+            "scopes" => Ok(Json::object(vec![(
+                "scopes",
+                vec![
+                    scope("Registers", REGISTERS_REFERENCE, false),
+                    scope("Flags", FLAGS_REFERENCE, false),
+                    scope("Memory", MEMORY_REFERENCE, true),
+                ]
+                .into(),
+            )])),
+            "variables" => self.variables(arguments),
+            "setVariable" => self.set_variable(arguments),
+            "evaluate" => self.evaluate(arguments),
+            "continue" => {
+                self.resume(None, "breakpoint")?;
+                Ok(Json::object(vec![("allThreadsContinued", true.into())]))
+            }
+            "next" => {
+                let session = self.session()?;
+                let pc = session.cpu.pc;
+                match decode(&session.cpu.memory, pc) {
+                    Some((ins, length)) if is_call(&ins) && !session.ended => {
+                        self.resume(Some(pc.wrapping_add(length as u16)), "step")?
+                    }
+                    _ => self.step()?,
+                }
+                Ok(Json::Null)
+            }
+            "stepIn" => self.step().map(|_| Json::Null),
+            "stepOut" => self.step_out().map(|_| Json::Null),
+            "stepBack" => {
+                let session = self.session_mut()?;
+                if session.cpu.step_back() {
+                    session.ended = false;
+                }
+                self.stopped("step", None);
+                Ok(Json::Null)
+            }
+            "reverseContinue" => {
+                let session = self.session_mut()?;
+                let (breakpoint, steps) = session.cpu.run_back();
+                if steps > 0 {
+                    session.ended = false;
+                }
+                match breakpoint {
+                    true => self.stopped("breakpoint", None),
+                    false => self.stopped("step", Some("No more history".to_string())),
+                }
+                Ok(Json::Null)
+            }
+            // Requests are handled one at a time, so the program never runs when this arrives
+            "pause" => {
+                self.stopped("pause", None);
+                Ok(Json::Null)
+            }
+            "disconnect" => {
+                self.finished = true;
+                Ok(Json::Null)
+            }
+            "terminate" => {
+                self.event("terminated", Json::object(vec![]));
+                Ok(Json::Null)
+            }
+            _ => Err(format!("Unsupported request {}", command)),
+        }
+    }
+
+    fn session(&self) -> Result<&Session, String> {
+        self.session
+            .as_ref()
+            .ok_or("No program was launched".to_string())
+    }
+
+    fn session_mut(&mut self) -> Result<&mut Session, String> {
+        self.session
+            .as_mut()
+            .ok_or("No program was launched".to_string())
+    }
+
+    /// `launch` takes the `program` to assemble, `stopOnEntry` and `maxSteps`, the number of
+    /// instructions a continue runs before pausing.
+    fn launch(&mut self, arguments: &Json) -> Result<Json, String> {
+        let path = arguments
+            .get("program")
+            .and_then(Json::as_str)
+            .ok_or("Missing program")?
+            .to_string();
+        let source = std::fs::read_to_string(&path)
+            .map_err(|err| format!("Couldn't read {}: {}", path, err))?;
+        let instructions = parse_source(&source).map_err(|err| format!("{}: {}", path, err))?;
+        for warning in lint(&instructions, &[]) {
+            self.output(format!("{}: {}", path, warning));
+        }
+        let mut image = assemble(&instructions);
+        image.source_map.name_source(&path);
+
+        let mut cpu = Cpu::new();
+        cpu.load_image(&image);
+        cpu.history = Some(History::new(history::DEFAULT_CAPACITY));
+        self.session = Some(Session {
+            path,
+            image,
+            cpu,
+            max_steps: arguments
+                .get("maxSteps")
+                .and_then(Json::as_u64)
+                .unwrap_or(DEFAULT_MAX_STEPS),
+            stop_on_entry: arguments
+                .get("stopOnEntry")
+                .and_then(Json::as_bool)
+                .unwrap_or(false),
+            ended: false,
+        });
+        self.install_breakpoints();
+        self.event("initialized", Json::object(vec![]));
+        Ok(Json::Null)
+    }
+
+    fn set_breakpoints(&mut self, arguments: &Json) -> Result<Json, String> {
+        let path = arguments
+            .path(&["source", "path"])
+            .and_then(Json::as_str)
+            .ok_or("Missing source path")?
+            .to_string();
+        let breakpoints: Vec<SourceBreakpoint> = arguments
+            .get("breakpoints")
+            .and_then(Json::as_array)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|breakpoint| {
+                Some(SourceBreakpoint {
+                    line: breakpoint.get("line")?.as_u64()? as usize,
+                    condition: breakpoint
+                        .get("condition")
+                        .and_then(Json::as_str)
+                        .filter(|condition| !condition.trim().is_empty())
+                        .map(str::to_string),
+                })
+            })
+            .collect();
+        self.breakpoints.insert(path.clone(), breakpoints.clone());
+        self.install_breakpoints();
+
+        let replies: Vec<Json> = breakpoints
+            .iter()
+            .enumerate()
+            .map(|(index, breakpoint)| {
+                let mut reply = vec![("id", (index + 1).into())];
+                match self.resolve(&path, breakpoint) {
+                    Ok(Some(found)) => {
+                        reply.push(("verified", true.into()));
+                        reply.push(("line", found.1.into()));
+                    }
+                    // Not launched yet
+                    Ok(None) => {
+                        reply.push(("verified", false.into()));
+                        reply.push(("line", breakpoint.line.into()));
+                    }
+                    Err(message) => {
+                        reply.push(("verified", false.into()));
+                        reply.push(("line", breakpoint.line.into()));
+                        reply.push(("message", message.into()));
+                    }
+                }
+                Json::object(reply)
+            })
+            .collect();
+        Ok(Json::object(vec![("breakpoints", replies.into())]))
+    }
+
+    /// The breakpoint and the line it ended up on: the first line from `breakpoint.line` on
+    /// that has an instruction.
+    fn resolve(
+        &self,
+        path: &str,
+        breakpoint: &SourceBreakpoint,
+    ) -> Result<Option<(Breakpoint, usize)>, String> {
+        let Some(session) = &self.session else {
+            return Ok(None);
+        };
+        if !same_file(path, &session.path) {
+            return Err("Not part of the program".to_string());
+        }
+        let (address, line) = session
+            .image
+            .source_map
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.location.line >= breakpoint.line)
+            .min_by_key(|(address, entry)| (entry.location.line, **address))
+            .map(|(address, entry)| (*address, entry.location.line))
+            .ok_or("No instruction on or after this line".to_string())?;
+        let condition = match &breakpoint.condition {
+            Some(text) => {
+                Some(Condition::parse(text).ok_or(format!("Invalid condition: {}", text))?)
+            }
+            None => None,
+        };
+        Ok(Some((Breakpoint { address, condition }, line)))
+    }
+
+    fn install_breakpoints(&mut self) {
+        let mut installed = Vec::new();
+        for (path, breakpoints) in &self.breakpoints {
+            for breakpoint in breakpoints {
+                if let Ok(Some((breakpoint, _))) = self.resolve(path, breakpoint) {
+                    installed.push(breakpoint);
+                }
+            }
+        }
+        if let Some(session) = self.session.as_mut() {
+            session.cpu.breakpoints = installed;
+        }
+    }
+
+    fn step(&mut self) -> Result<(), String> {
+        let session = self.session_mut()?;
+        if session.ended {
+            self.event("terminated", Json::object(vec![]));
+            return Ok(());
+        }
+        let (stop, _) = session.cpu.run(&session.image, 1);
+        self.report(stop, "step");
+        Ok(())
+    }
+
+    /// Steps until a return pops the stack above where it was.
+    fn step_out(&mut self) -> Result<(), String> {
+        let session = self.session_mut()?;
+        let start = session.cpu.sp;
+        for _ in 0..session.max_steps {
+            let returning = matches!(
+                decode(&session.cpu.memory, session.cpu.pc),
+                Some((
+                    Ins::Ret
+                        | Ins::Rnz
+                        | Ins::Rz
+                        | Ins::Rnc
+                        | Ins::Rc
+                        | Ins::Rpo
+                        | Ins::Rpe
+                        | Ins::Rp
+                        | Ins::Rm,
+                    _
+                ))
+            );
+            let (stop, _) = session.cpu.run(&session.image, 1);
+            if stop != Stop::StepLimit || (returning && session.cpu.sp > start) {
+                self.report(stop, "step");
+                return Ok(());
+            }
+        }
+        self.report(Stop::StepLimit, "pause");
+        Ok(())
+    }
+
+    /// Runs until `target`, a breakpoint, the end of the program or the step limit.
+    fn resume(&mut self, target: Option<u16>, reason: &str) -> Result<(), String> {
+        let session = self.session_mut()?;
+        if session.ended {
+            self.event("terminated", Json::object(vec![]));
+            return Ok(());
+        }
+        if let Some(address) = target {
+            session.cpu.breakpoints.push(Breakpoint {
+                address,
+                condition: None,
+            });
+        }
+        let (stop, _) = session.cpu.run(&session.image, session.max_steps);
+        if target.is_some() {
+            session.cpu.breakpoints.pop();
+        }
+        let reason = match stop {
+            Stop::Breakpoint(address) if Some(address) == target => "step",
+            Stop::StepLimit => "pause",
+            _ => reason,
+        };
+        self.report(stop, reason);
+        Ok(())
+    }
+
+    /// Tells the editor why the program stopped.
+    fn report(&mut self, stop: Stop, reason: &str) {
+        let Some(session) = self.session.as_mut() else {
+            return;
+        };
+        match stop {
+            Stop::Halted => {
+                session.ended = true;
+                let text = format!(
+                    "Halted at {} after {} T-states",
+                    session
+                        .image
+                        .source_map
+                        .describe(session.cpu.pc.wrapping_sub(1)),
+                    session.cpu.cycles
+                );
+                self.output(text);
+                self.event("exited", Json::object(vec![("exitCode", 0u64.into())]));
+                self.event("terminated", Json::object(vec![]));
+            }
+            Stop::UndefinedOpcode(address) => {
+                let text = format!(
+                    "Undefined opcode {:02X} at {}",
+                    session.cpu.read_memory(address as usize),
+                    session.image.source_map.describe(address)
+                );
+                self.stopped("exception", Some(text));
+            }
+            Stop::LeftProgram(address) => {
+                let text = format!("Execution left the program at {:04X}H", address);
+                self.stopped("exception", Some(text));
+            }
+            Stop::StepLimit if reason == "pause" => {
+                let text = format!("Still running after {} steps", session.max_steps);
+                self.stopped("pause", Some(text));
+            }
+            Stop::Watchpoint(access) => {
+                self.stopped("data breakpoint", Some(format!("Watchpoint on {}", access)))
+            }
+            Stop::StepLimit | Stop::Breakpoint(_) => self.stopped(reason, None),
+        }
+    }
+
+    fn stack_trace(&self) -> Result<Json, String> {
+        let session = self.session()?;
+        let pc = session.cpu.pc;
+        let symbols = symbols_by_address(&session.image.symbols);
+        let name = match decode(&session.cpu.memory, pc) {
+            Some((ins, _)) => format!("{:04X}H  {}", pc, render(&ins, &symbols)),
+            None => format!("{:04X}H", pc),
+        };
+        let mut frame = vec![
+            ("id", 0u64.into()),
+            ("name", name.into()),
+            (
+                "instructionPointerReference",
+                format!("0x{:04X}", pc).into(),
+            ),
+        ];
+        match session.image.source_map.lookup(pc) {
+            Some((_, location)) => {
+                frame.push(("source", source(&session.path)));
+                frame.push(("line", location.line.into()));
+                frame.push(("column", location.column.into()));
+            }
+            None => {
+                frame.push(("line", 0u64.into()));
+                frame.push(("column", 0u64.into()));
+            }
+        }
+        Ok(Json::object(vec![
+            ("stackFrames", vec![Json::object(frame)].into()),
+            ("totalFrames", 1u64.into()),
+        ]))
+    }
+
+    fn variables(&self, arguments: &Json) -> Result<Json, String> {
+        let cpu = &self.session()?.cpu;
+        let variables: Vec<Json> = match arguments.get("variablesReference").and_then(Json::as_u64)
+        {
+            Some(REGISTERS_REFERENCE) => REGISTER_NAMES
+                .iter()
+                .map(|name| variable(name, format_register(cpu, name)))
+                .collect(),
+            Some(FLAGS_REFERENCE) => Flag::ALL
+                .iter()
+                .map(|flag| variable(flag.name(), u8::from(flag.get(cpu)).to_string()))
+                .collect(),
+            Some(MEMORY_REFERENCE) => cpu
+                .memory
+                .chunks(MEMORY_ROW_LENGTH)
+                .enumerate()
+                .filter(|(_, row)| row.iter().any(|byte| *byte != 0))
+                .map(|(index, row)| {
+                    let bytes: Vec<String> =
+                        row.iter().map(|byte| format!("{:02X}", byte)).collect();
+                    variable(
+                        &format!("{:04X}", index * MEMORY_ROW_LENGTH),
+                        bytes.join(" "),
+                    )
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        Ok(Json::object(vec![("variables", variables.into())]))
+    }
+
+    /// Sets a register, flag or memory row from hex.
+    fn set_variable(&mut self, arguments: &Json) -> Result<Json, String> {
+        let reference = arguments.get("variablesReference").and_then(Json::as_u64);
+        let name = arguments
+            .get("name")
+            .and_then(Json::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let value = arguments
+            .get("value")
+            .and_then(Json::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let invalid = || format!("Invalid value for {}: {}", name, value);
+        let cpu = &mut self.session_mut()?.cpu;
+        let shown = match reference {
+            Some(REGISTERS_REFERENCE) if REGISTER_NAMES.contains(&name.as_str()) => {
+                let number = parse_number(value.trim()).ok_or_else(invalid)?;
+                if name.len() == 1 && number > 0xFF {
+                    return Err(invalid());
+                }
+                let register = Operand::parse(&name).ok_or_else(invalid)?;
+                register.set(cpu, number);
+                register.format(cpu)
+            }
+            Some(FLAGS_REFERENCE) => {
+                let flag = Flag::from(&name).ok_or_else(|| format!("Can't set {}", name))?;
+                let set = match value.trim() {
+                    "0" => false,
+                    "1" => true,
+                    _ => return Err(invalid()),
+                };
+                flag.set(cpu, set);
+                u8::from(set).to_string()
+            }
+            Some(MEMORY_REFERENCE) => {
+                let address = u16::from_str_radix(&name, 16).map_err(|_| invalid())?;
+                let bytes: Option<Vec<u8>> = value
+                    .split_whitespace()
+                    .map(|byte| u8::from_str_radix(byte, 16).ok())
+                    .collect();
+                let bytes = bytes
+                    .filter(|bytes| bytes.len() <= MEMORY_ROW_LENGTH)
+                    .ok_or_else(invalid)?;
+                for (offset, byte) in bytes.iter().enumerate() {
+                    cpu.write_memory(address.wrapping_add(offset as u16) as usize, *byte);
+                }
+                let row: Vec<String> = (0..MEMORY_ROW_LENGTH)
+                    .map(|offset| {
+                        let address = address.wrapping_add(offset as u16);
+                        format!("{:02X}", cpu.read_memory(address as usize))
+                    })
+                    .collect();
+                row.join(" ")
+            }
+            _ => return Err(format!("Can't set {}", name)),
+        };
+        Ok(Json::object(vec![("value", shown.into())]))
+    }
+
+    /// Evaluates a register, pair, flag, label or `[address]`.
+    fn evaluate(&self, arguments: &Json) -> Result<Json, String> {
+        let session = self.session()?;
+        let expression = arguments
+            .get("expression")
+            .and_then(Json::as_str)
+            .unwrap_or_default()
+            .trim();
+        let upper = expression.to_uppercase();
+        let result = if REGISTER_NAMES.contains(&upper.as_str()) {
+            format_register(&session.cpu, &upper)
+        } else if let Some(flag) = Flag::from(&upper) {
+            u8::from(flag.get(&session.cpu)).to_string()
+        } else if let Some(address) = session
+            .image
+            .symbols
+            .get(expression)
+            .or_else(|| session.image.symbols.get(&upper))
+        {
+            format!("{:04X}H", address)
+        } else if let Some(address) = upper
+            .strip_prefix('[')
+            .and_then(|address| address.strip_suffix(']'))
+            .and_then(|address| parse_number(address.trim()))
+        {
+            format!("{:02X}H", session.cpu.read_memory(address as usize))
+        } else {
+            return Err(format!("Unknown expression: {}", expression));
+        };
+        Ok(Json::object(vec![
+            ("result", result.into()),
+            ("variablesReference", 0u64.into()),
+        ]))
+    }
+}
+
+fn scope(name: &str, reference: u64, expensive: bool) -> Json {
+    Json::object(vec![
+        ("name", name.into()),
+        ("variablesReference", reference.into()),
+        ("expensive", expensive.into()),
+    ])
+}
+
+fn variable(name: &str, value: String) -> Json {
+    Json::object(vec![
+        ("name", name.into()),
+        ("value", value.into()),
+        ("variablesReference", 0u64.into()),
+    ])
+}
+
+fn source(path: &str) -> Json {
+    let name = Path::new(path)
+        .file_name()
+        .map_or(path.to_string(), |name| name.to_string_lossy().to_string());
+    Json::object(vec![("name", name.into()), ("path", path.into())])
+}
+
+fn same_file(first: &str, second: &str) -> bool {
+    first == second
+        || matches!(
+            (std::fs::canonicalize(first), std::fs::canonicalize(second)),
+            (Ok(first), Ok(second)) if first == second
+        )
+}
+
+fn is_call(ins: &Ins) -> bool {
+    matches!(
+        ins,
+        Ins::Call(_)
+            | Ins::Cnz(_)
+            | Ins::Cz(_)
+            | Ins::Cnc(_)
+            | Ins::Cc(_)
+            | Ins::Cpo(_)
+            | Ins::Cpe(_)
+            | Ins::Cp(_)
+            | Ins::Cm(_)
+            | Ins::Rst(_)
+    )
+}
+
+/// One of `REGISTER_NAMES` in hex.
+fn format_register(cpu: &Cpu, name: &str) -> String {
+    Operand::parse(name).map_or(String::new(), |register| register.format(cpu))
+}
+
+#[cfg(test)]
+mod tests {
+    use emulator_8085::core::json::{parse_json, Json};
+
+    use super::Adapter;
+
+    fn request(adapter: &mut Adapter, seq: u64, command: &str, arguments: &str) -> Vec<Json> {
+        let request = format!(
+            r#"{{"seq":{},"type":"request","command":"{}","arguments":{}}}"#,
+            seq, command, arguments
+        );
+        adapter.handle(&parse_json(&request).unwrap())
+    }
+
+    fn events(messages: &[Json]) -> Vec<&str> {
+        messages[1..]
+            .iter()
+            .filter_map(|message| message.get("event").and_then(Json::as_str))
+            .collect()
+    }
+
+    #[test]
+    fn test_adapter() {
+        let path = std::env::temp_dir().join("dap-test.s");
+        std::fs::write(
+            &path,
+            "MVI C, 02H\nLOOP: CALL STORE\nDCR C\n\nJNZ LOOP\nHLT\nSTORE: MOV A, C\nRET\n",
+        )
+        .unwrap();
+        let path = path.to_str().unwrap().replace('\\', "/");
+        let mut adapter = Adapter::new();
+
+        let messages = request(&mut adapter, 1, "initialize", "{}");
+        assert_eq!(
+            messages[0].path(&["body", "supportsStepBack"]),
+            Some(&Json::Bool(true))
+        );
+        let messages = request(
+            &mut adapter,
+            2,
+            "launch",
+            &format!(r#"{{"program":"{}","stopOnEntry":true}}"#, path),
+        );
+        assert_eq!(messages[0].get("success"), Some(&Json::Bool(true)));
+        assert_eq!(events(&messages), vec!["initialized"]);
+
+        let messages = request(
+            &mut adapter,
+            3,
+            "setBreakpoints",
+            &format!(
+                r#"{{"source":{{"path":"{}"}},"breakpoints":[{{"line":4}},{{"line":3,"condition":"C == 1"}},{{"line":2,"condition":"Q"}}]}}"#,
+                path
+            ),
+        );
+        let breakpoints = messages[0].path(&["body", "breakpoints"]).unwrap();
+        assert_eq!(
+            breakpoints.to_string(),
+            r#"[{"id":1,"verified":true,"line":5},{"id":2,"verified":true,"line":3},{"id":3,"verified":false,"line":2,"message":"Invalid condition: Q"}]"#
+        );
+
+        let messages = request(&mut adapter, 4, "configurationDone", "{}");
+        assert_eq!(events(&messages), vec!["stopped"]);
+        let messages = request(&mut adapter, 5, "stackTrace", r#"{"threadId":1}"#);
+        let frame = &messages[0]
+            .path(&["body", "stackFrames"])
+            .unwrap()
+            .as_array()
+            .unwrap()[0];
+        assert_eq!(frame.get("line").and_then(Json::as_u64), Some(1));
+        assert_eq!(
+            frame.get("name").and_then(Json::as_str),
+            Some("0000H  MVI C, 02H")
+        );
+
+        // The first JNZ, then DCR C once C is 1
+        request(&mut adapter, 6, "continue", r#"{"threadId":1}"#);
+        let messages = request(&mut adapter, 7, "stackTrace", r#"{"threadId":1}"#);
+        assert!(messages[0].to_string().contains(r#""line":5"#));
+        let messages = request(&mut adapter, 8, "continue", r#"{"threadId":1}"#);
+        assert_eq!(
+            messages[1].path(&["body", "reason"]).and_then(Json::as_str),
+            Some("breakpoint")
+        );
+        let messages = request(&mut adapter, 9, "variables", r#"{"variablesReference":1}"#);
+        assert!(messages[0]
+            .to_string()
+            .contains(r#"{"name":"C","value":"01H","variablesReference":0}"#));
+        let messages = request(&mut adapter, 10, "evaluate", r#"{"expression":"store"}"#);
+        assert_eq!(
+            messages[0].path(&["body", "result"]).and_then(Json::as_str),
+            Some("000AH")
+        );
+
+        let messages = request(
+            &mut adapter,
+            11,
+            "setVariable",
+            r#"{"variablesReference":1,"name":"A","value":"42"}"#,
+        );
+        assert_eq!(
+            messages[0].path(&["body", "value"]).and_then(Json::as_str),
+            Some("42H")
+        );
+        // A row near the top of memory wraps around to 0000H instead of running past it.
+        let messages = request(
+            &mut adapter,
+            12,
+            "setVariable",
+            r#"{"variablesReference":3,"name":"FFF4","value":"01 02 03 04 05 06 07 08"}"#,
+        );
+        assert!(messages[0]
+            .path(&["body", "value"])
+            .and_then(Json::as_str)
+            .is_some_and(|row| row.starts_with("01 02 03 04 05 06 07 08 ")));
+        request(
+            &mut adapter,
+            13,
+            "setVariable",
+            r#"{"variablesReference":2,"name":"CY","value":"1"}"#,
+        );
+        let messages = request(&mut adapter, 14, "evaluate", r#"{"expression":"cy"}"#);
+        assert_eq!(
+            messages[0].path(&["body", "result"]).and_then(Json::as_str),
+            Some("1")
+        );
+        request(&mut adapter, 15, "stepBack", r#"{"threadId":1}"#);
+        let messages = request(&mut adapter, 16, "stackTrace", r#"{"threadId":1}"#);
+        assert!(messages[0].to_string().contains(r#""line":8"#));
+
+        request(
+            &mut adapter,
+            17,
+            "setBreakpoints",
+            &format!(r#"{{"source":{{"path":"{}"}},"breakpoints":[]}}"#, path),
+        );
+        request(&mut adapter, 18, "next", r#"{"threadId":1}"#);
+        let messages = request(&mut adapter, 19, "continue", r#"{"threadId":1}"#);
+        assert_eq!(events(&messages), vec!["output", "exited", "terminated"]);
+
+        let messages = request(&mut adapter, 20, "restart", "{}");
+        assert_eq!(messages[0].get("success"), Some(&Json::Bool(false)));
+        request(&mut adapter, 21, "disconnect", "{}");
+        assert!(adapter.finished);
+        std::fs::remove_file(path).ok();
+    }
+}
@@ -0,0 +1,33 @@
+mod adapter;
+
+use std::io::{self, Write};
+
+use emulator_8085::core::json::{parse_json, read_message, write_message};
+
+use adapter::Adapter;
+
+/// A Debug Adapter Protocol server on stdin and stdout, for editors to debug `.s` files.
+fn main() -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let mut output = io::stdout().lock();
+    let mut adapter = Adapter::new();
+
+    while !adapter.finished {
+        let Some(message) = read_message(&mut input)? else {
+            break;
+        };
+        let request = match parse_json(&message) {
+            Ok(request) => request,
+            Err(err) => {
+                eprintln!("Ignoring a message: {}", err);
+                continue;
+            }
+        };
+        for reply in adapter.handle(&request) {
+            write_message(&mut output, &reply)?;
+        }
+        output.flush()?;
+    }
+    Ok(())
+}
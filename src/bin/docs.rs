@@ -0,0 +1,22 @@
+use std::{fs, process::exit};
+
+use emulator_8085::core::reference::instruction_reference;
+
+const PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/DOCUMENTATION.md");
+
+/// Writes the instruction reference to DOCUMENTATION.md, or with `--check` fails when the file is
+/// out of date.
+fn main() {
+    let reference = instruction_reference();
+    if std::env::args().nth(1).as_deref() == Some("--check") {
+        if fs::read_to_string(PATH).ok().as_deref() != Some(reference.as_str()) {
+            eprintln!("DOCUMENTATION.md is out of date, run `cargo run --bin docs`");
+            exit(1);
+        }
+        return;
+    }
+    if let Err(err) = fs::write(PATH, reference) {
+        eprintln!("Couldn't write {}: {}", PATH, err);
+        exit(1);
+    }
+}
@@ -0,0 +1,33 @@
+mod server;
+
+use std::io::{self, Write};
+
+use emulator_8085::core::json::{parse_json, read_message, write_message};
+
+use server::Server;
+
+/// A Language Server Protocol server on stdin and stdout for 8085 assembly.
+fn main() -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let mut output = io::stdout().lock();
+    let mut server = Server::new();
+
+    while !server.finished {
+        let Some(message) = read_message(&mut input)? else {
+            break;
+        };
+        let message = match parse_json(&message) {
+            Ok(message) => message,
+            Err(err) => {
+                eprintln!("Ignoring a message: {}", err);
+                continue;
+            }
+        };
+        for reply in server.handle(&message) {
+            write_message(&mut output, &reply)?;
+        }
+        output.flush()?;
+    }
+    Ok(())
+}
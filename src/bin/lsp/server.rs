@@ -0,0 +1,695 @@
+use std::collections::HashMap;
+
+use logos::Logos;
+
+use emulator_8085::core::{
+    assembler::assemble,
+    image::Image,
+    instruction_set::{lookup, DIRECTIVES, INSTRUCTIONS},
+    json::Json,
+    lint::lint,
+    parser::{parse_instruction, parse_source},
+    token::Token,
+};
+
+const REGISTERS: [&str; 10] = ["A", "B", "C", "D", "E", "H", "L", "M", "SP", "PSW"];
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// LSP severities, kinds of completion items and symbols.
+const ERROR: u64 = 1;
+const WARNING: u64 = 2;
+const COMPLETION_VARIABLE: u64 = 6;
+const COMPLETION_KEYWORD: u64 = 14;
+const COMPLETION_REFERENCE: u64 = 18;
+const SYMBOL_FUNCTION: u64 = 12;
+
+/// Where a token is: a 0-based line and the characters it covers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Span {
+    line: usize,
+    start: usize,
+    end: usize,
+}
+
+impl Span {
+    fn contains(&self, line: usize, character: usize) -> bool {
+        self.line == line && (self.start..=self.end).contains(&character)
+    }
+
+    fn to_json(self) -> Json {
+        Json::object(vec![
+            ("start", position(self.line, self.start)),
+            ("end", position(self.line, self.end)),
+        ])
+    }
+}
+
+fn position(line: usize, character: usize) -> Json {
+    Json::object(vec![("line", line.into()), ("character", character.into())])
+}
+
+/// A token, or text the lexer doesn't accept.
+type Lexeme<'a> = (Span, Result<Token<'a>, ()>, &'a str);
+
+/// Lexes every line on its own, so that a token never spans lines.
+fn lex(text: &str) -> Vec<Lexeme<'_>> {
+    let mut lexemes = Vec::new();
+    for (line, source) in text.lines().enumerate() {
+        for (token, range) in Token::lexer(source).spanned() {
+            let start = source[..range.start].chars().count();
+            let span = Span {
+                line,
+                start,
+                end: start + source[range.clone()].chars().count(),
+            };
+            lexemes.push((span, token, &source[range]));
+        }
+    }
+    lexemes
+}
+
+fn is_keyword(word: &str) -> bool {
+    lookup(word).is_some()
+}
+
+/// The labels of a document: where they are defined and used.
+struct Labels<'a> {
+    definitions: Vec<(&'a str, Span)>,
+    references: Vec<(&'a str, Span)>,
+    externals: Vec<&'a str>,
+}
+
+impl<'a> Labels<'a> {
+    fn new(lexemes: &[Lexeme<'a>]) -> Self {
+        let mut labels = Labels {
+            definitions: Vec::new(),
+            references: Vec::new(),
+            externals: Vec::new(),
+        };
+        let mut previous = None;
+        for (span, token, _) in lexemes {
+            match token {
+                Ok(Token::Label(name)) => labels.definitions.push((name, *span)),
+                // Section names aren't labels.
+                Ok(Token::Word(_)) if previous == Some(Token::Word("SECTION")) => {}
+                Ok(Token::Word(word)) if !is_keyword(word) => {
+                    if previous == Some(Token::Word("EXTRN")) {
+                        labels.externals.push(word);
+                    }
+                    labels.references.push((word, *span));
+                }
+                _ => {}
+            }
+            previous = token.ok();
+        }
+        labels
+    }
+
+    fn definition(&self, name: &str) -> Option<Span> {
+        self.definitions
+            .iter()
+            .find(|(label, _)| *label == name)
+            .map(|(_, span)| *span)
+    }
+}
+
+/// The word or label under the cursor.
+fn name_at<'a>(lexemes: &[Lexeme<'a>], line: usize, character: usize) -> Option<(&'a str, Span)> {
+    lexemes
+        .iter()
+        .find(|(span, _, _)| span.contains(line, character))
+        .and_then(|(span, token, _)| match token {
+            Ok(Token::Word(name)) | Ok(Token::Label(name)) => Some((*name, *span)),
+            _ => None,
+        })
+}
+
+fn diagnostic(span: Span, severity: u64, message: String, code: Option<&str>) -> Json {
+    let mut diagnostic = vec![
+        ("range", span.to_json()),
+        ("severity", severity.into()),
+        ("source", "8085".into()),
+        ("message", message.into()),
+    ];
+    if let Some(code) = code {
+        diagnostic.push(("code", code.into()));
+    }
+    Json::object(diagnostic)
+}
+
+/// Checks every line on its own, then the whole program when all lines parse.
+fn diagnostics(text: &str) -> Vec<Json> {
+    let lexemes = lex(text);
+    let mut diagnostics = Vec::new();
+    let lines = text.lines().count();
+    for line in 0..lines {
+        let on_line: Vec<&Lexeme> = lexemes
+            .iter()
+            .filter(|(span, token, _)| span.line == line && !matches!(token, Ok(Token::Comment(_))))
+            .collect();
+        if let Some((span, _, text)) = on_line.iter().find(|(_, token, _)| token.is_err()) {
+            diagnostics.push(diagnostic(
+                *span,
+                ERROR,
+                format!("Unrecognized text '{}'", text),
+                None,
+            ));
+            continue;
+        }
+        let mut tokens = on_line
+            .iter()
+            .skip_while(|(_, token, _)| matches!(token, Ok(Token::Label(_))));
+        let Some((span, token, text)) = tokens.next() else {
+            continue;
+        };
+        let word = match token {
+            Ok(Token::Word(word)) if is_keyword(word) => word,
+            Ok(Token::Word(word)) => {
+                let message = format!("Unknown instruction '{}'", word);
+                diagnostics.push(diagnostic(*span, ERROR, message, None));
+                continue;
+            }
+            _ => {
+                let message = format!("Expected an instruction, found '{}'", text);
+                diagnostics.push(diagnostic(*span, ERROR, message, None));
+                continue;
+            }
+        };
+
+        let operands: Vec<&Lexeme> = tokens.copied().collect();
+        let whole = Span {
+            end: operands.last().map_or(span.end, |(last, _, _)| last.end),
+            ..*span
+        };
+        let mut remaining = operands.iter().filter_map(|(_, token, _)| token.ok());
+        match parse_instruction(word, &mut remaining) {
+            Err(err) => diagnostics.push(diagnostic(whole, ERROR, err.to_string(), None)),
+            Ok(_) => {
+                let used = operands.len() - remaining.count();
+                if let Some((extra, _, text)) = operands.get(used) {
+                    let message = format!("Unexpected '{}' after the operands", text);
+                    diagnostics.push(diagnostic(*extra, ERROR, message, None));
+                }
+            }
+        }
+    }
+    if !diagnostics.is_empty() {
+        return diagnostics;
+    }
+
+    let labels = Labels::new(&lexemes);
+    for (index, (name, span)) in labels.definitions.iter().enumerate() {
+        if labels.definitions[..index]
+            .iter()
+            .any(|(other, _)| other == name)
+        {
+            let message = format!("Label '{}' is defined more than once", name);
+            diagnostics.push(diagnostic(*span, ERROR, message, None));
+        }
+    }
+    for (name, span) in &labels.references {
+        if labels.definition(name).is_none() && !labels.externals.contains(name) {
+            let message = format!("Undefined label '{}'", name);
+            diagnostics.push(diagnostic(*span, ERROR, message, None));
+        }
+    }
+    if let Ok(instructions) = parse_source(text) {
+        for warning in lint(&instructions, &[]) {
+            let Some(location) = warning.location else {
+                continue;
+            };
+            let line = location.line - 1;
+            let span = Span {
+                line,
+                start: location.column - 1,
+                end: text
+                    .lines()
+                    .nth(line)
+                    .map_or(0, |line| line.chars().count()),
+            };
+            diagnostics.push(diagnostic(
+                span,
+                WARNING,
+                warning.message,
+                Some(warning.code.code()),
+            ));
+        }
+    }
+    diagnostics
+}
+
+/// Answers Language Server Protocol messages for 8085 assembly documents.
+pub struct Server {
+    /// The text of every open document by URI.
+    documents: HashMap<String, String>,
+    /// Set once the client sends `exit`.
+    pub finished: bool,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self {
+            documents: HashMap::new(),
+            finished: false,
+        }
+    }
+
+    /// The response to a request and any notifications it causes.
+    pub fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let method = message
+            .get("method")
+            .and_then(Json::as_str)
+            .unwrap_or_default();
+        let params = message.get("params").cloned().unwrap_or(Json::Null);
+        let mut notifications = Vec::new();
+        let result = match method {
+            "initialize" => Ok(Json::object(vec![
+                (
+                    "capabilities",
+                    Json::object(vec![
+                        ("textDocumentSync", 1u64.into()),
+                        ("hoverProvider", true.into()),
+                        ("definitionProvider", true.into()),
+                        ("referencesProvider", true.into()),
+                        ("completionProvider", Json::object(vec![])),
+                        ("documentSymbolProvider", true.into()),
+                    ]),
+                ),
+                (
+                    "serverInfo",
+                    Json::object(vec![("name", "8085-lsp".into())]),
+                ),
+            ])),
+            "shutdown" => Ok(Json::Null),
+            "exit" => {
+                self.finished = true;
+                return Vec::new();
+            }
+            "textDocument/didOpen" | "textDocument/didChange" => {
+                let uri = params.path(&["textDocument", "uri"]).and_then(Json::as_str);
+                let text = params.path(&["textDocument", "text"]).or_else(|| {
+                    params
+                        .get("contentChanges")
+                        .and_then(Json::as_array)
+                        .and_then(|changes| changes.last())
+                        .and_then(|change| change.get("text"))
+                });
+                if let (Some(uri), Some(text)) = (uri, text.and_then(Json::as_str)) {
+                    self.documents.insert(uri.to_string(), text.to_string());
+                    notifications.push(publish(uri, diagnostics(text)));
+                }
+                Ok(Json::Null)
+            }
+            "textDocument/didClose" => {
+                if let Some(uri) = params.path(&["textDocument", "uri"]).and_then(Json::as_str) {
+                    self.documents.remove(uri);
+                    notifications.push(publish(uri, Vec::new()));
+                }
+                Ok(Json::Null)
+            }
+            "textDocument/hover" => self.with_position(&params, hover),
+            "textDocument/definition" => self.with_position(&params, definition),
+            "textDocument/references" => {
+                let declaration = params
+                    .path(&["context", "includeDeclaration"])
+                    .and_then(Json::as_bool)
+                    .unwrap_or(true);
+                self.with_position(&params, |uri, text, line, character| {
+                    references(uri, text, line, character, declaration)
+                })
+            }
+            "textDocument/completion" => self.with_document(&params, |_, text| completion(text)),
+            "textDocument/documentSymbol" => {
+                self.with_document(&params, |_, text| document_symbols(text))
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("Unsupported method {}", method))),
+        };
+
+        // Notifications get no response
+        let Some(id) = message.get("id") else {
+            return notifications;
+        };
+        let mut response = vec![("jsonrpc", "2.0".into()), ("id", id.clone())];
+        match result {
+            Ok(result) => response.push(("result", result)),
+            Err((code, message)) => response.push((
+                "error",
+                Json::object(vec![("code", code.into()), ("message", message.into())]),
+            )),
+        }
+        let mut messages = vec![Json::object(response)];
+        messages.append(&mut notifications);
+        messages
+    }
+
+    fn with_document(
+        &self,
+        params: &Json,
+        answer: impl FnOnce(&str, &str) -> Json,
+    ) -> Result<Json, (i64, String)> {
+        let uri = params
+            .path(&["textDocument", "uri"])
+            .and_then(Json::as_str)
+            .ok_or((INVALID_PARAMS, "Missing textDocument".to_string()))?;
+        let text = self
+            .documents
+            .get(uri)
+            .ok_or((INVALID_PARAMS, format!("{} isn't open", uri)))?;
+        Ok(answer(uri, text))
+    }
+
+    fn with_position(
+        &self,
+        params: &Json,
+        answer: impl FnOnce(&str, &str, usize, usize) -> Json,
+    ) -> Result<Json, (i64, String)> {
+        let line = params.path(&["position", "line"]).and_then(Json::as_u64);
+        let character = params
+            .path(&["position", "character"])
+            .and_then(Json::as_u64);
+        let (Some(line), Some(character)) = (line, character) else {
+            return Err((INVALID_PARAMS, "Missing position".to_string()));
+        };
+        self.with_document(params, |uri, text| {
+            answer(uri, text, line as usize, character as usize)
+        })
+    }
+}
+
+fn publish(uri: &str, diagnostics: Vec<Json>) -> Json {
+    Json::object(vec![
+        ("jsonrpc", "2.0".into()),
+        ("method", "textDocument/publishDiagnostics".into()),
+        (
+            "params",
+            Json::object(vec![
+                ("uri", uri.into()),
+                ("diagnostics", diagnostics.into()),
+            ]),
+        ),
+    ])
+}
+
+/// The assembled document, when it parses.
+fn assemble_text(text: &str) -> Option<Image> {
+    parse_source(text)
+        .ok()
+        .map(|instructions| assemble(&instructions))
+}
+
+fn hover(_: &str, text: &str, line: usize, character: usize) -> Json {
+    let lexemes = lex(text);
+    let Some((name, span)) = name_at(&lexemes, line, character) else {
+        return Json::Null;
+    };
+    let image = assemble_text(text);
+    let contents = match lookup(name) {
+        Some(info) if info.is_directive() => {
+            format!("**{}**\n\n{}", info.syntax(), info.description)
+        }
+        Some(info) => {
+            let mut contents = format!("**{}**\n\n{}\n\n", info.syntax(), info.description);
+            let bytes = image.as_ref().and_then(|image| {
+                let (address, entry) = image
+                    .source_map
+                    .entries
+                    .iter()
+                    .find(|(_, entry)| entry.location.line == line + 1)?;
+                let bytes: Vec<String> = (0..entry.length)
+                    .filter_map(|offset| {
+                        let address = address.wrapping_add(offset);
+                        image
+                            .bytes()
+                            .find(|(at, _)| *at == address)
+                            .map(|(_, byte)| format!("{:02X}", byte))
+                    })
+                    .collect();
+                Some(format!(
+                    "Bytes: `{}` at {:04X}H · ",
+                    bytes.join(" "),
+                    address
+                ))
+            });
+            contents.push_str(&bytes.unwrap_or_default());
+            contents.push_str(&format!(
+                "T-states: {} · Flags: {}",
+                info.cycles, info.flags
+            ));
+            contents
+        }
+        None => {
+            let labels = Labels::new(&lexemes);
+            let Some(definition) = labels.definition(name) else {
+                return Json::Null;
+            };
+            match image.as_ref().and_then(|image| image.symbols.get(name)) {
+                Some(address) => format!(
+                    "**{}** = {:04X}H, defined on line {}",
+                    name,
+                    address,
+                    definition.line + 1
+                ),
+                None => format!("**{}**, defined on line {}", name, definition.line + 1),
+            }
+        }
+    };
+    Json::object(vec![
+        (
+            "contents",
+            Json::object(vec![
+                ("kind", "markdown".into()),
+                ("value", contents.into()),
+            ]),
+        ),
+        ("range", span.to_json()),
+    ])
+}
+
+fn location(uri: &str, span: Span) -> Json {
+    Json::object(vec![("uri", uri.into()), ("range", span.to_json())])
+}
+
+fn definition(uri: &str, text: &str, line: usize, character: usize) -> Json {
+    let lexemes = lex(text);
+    name_at(&lexemes, line, character)
+        .and_then(|(name, _)| Labels::new(&lexemes).definition(name))
+        .map_or(Json::Null, |span| location(uri, span))
+}
+
+fn references(uri: &str, text: &str, line: usize, character: usize, declaration: bool) -> Json {
+    let lexemes = lex(text);
+    let Some((name, _)) = name_at(&lexemes, line, character) else {
+        return Json::Array(Vec::new());
+    };
+    let labels = Labels::new(&lexemes);
+    let mut spans: Vec<Span> = Vec::new();
+    if declaration {
+        spans.extend(labels.definition(name));
+    }
+    spans.extend(
+        labels
+            .references
+            .iter()
+            .filter(|(reference, _)| *reference == name)
+            .map(|(_, span)| *span),
+    );
+    spans
+        .into_iter()
+        .map(|span| location(uri, span))
+        .collect::<Vec<_>>()
+        .into()
+}
+
+/// Every mnemonic, register and label; the editor filters them as the user types.
+fn completion(text: &str) -> Json {
+    let item = |label: &str, kind: u64, detail: &str| {
+        Json::object(vec![
+            ("label", label.into()),
+            ("kind", kind.into()),
+            ("detail", detail.into()),
+        ])
+    };
+    let mut items: Vec<Json> = INSTRUCTIONS
+        .iter()
+        .chain(DIRECTIVES)
+        .map(|info| item(info.mnemonic, COMPLETION_KEYWORD, &info.syntax()))
+        .collect();
+    items.extend(
+        REGISTERS
+            .iter()
+            .map(|register| item(register, COMPLETION_VARIABLE, "register")),
+    );
+    let lexemes = lex(text);
+    items.extend(
+        Labels::new(&lexemes)
+            .definitions
+            .iter()
+            .map(|(name, _)| item(name, COMPLETION_REFERENCE, "label")),
+    );
+    items.into()
+}
+
+fn document_symbols(text: &str) -> Json {
+    let lexemes = lex(text);
+    let image = assemble_text(text);
+    Labels::new(&lexemes)
+        .definitions
+        .iter()
+        .map(|(name, span)| {
+            let line = Span {
+                line: span.line,
+                start: 0,
+                end: text
+                    .lines()
+                    .nth(span.line)
+                    .map_or(0, |line| line.chars().count()),
+            };
+            let mut symbol = vec![
+                ("name", (*name).into()),
+                ("kind", SYMBOL_FUNCTION.into()),
+                ("range", line.to_json()),
+                ("selectionRange", span.to_json()),
+            ];
+            if let Some(address) = image.as_ref().and_then(|image| image.symbols.get(*name)) {
+                symbol.push(("detail", format!("{:04X}H", address).into()));
+            }
+            Json::object(symbol)
+        })
+        .collect::<Vec<_>>()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use emulator_8085::core::json::{parse_json, Json};
+
+    use super::Server;
+
+    fn send(server: &mut Server, message: &str) -> Vec<Json> {
+        server.handle(&parse_json(message).unwrap())
+    }
+
+    fn request(server: &mut Server, method: &str, params: &str) -> Json {
+        let message = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"{}","params":{}}}"#,
+            method, params
+        );
+        send(server, &message)
+            .remove(0)
+            .get("result")
+            .unwrap()
+            .clone()
+    }
+
+    fn open(server: &mut Server, text: &str) -> Vec<Json> {
+        let text = Json::from(text).to_string();
+        send(
+            server,
+            &format!(
+                r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"file:///a.s","text":{}}}}}}}"#,
+                text
+            ),
+        )
+    }
+
+    fn at(line: usize, character: usize) -> String {
+        format!(
+            r#"{{"textDocument":{{"uri":"file:///a.s"}},"position":{{"line":{},"character":{}}}}}"#,
+            line, character
+        )
+    }
+
+    fn messages(notification: &Json) -> Vec<&str> {
+        notification
+            .path(&["params", "diagnostics"])
+            .and_then(Json::as_array)
+            .unwrap()
+            .iter()
+            .filter_map(|diagnostic| diagnostic.get("message").and_then(Json::as_str))
+            .collect()
+    }
+
+    #[test]
+    fn test_server() {
+        let mut server = Server::new();
+        let capabilities = request(&mut server, "initialize", "{}");
+        assert_eq!(
+            capabilities.path(&["capabilities", "hoverProvider"]),
+            Some(&Json::Bool(true))
+        );
+
+        let notifications = open(
+            &mut server,
+            "MVI A, 05H\nFOO B\nMOV A\nMOV A, B, C\nJMP ?\n",
+        );
+        assert_eq!(
+            messages(&notifications[0]),
+            vec![
+                "Unknown instruction 'FOO'",
+                "Missing token",
+                "Unexpected 'C' after the operands",
+                "Unrecognized text '?'"
+            ]
+        );
+        let notifications = open(&mut server, "MOV A, A\nJMP DONE\n");
+        assert_eq!(
+            messages(&notifications[0]),
+            vec!["Undefined label 'DONE'", "MOV A, A moves A to itself"]
+        );
+
+        let notifications = open(
+            &mut server,
+            "SECTION CODE\nPUBLIC START\nEXTRN PRINT\nSTART: CALL PRINT\nHLT\n",
+        );
+        assert!(messages(&notifications[0]).is_empty());
+
+        let source = "START: MVI C, 02H\nLOOP: DCR C ; count\n  JNZ LOOP\n  HLT\n";
+        let notifications = open(&mut server, source);
+        assert!(messages(&notifications[0]).is_empty());
+
+        let hover = request(&mut server, "textDocument/hover", &at(2, 3));
+        let contents = hover
+            .path(&["contents", "value"])
+            .and_then(Json::as_str)
+            .unwrap();
+        assert!(contents.starts_with("**JNZ address**\n\nJump if not zero."));
+        assert!(contents.contains("Bytes: `C2 02 00` at 0003H · T-states: 7/10 · Flags: none"));
+        let hover = request(&mut server, "textDocument/hover", &at(2, 7));
+        assert_eq!(
+            hover.path(&["contents", "value"]).and_then(Json::as_str),
+            Some("**LOOP** = 0002H, defined on line 2")
+        );
+
+        let definition = request(&mut server, "textDocument/definition", &at(2, 7));
+        assert_eq!(
+            definition.get("range").unwrap().to_string(),
+            r#"{"start":{"line":1,"character":0},"end":{"line":1,"character":5}}"#
+        );
+        let references = request(&mut server, "textDocument/references", &at(1, 1));
+        assert_eq!(references.as_array().unwrap().len(), 2);
+
+        let completion = request(&mut server, "textDocument/completion", &at(3, 0));
+        let labels: Vec<&str> = completion
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|item| item.get("label").and_then(Json::as_str))
+            .collect();
+        assert!(labels.contains(&"MOV") && labels.contains(&"PSW") && labels.contains(&"LOOP"));
+
+        let symbols = request(
+            &mut server,
+            "textDocument/documentSymbol",
+            r#"{"textDocument":{"uri":"file:///a.s"}}"#,
+        );
+        assert_eq!(
+            symbols.as_array().unwrap()[1].get("detail"),
+            Some(&Json::from("0002H"))
+        );
+
+        let unknown = send(&mut server, r#"{"jsonrpc":"2.0","id":2,"method":"foo"}"#);
+        assert!(unknown[0].get("error").is_some());
+        assert!(send(&mut server, r#"{"jsonrpc":"2.0","method":"exit"}"#).is_empty());
+        assert!(server.finished);
+    }
+}
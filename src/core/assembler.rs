@@ -2,7 +2,13 @@ use std::collections::HashMap;
 
 use super::{
     cpu::Registers,
-    parser::{Ins, Instruction, JumpTarget},
+    image::{Image, Segment, SourceEntry},
+    instruction_set::Operands,
+    object::{
+        LineEntry, Object, ObjectError, Relocation, RelocationTarget, Section, Symbol,
+        ABSOLUTE_SECTION, DEFAULT_SECTION,
+    },
+    parser::{Ins, Instruction, JumpTarget, Location},
 };
 
 fn split_address(address: u16) -> (u8, u8) {
@@ -11,491 +17,381 @@ fn split_address(address: u16) -> (u8, u8) {
     (low_byte, high_byte)
 }
 
-pub fn assemble(instructions: &Vec<Instruction>) -> Vec<u8> {
-    let mut assembled_instructions: Vec<u8> = Vec::new();
-    let mut symbol_table: HashMap<&str, u16> = HashMap::new();
-    let mut unresolved_labels: HashMap<&str, Vec<usize>> = HashMap::new();
-
-    for instruction in instructions {
-        if let Some(label) = instruction.label {
-            symbol_table.insert(label, assembled_instructions.len() as u16);
+/// The parser only accepts A-L and M where a single register goes.
+fn register_code(register: Registers) -> u8 {
+    match register {
+        Registers::RegB => 0,
+        Registers::RegC => 1,
+        Registers::RegD => 2,
+        Registers::RegE => 3,
+        Registers::RegH => 4,
+        Registers::RegL => 5,
+        Registers::RegM => 6,
+        Registers::RegA => 7,
+        Registers::RegSP | Registers::RegPSW => {
+            unreachable!("{} isn't a single register", register)
         }
-        match instruction.ins {
-            Ins::Mov(destination, source) => match (destination, source) {
-                (Registers::RegA, Registers::RegB) => {
-                    assembled_instructions.push(0x78);
-                }
-                (Registers::RegA, Registers::RegC) => {
-                    assembled_instructions.push(0x79);
-                }
-                (Registers::RegA, Registers::RegD) => {
-                    assembled_instructions.push(0x7A);
-                }
-                (Registers::RegA, Registers::RegE) => {
-                    assembled_instructions.push(0x7B);
-                }
-                (Registers::RegA, Registers::RegH) => {
-                    assembled_instructions.push(0x7C);
-                }
-                (Registers::RegA, Registers::RegL) => {
-                    assembled_instructions.push(0x7D);
-                }
-                (Registers::RegA, Registers::RegA) => {
-                    assembled_instructions.push(0x47);
-                }
-                (Registers::RegA, Registers::RegM) => {
-                    assembled_instructions.push(0x7E);
-                }
-                (Registers::RegB, Registers::RegA) => {
-                    assembled_instructions.push(0x7F);
-                }
-                (Registers::RegB, Registers::RegB) => {
-                    assembled_instructions.push(0x40);
-                }
-                (Registers::RegB, Registers::RegC) => {
-                    assembled_instructions.push(0x41);
-                }
-                (Registers::RegB, Registers::RegD) => {
-                    assembled_instructions.push(0x42);
-                }
-                (Registers::RegB, Registers::RegE) => {
-                    assembled_instructions.push(0x43);
-                }
-                (Registers::RegB, Registers::RegH) => {
-                    assembled_instructions.push(0x44);
-                }
-                (Registers::RegB, Registers::RegL) => {
-                    assembled_instructions.push(0x45);
-                }
-                (Registers::RegB, Registers::RegM) => {
-                    assembled_instructions.push(0x46);
-                }
-                (Registers::RegC, Registers::RegA) => {
-                    assembled_instructions.push(0x4F);
-                }
-                (Registers::RegC, Registers::RegB) => {
-                    assembled_instructions.push(0x48);
-                }
-                (Registers::RegC, Registers::RegC) => {
-                    assembled_instructions.push(0x49);
-                }
-                (Registers::RegC, Registers::RegD) => {
-                    assembled_instructions.push(0x4A);
-                }
-                (Registers::RegC, Registers::RegE) => {
-                    assembled_instructions.push(0x4B);
-                }
-                (Registers::RegC, Registers::RegH) => {
-                    assembled_instructions.push(0x4C);
-                }
-                (Registers::RegC, Registers::RegL) => {
-                    assembled_instructions.push(0x4D);
-                }
-                (Registers::RegD, Registers::RegA) => {
-                    assembled_instructions.push(0x57);
-                }
-                (Registers::RegD, Registers::RegB) => {
-                    assembled_instructions.push(0x50);
-                }
-                (Registers::RegD, Registers::RegC) => {
-                    assembled_instructions.push(0x51);
-                }
-                (Registers::RegD, Registers::RegD) => {
-                    assembled_instructions.push(0x52);
-                }
-                (Registers::RegD, Registers::RegE) => {
-                    assembled_instructions.push(0x53);
-                }
-                (Registers::RegD, Registers::RegH) => {
-                    assembled_instructions.push(0x54);
-                }
-                (Registers::RegD, Registers::RegL) => {
-                    assembled_instructions.push(0x55);
-                }
-                (Registers::RegE, Registers::RegA) => {
-                    assembled_instructions.push(0x5F);
-                }
-                (Registers::RegE, Registers::RegB) => {
-                    assembled_instructions.push(0x58);
-                }
-                (Registers::RegE, Registers::RegC) => {
-                    assembled_instructions.push(0x59);
-                }
-                (Registers::RegE, Registers::RegD) => {
-                    assembled_instructions.push(0x5A);
-                }
-                (Registers::RegE, Registers::RegE) => {
-                    assembled_instructions.push(0x5B);
-                }
-                (Registers::RegE, Registers::RegH) => {
-                    assembled_instructions.push(0x5C);
-                }
-                (Registers::RegE, Registers::RegL) => {
-                    assembled_instructions.push(0x5D);
-                }
-                (Registers::RegH, Registers::RegA) => {
-                    assembled_instructions.push(0x67);
-                }
-                (Registers::RegH, Registers::RegB) => {
-                    assembled_instructions.push(0x60);
-                }
-                (Registers::RegH, Registers::RegC) => {
-                    assembled_instructions.push(0x61);
-                }
-                (Registers::RegH, Registers::RegD) => {
-                    assembled_instructions.push(0x62);
-                }
-                (Registers::RegH, Registers::RegE) => {
-                    assembled_instructions.push(0x63);
-                }
-                (Registers::RegH, Registers::RegH) => {
-                    assembled_instructions.push(0x64);
-                }
-                (Registers::RegH, Registers::RegL) => {
-                    assembled_instructions.push(0x65);
-                }
-                (Registers::RegL, Registers::RegA) => {
-                    assembled_instructions.push(0x6F);
-                }
-                (Registers::RegL, Registers::RegB) => {
-                    assembled_instructions.push(0x68);
-                }
-                (Registers::RegL, Registers::RegC) => {
-                    assembled_instructions.push(0x69);
-                }
-                (Registers::RegL, Registers::RegD) => {
-                    assembled_instructions.push(0x6A);
-                }
-                (Registers::RegL, Registers::RegE) => {
-                    assembled_instructions.push(0x6B);
-                }
-                (Registers::RegL, Registers::RegH) => {
-                    assembled_instructions.push(0x6C);
-                }
-                (Registers::RegL, Registers::RegL) => {
-                    assembled_instructions.push(0x6D);
-                }
-                (Registers::RegM, Registers::RegA) => {
-                    assembled_instructions.push(0x77);
-                }
-                (Registers::RegM, Registers::RegB) => {
-                    assembled_instructions.push(0x70);
-                }
-                (Registers::RegM, Registers::RegC) => {
-                    assembled_instructions.push(0x71);
-                }
-                (Registers::RegM, Registers::RegD) => {
-                    assembled_instructions.push(0x72);
-                }
-                (Registers::RegM, Registers::RegE) => {
-                    assembled_instructions.push(0x73);
-                }
-                (Registers::RegM, Registers::RegH) => {
-                    assembled_instructions.push(0x74);
-                }
-                (Registers::RegM, Registers::RegL) => {
-                    assembled_instructions.push(0x75);
-                }
-                _ => {}
-            },
-            Ins::Mvi(register, value) => match register {
-                Registers::RegA => {
-                    assembled_instructions.push(0x3E);
-
-                    assembled_instructions.push(value);
-                }
-                Registers::RegB => {
-                    assembled_instructions.push(0x06);
+    }
+}
 
-                    assembled_instructions.push(value);
-                }
-                Registers::RegC => {
-                    assembled_instructions.push(0x0E);
-                    assembled_instructions.push(value);
-                }
-                Registers::RegD => {
-                    assembled_instructions.push(0x16);
-                    assembled_instructions.push(value);
-                }
-                Registers::RegE => {
-                    assembled_instructions.push(0x1E);
-                    assembled_instructions.push(value);
-                }
-                Registers::RegH => {
-                    assembled_instructions.push(0x26);
-                    assembled_instructions.push(value);
-                }
-                Registers::RegL => {
-                    assembled_instructions.push(0x2E);
-                    assembled_instructions.push(value);
-                }
-                _ => {}
-            },
-            Ins::Adi(value) => {
-                assembled_instructions.push(0xC6);
-                assembled_instructions.push(value);
-            }
-            Ins::Add(Registers::RegA) => {
-                assembled_instructions.push(0x87);
-            }
-            Ins::Add(Registers::RegB) => {
-                assembled_instructions.push(0x80);
-            }
-            Ins::Add(Registers::RegC) => {
-                assembled_instructions.push(0x81);
-            }
-            Ins::Add(Registers::RegD) => {
-                assembled_instructions.push(0x82);
-            }
-            Ins::Add(Registers::RegE) => {
-                assembled_instructions.push(0x83);
-            }
-            Ins::Add(Registers::RegH) => {
-                assembled_instructions.push(0x84);
-            }
-            Ins::Sub(Registers::RegA) => assembled_instructions.push(0x97),
-            Ins::Sub(Registers::RegB) => assembled_instructions.push(0x90),
-            Ins::Sub(Registers::RegC) => assembled_instructions.push(0x91),
-            Ins::Sub(Registers::RegD) => assembled_instructions.push(0x92),
-            Ins::Sub(Registers::RegE) => assembled_instructions.push(0x93),
-            Ins::Sub(Registers::RegH) => assembled_instructions.push(0x94),
-            Ins::Sub(Registers::RegL) => assembled_instructions.push(0x95),
-            Ins::Sub(Registers::RegM) => assembled_instructions.push(0x96),
-            Ins::Lxi(registers, address) => match registers {
-                Registers::RegH => {
-                    assembled_instructions.push(0x21);
-                    let (low_byte, high_byte) = split_address(address);
-                    assembled_instructions.push(low_byte);
-                    assembled_instructions.push(high_byte);
-                }
-                Registers::RegD => {
-                    assembled_instructions.push(0x11);
-                    let (low_byte, high_byte) = split_address(address);
-                    assembled_instructions.push(low_byte);
-                    assembled_instructions.push(high_byte);
-                }
-                _ => {}
-            },
-            Ins::Jnz(value) => {
-                if let JumpTarget::Address(address) = value {
-                    assembled_instructions.push(0xC2);
+fn pair_code(register: Registers) -> u8 {
+    match register {
+        Registers::RegB => 0x00,
+        Registers::RegD => 0x10,
+        Registers::RegH => 0x20,
+        _ => 0x30,
+    }
+}
 
-                    let (low_byte, high_byte) = split_address(address);
-                    assembled_instructions.push(low_byte);
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    /// Code outside an `ORG` continues from the previous segment, starting at 0.
+    Absolute,
+    /// Code outside an `ORG` goes into named sections that start at 0 and are placed by the
+    /// linker.
+    Relocatable,
+}
 
-                    assembled_instructions.push(high_byte);
-                } else if let JumpTarget::Label(label) = value {
-                    if let Some(address) = symbol_table.get(label) {
-                        let (low_byte, high_byte) = split_address(*address);
-                        assembled_instructions.push(0xC2);
-                        assembled_instructions.push(low_byte);
-                        assembled_instructions.push(high_byte);
-                    } else {
-                        let location = assembled_instructions.len();
-                        assembled_instructions.push(0xC2);
-                        assembled_instructions.push(0x00);
-                        assembled_instructions.push(0x00);
-                        unresolved_labels.entry(label).or_default().push(location);
-                    }
-                }
-            }
-            Ins::Jmp(value) => {
-                if let JumpTarget::Address(address) = value {
-                    assembled_instructions.push(0xC3);
+struct Assembly<'a> {
+    segments: Vec<Segment>,
+    /// The section of each segment, `None` for the ones placed by `ORG`.
+    sections: Vec<Option<&'a str>>,
+    /// Every label with its segment and address.
+    symbols: HashMap<&'a str, (usize, u16)>,
+    symbol_lines: HashMap<&'a str, usize>,
+    /// The segment, offset, length and source location of every instruction from source text.
+    lines: Vec<(usize, usize, usize, Location)>,
+    /// Every label operand, with the segment and offset of the instruction using it.
+    references: Vec<(&'a str, usize, usize)>,
+    publics: Vec<&'a str>,
+    externals: Vec<&'a str>,
+}
 
-                    let (low_byte, high_byte) = split_address(address);
-                    assembled_instructions.push(low_byte);
+fn assemble_segments<'a>(instructions: &[Instruction<'a>], mode: Mode) -> Assembly<'a> {
+    let mut segments: Vec<Segment> = vec![Segment::default()];
+    let mut sections: Vec<Option<&str>> = vec![match mode {
+        Mode::Absolute => None,
+        Mode::Relocatable => Some(DEFAULT_SECTION),
+    }];
+    let mut current = 0;
+    let mut symbol_table: HashMap<&str, (usize, u16)> = HashMap::new();
+    let mut symbol_lines = HashMap::new();
+    let mut lines = Vec::new();
+    let mut references: Vec<(&str, usize, usize)> = Vec::new();
+    let mut publics = Vec::new();
+    let mut externals = Vec::new();
 
-                    assembled_instructions.push(high_byte);
-                } else if let JumpTarget::Label(label) = value {
-                    if let Some(address) = symbol_table.get(label) {
-                        let (low_byte, high_byte) = split_address(*address);
-                        assembled_instructions.push(0xC3);
-                        assembled_instructions.push(low_byte);
-                        assembled_instructions.push(high_byte);
-                    } else {
-                        let location = assembled_instructions.len();
-                        assembled_instructions.push(0xC3);
-                        assembled_instructions.push(0x00);
-                        assembled_instructions.push(0x00);
-                        unresolved_labels.entry(label).or_default().push(location);
-                    }
-                }
+    for instruction in instructions {
+        match instruction.ins {
+            Ins::Org(address) => {
+                segments.push(Segment {
+                    address,
+                    bytes: Vec::new(),
+                });
+                sections.push(None);
+                current = segments.len() - 1;
             }
-            Ins::Inx(register) => match register {
-                Registers::RegB => {
-                    assembled_instructions.push(0x03);
-                }
-                Registers::RegD => {
-                    assembled_instructions.push(0x13);
-                }
-                Registers::RegH => {
-                    assembled_instructions.push(0x23);
-                }
-                _ => {}
-            },
-
-            Ins::Inr(register) => match register {
-                Registers::RegA => {
-                    assembled_instructions.push(0x3C);
-                }
-                Registers::RegB => {
-                    assembled_instructions.push(0x04);
-                }
-                Registers::RegC => {
-                    assembled_instructions.push(0x0C);
-                }
-                Registers::RegD => {
-                    assembled_instructions.push(0x14);
-                }
-                Registers::RegE => {
-                    assembled_instructions.push(0x1C);
-                }
-                Registers::RegH => {
-                    assembled_instructions.push(0x24);
-                }
-                Registers::RegL => {
-                    assembled_instructions.push(0x2C);
-                }
-                _ => {}
-            },
-            Ins::Dcr(register) => match register {
-                Registers::RegA => {
-                    assembled_instructions.push(0x3D);
-                }
-                Registers::RegB => {
-                    assembled_instructions.push(0x05);
-                }
-                Registers::RegC => {
-                    assembled_instructions.push(0x0D);
-                }
-                Registers::RegD => {
-                    assembled_instructions.push(0x15);
-                }
-                Registers::RegE => {
-                    assembled_instructions.push(0x1D);
-                }
-                Registers::RegH => {
-                    assembled_instructions.push(0x25);
-                }
-                Registers::RegL => {
-                    assembled_instructions.push(0x2D);
-                }
-                _ => {}
-            },
-            Ins::Cmp(register) => match register {
-                Registers::RegA => {
-                    assembled_instructions.push(0xBF);
-                }
-                Registers::RegB => {
-                    assembled_instructions.push(0xB8);
-                }
-                Registers::RegC => {
-                    assembled_instructions.push(0xB9);
-                }
-                Registers::RegD => {
-                    assembled_instructions.push(0xBA);
-                }
-                Registers::RegE => {
-                    assembled_instructions.push(0xBB);
-                }
-                Registers::RegH => {
-                    assembled_instructions.push(0xBC);
-                }
-                Registers::RegL => {
-                    assembled_instructions.push(0xBD);
-                }
-                Registers::RegM => {
-                    assembled_instructions.push(0xBE);
-                }
-            },
-            Ins::Jnc(value) => {
-                if let JumpTarget::Address(address) = value {
-                    assembled_instructions.push(0xD2);
-
-                    let (low_byte, high_byte) = split_address(address);
-                    assembled_instructions.push(low_byte);
-
-                    assembled_instructions.push(high_byte);
-                } else if let JumpTarget::Label(label) = value {
-                    if let Some(address) = symbol_table.get(label) {
-                        let (low_byte, high_byte) = split_address(*address);
-                        assembled_instructions.push(0xD2);
-                        assembled_instructions.push(low_byte);
-                        assembled_instructions.push(high_byte);
-                    } else {
-                        let location = assembled_instructions.len();
-                        assembled_instructions.push(0xD2);
-                        assembled_instructions.push(0x00);
-                        assembled_instructions.push(0x00);
-                        unresolved_labels.entry(label).or_default().push(location);
+            Ins::Section(name) => {
+                let existing = sections.iter().position(|section| *section == Some(name));
+                current = match (mode, existing) {
+                    (Mode::Relocatable, Some(index)) => index,
+                    (Mode::Relocatable, None) => {
+                        segments.push(Segment::default());
+                        sections.push(Some(name));
+                        segments.len() - 1
                     }
-                }
+                    (Mode::Absolute, _) => {
+                        let segment = &segments[current];
+                        let address = segment.address.wrapping_add(segment.bytes.len() as u16);
+                        segments.push(Segment {
+                            address,
+                            bytes: Vec::new(),
+                        });
+                        sections.push(Some(name));
+                        segments.len() - 1
+                    }
+                };
             }
-            Ins::Sta(address) => {
-                assembled_instructions.push(0x32);
-                let (low_byte, high_byte) = split_address(address);
-                assembled_instructions.push(low_byte);
-                assembled_instructions.push(high_byte);
+            Ins::Public(name) => publics.push(name),
+            Ins::Extrn(name) => externals.push(name),
+            _ => {}
+        }
+        let segment_index = current;
+        let segment = &mut segments[segment_index];
+        if let Some(label) = instruction.label {
+            let address = segment.address.wrapping_add(segment.bytes.len() as u16);
+            symbol_table.insert(label, (segment_index, address));
+            if let Some(location) = instruction.location {
+                symbol_lines.insert(label, location.line);
+            }
+        }
+        let assembled_instructions = &mut segment.bytes;
+        let start = assembled_instructions.len();
+        let info = instruction.ins.info();
+        match instruction.ins {
+            Ins::Mov(destination, source) => {
+                assembled_instructions
+                    .push(info.opcode | register_code(destination) << 3 | register_code(source));
             }
-            Ins::Add(Registers::RegM) => {
-                assembled_instructions.push(0x86);
+            Ins::Mvi(register, value) => {
+                assembled_instructions.push(info.opcode | register_code(register) << 3);
+                assembled_instructions.push(value);
+            }
+            Ins::Add(register)
+            | Ins::Adc(register)
+            | Ins::Sub(register)
+            | Ins::Sbb(register)
+            | Ins::Ana(register)
+            | Ins::Xra(register)
+            | Ins::Ora(register)
+            | Ins::Cmp(register) => {
+                assembled_instructions.push(info.opcode | register_code(register));
             }
-            Ins::Add(Registers::RegL) => {
-                assembled_instructions.push(0x85);
+            Ins::Inr(register) | Ins::Dcr(register) => {
+                assembled_instructions.push(info.opcode | register_code(register) << 3);
             }
-            Ins::Lhld(address) => {
-                assembled_instructions.push(0x2A);
+            Ins::Lxi(
+                register @ (Registers::RegB | Registers::RegD | Registers::RegH | Registers::RegSP),
+                address,
+            ) => {
+                assembled_instructions.push(info.opcode | pair_code(register));
                 let (low_byte, high_byte) = split_address(address);
                 assembled_instructions.push(low_byte);
                 assembled_instructions.push(high_byte);
             }
-            Ins::Xchg => {
-                assembled_instructions.push(0xEB);
+            Ins::Inx(
+                register @ (Registers::RegB | Registers::RegD | Registers::RegH | Registers::RegSP),
+            )
+            | Ins::Dcx(
+                register @ (Registers::RegB | Registers::RegD | Registers::RegH | Registers::RegSP),
+            )
+            | Ins::Dad(
+                register @ (Registers::RegB | Registers::RegD | Registers::RegH | Registers::RegSP),
+            )
+            | Ins::Stax(register @ (Registers::RegB | Registers::RegD))
+            | Ins::Ldax(register @ (Registers::RegB | Registers::RegD))
+            | Ins::Push(
+                register
+                @ (Registers::RegB | Registers::RegD | Registers::RegH | Registers::RegPSW),
+            )
+            | Ins::Pop(
+                register
+                @ (Registers::RegB | Registers::RegD | Registers::RegH | Registers::RegPSW),
+            ) => {
+                assembled_instructions.push(info.opcode | pair_code(register));
             }
-            Ins::Shld(address) => {
-                assembled_instructions.push(0x22);
+            Ins::Lda(address) | Ins::Sta(address) | Ins::Lhld(address) | Ins::Shld(address) => {
+                assembled_instructions.push(info.opcode);
                 let (low_byte, high_byte) = split_address(address);
                 assembled_instructions.push(low_byte);
                 assembled_instructions.push(high_byte);
             }
-            Ins::Adc(register) => match register {
-                Registers::RegA => assembled_instructions.push(0xC6),
-                Registers::RegB => assembled_instructions.push(0x8F),
-                Registers::RegC => assembled_instructions.push(0x88),
-                Registers::RegD => assembled_instructions.push(0x89),
-                Registers::RegE => assembled_instructions.push(0x8B),
-                Registers::RegH => assembled_instructions.push(0x8C),
-                Registers::RegL => assembled_instructions.push(0x8D),
-                Registers::RegM => assembled_instructions.push(0x8E),
-            },
-            Ins::Lda(address) => {
-                assembled_instructions.push(0x3A);
+            Ins::Adi(value)
+            | Ins::Aci(value)
+            | Ins::Sui(value)
+            | Ins::Sbi(value)
+            | Ins::Ani(value)
+            | Ins::Xri(value)
+            | Ins::Ori(value)
+            | Ins::Cpi(value)
+            | Ins::In(value)
+            | Ins::Out(value) => {
+                assembled_instructions.push(info.opcode);
+                assembled_instructions.push(value);
+            }
+            Ins::Jmp(target)
+            | Ins::Jnz(target)
+            | Ins::Jz(target)
+            | Ins::Jnc(target)
+            | Ins::Jc(target)
+            | Ins::Jpo(target)
+            | Ins::Jpe(target)
+            | Ins::Jp(target)
+            | Ins::Jm(target)
+            | Ins::Call(target)
+            | Ins::Cnz(target)
+            | Ins::Cz(target)
+            | Ins::Cnc(target)
+            | Ins::Cc(target)
+            | Ins::Cpo(target)
+            | Ins::Cpe(target)
+            | Ins::Cp(target)
+            | Ins::Cm(target) => {
+                let location = assembled_instructions.len();
+                assembled_instructions.push(info.opcode);
+                let address = match target {
+                    JumpTarget::Address(address) => address,
+                    JumpTarget::Label(label) => {
+                        references.push((label, segment_index, location));
+                        0
+                    }
+                };
                 let (low_byte, high_byte) = split_address(address);
                 assembled_instructions.push(low_byte);
                 assembled_instructions.push(high_byte);
             }
-            Ins::Ani(value) => {
-                assembled_instructions.push(0xE6);
-                assembled_instructions.push(value);
-            }
-            Ins::Hlt => {
-                assembled_instructions.push(0x76);
-            }
+            Ins::Rst(vector) => assembled_instructions.push(info.opcode | ((vector & 0x07) << 3)),
+            Ins::Org(_) | Ins::Section(_) | Ins::Public(_) | Ins::Extrn(_) => {}
+            _ if info.operands == Operands::Implied => assembled_instructions.push(info.opcode),
+            // The parser rejects operand combinations the 8085 has no encoding for
+            _ => unreachable!("{} has no encoding", instruction.ins),
+        }
+        let length = assembled_instructions.len() - start;
+        if let (Some(location), true) = (instruction.location, length > 0) {
+            lines.push((segment_index, start, length, location));
+        }
+    }
+
+    Assembly {
+        segments,
+        sections,
+        symbols: symbol_table,
+        symbol_lines,
+        lines,
+        references,
+        publics,
+        externals,
+    }
+}
+
+fn patch_address(bytes: &mut [u8], location: usize, address: u16) {
+    let (low_byte, high_byte) = split_address(address);
+    bytes[location + 1] = low_byte;
+    bytes[location + 2] = high_byte;
+}
+
+pub fn assemble(instructions: &Vec<Instruction>) -> Image {
+    let mut assembly = assemble_segments(instructions, Mode::Absolute);
+
+    for (label, segment_index, location) in &assembly.references {
+        if let Some((_, address)) = assembly.symbols.get(label) {
+            patch_address(
+                &mut assembly.segments[*segment_index].bytes,
+                *location,
+                *address,
+            );
+        }
+    }
+
+    let mut image = Image::new();
+    for segment in assembly
+        .segments
+        .iter()
+        .filter(|segment| !segment.bytes.is_empty())
+    {
+        image.push(segment.address, &segment.bytes);
+    }
+    image.entry = image.segments.first().map_or(0, |segment| segment.address);
+    image.symbols = assembly
+        .symbols
+        .into_iter()
+        .map(|(label, (_, address))| (label.to_string(), address))
+        .collect();
+    image.symbol_lines = assembly
+        .symbol_lines
+        .into_iter()
+        .map(|(label, line)| (label.to_string(), line))
+        .collect();
+    if !assembly.lines.is_empty() {
+        image.source_map.files.push(String::new());
+    }
+    for (segment_index, offset, length, location) in assembly.lines {
+        let address = assembly.segments[segment_index]
+            .address
+            .wrapping_add(offset as u16);
+        image.source_map.entries.insert(
+            address,
+            SourceEntry {
+                file: 0,
+                location,
+                length: length as u16,
+            },
+        );
+    }
+    image
+}
+
+/// Assembles `instructions` into a relocatable object called `name`. Label operands that refer
+/// to relocatable sections or `EXTRN` symbols become relocation entries for the linker.
+pub fn assemble_object(instructions: &[Instruction], name: &str) -> Result<Object, ObjectError> {
+    let mut assembly = assemble_segments(instructions, Mode::Relocatable);
+
+    // Empty segments are dropped, so section indices differ from segment indices
+    let mut section_indices = vec![None; assembly.segments.len()];
+    let mut object = Object::new(name);
+    for (index, segment) in assembly.segments.iter().enumerate() {
+        if !segment.bytes.is_empty() {
+            section_indices[index] = Some(object.sections.len());
+            object.sections.push(Section {
+                name: assembly.sections[index]
+                    .unwrap_or(ABSOLUTE_SECTION)
+                    .to_string(),
+                address: assembly.sections[index].map_or(Some(segment.address), |_| None),
+                bytes: Vec::new(),
+            });
         }
     }
 
-    for (label, locations) in unresolved_labels {
-        if let Some(address) = symbol_table.get(label) {
-            let (low_byte, high_byte) = split_address(*address);
-            for location in locations {
-                assembled_instructions[location + 1] = low_byte;
-                assembled_instructions[location + 2] = high_byte;
+    for (label, segment_index, location) in &assembly.references {
+        let bytes = &mut assembly.segments[*segment_index].bytes;
+        let target = match assembly.symbols.get(label) {
+            Some((target_segment, address)) => {
+                patch_address(bytes, *location, *address);
+                match assembly.sections[*target_segment] {
+                    Some(_) => section_indices[*target_segment].map(RelocationTarget::Section),
+                    None => None,
+                }
+            }
+            None if assembly.externals.contains(label) => {
+                patch_address(bytes, *location, 0);
+                Some(RelocationTarget::External(label.to_string()))
             }
+            None => return Err(ObjectError::UndefinedSymbol(label.to_string())),
+        };
+        if let (Some(target), Some(section)) = (target, section_indices[*segment_index]) {
+            object.relocations.push(Relocation {
+                section,
+                offset: *location as u16 + 1,
+                target,
+            });
         }
     }
 
-    assembled_instructions
+    for (index, segment) in assembly.segments.into_iter().enumerate() {
+        if let Some(section) = section_indices[index] {
+            object.sections[section].bytes = segment.bytes;
+        }
+    }
+    for public in assembly.publics {
+        let (segment_index, offset) = assembly
+            .symbols
+            .get(public)
+            .ok_or_else(|| ObjectError::UndefinedSymbol(public.to_string()))?;
+        // A label with no code after it in its section has nothing to point at
+        let section = section_indices[*segment_index]
+            .ok_or_else(|| ObjectError::UndefinedSymbol(public.to_string()))?;
+        object.publics.push(Symbol {
+            name: public.to_string(),
+            section,
+            offset: *offset,
+        });
+    }
+    for (segment_index, offset, length, location) in assembly.lines {
+        if let Some(section) = section_indices[segment_index] {
+            object.lines.push(LineEntry {
+                section,
+                offset: offset as u16,
+                length: length as u16,
+                location,
+            });
+        }
+    }
+    object.externals = assembly
+        .externals
+        .into_iter()
+        .map(|name| name.to_string())
+        .collect();
+    Ok(object)
 }
 
 #[cfg(test)]
@@ -503,17 +399,14 @@ mod tests {
     use crate::core::{
         assembler::assemble,
         cpu::Registers,
+        image::Segment,
         parser::{Ins, Instruction, JumpTarget},
     };
 
     macro_rules! instructions {
-    ($(($label:expr, $ins:expr)),* $(,)?) => {{
-        let mut v = Vec::new();
-        $(
-            v.push(Instruction { ins: $ins, label: $label });
-        )*
-        v
-    }};
+    ($(($label:expr, $ins:expr)),* $(,)?) => {
+        vec![$(Instruction { ins: $ins, label: $label, location: None }),*]
+    };
     }
 
     #[test]
@@ -528,10 +421,39 @@ mod tests {
                 (None, Ins::Inr(Registers::RegB)),
                 (None, Ins::Dcr(Registers::RegC)),
                 (None, Ins::Jnz(JumpTarget::Label("X")))
-            )),
+            ))
+            .segments[0]
+                .bytes,
             vec![
                 0x21, 0x50, 0x20, 0x06, 0x01, 0x0E, 0x0A, 0x70, 0x23, 0x04, 0x0D, 0xC2, 0x07, 0x00
             ]
         );
     }
+
+    #[test]
+    fn test_org() {
+        let image = assemble(&instructions!(
+            (None, Ins::Org(0x2000)),
+            (Some("START"), Ins::Mvi(Registers::RegA, 0x01)),
+            (None, Ins::Jmp(JumpTarget::Label("NEXT"))),
+            (None, Ins::Org(0x3000)),
+            (Some("NEXT"), Ins::Jmp(JumpTarget::Label("START"))),
+        ));
+
+        assert_eq!(
+            image.segments,
+            vec![
+                Segment {
+                    address: 0x2000,
+                    bytes: vec![0x3E, 0x01, 0xC3, 0x00, 0x30]
+                },
+                Segment {
+                    address: 0x3000,
+                    bytes: vec![0xC3, 0x00, 0x20]
+                },
+            ]
+        );
+        assert_eq!(image.entry, 0x2000);
+        assert_eq!(image.symbols["NEXT"], 0x3000);
+    }
 }
@@ -0,0 +1,387 @@
+use std::fmt;
+
+use super::cpu::{Cpu, Registers};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flag {
+    Sign,
+    Zero,
+    AuxiliaryCarry,
+    Parity,
+    Carry,
+}
+
+impl Flag {
+    /// Every flag, from the high bit of the flag register to the low.
+    pub const ALL: [Flag; 5] = [
+        Flag::Sign,
+        Flag::Zero,
+        Flag::AuxiliaryCarry,
+        Flag::Parity,
+        Flag::Carry,
+    ];
+
+    pub fn from(name: &str) -> Option<Flag> {
+        match name {
+            "S" => Some(Flag::Sign),
+            "Z" => Some(Flag::Zero),
+            "AC" => Some(Flag::AuxiliaryCarry),
+            "P" => Some(Flag::Parity),
+            "CY" => Some(Flag::Carry),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Flag::Sign => "S",
+            Flag::Zero => "Z",
+            Flag::AuxiliaryCarry => "AC",
+            Flag::Parity => "P",
+            Flag::Carry => "CY",
+        }
+    }
+
+    pub fn get(&self, cpu: &Cpu) -> bool {
+        match self {
+            Flag::Sign => cpu.flags.sign,
+            Flag::Zero => cpu.flags.zero,
+            Flag::AuxiliaryCarry => cpu.flags.auxiliary_carry,
+            Flag::Parity => cpu.flags.parity,
+            Flag::Carry => cpu.flags.carry,
+        }
+    }
+
+    pub fn set(&self, cpu: &mut Cpu, value: bool) {
+        match self {
+            Flag::Sign => cpu.flags.sign = value,
+            Flag::Zero => cpu.flags.zero = value,
+            Flag::AuxiliaryCarry => cpu.flags.auxiliary_carry = value,
+            Flag::Parity => cpu.flags.parity = value,
+            Flag::Carry => cpu.flags.carry = value,
+        }
+    }
+}
+
+/// The registers, pairs and `PC` that front ends show and let users set, in display order.
+pub const REGISTER_NAMES: [&str; 12] = [
+    "A", "B", "C", "D", "E", "H", "L", "BC", "DE", "HL", "SP", "PC",
+];
+
+/// What a condition compares.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operand {
+    /// A single register, `M` reading the byte HL points to.
+    Register(Registers),
+    /// A register pair named by its first register, or `SP`.
+    Pair(Registers),
+    Pc,
+    Flag(Flag),
+    Memory(u16),
+}
+
+impl Operand {
+    /// Parses a register (`A`-`L`, `M`), pair (`BC`, `DE`, `HL`, `SP`), `PC`, flag (`S`, `Z`,
+    /// `AC`, `P`, `CY`) or memory byte (`[2050]`).
+    pub fn parse(text: &str) -> Option<Operand> {
+        let name = text.trim().to_uppercase();
+        let operand = match name.as_str() {
+            "A" | "B" | "C" | "D" | "E" | "H" | "L" | "M" => {
+                Operand::Register(Registers::from(&name))
+            }
+            "BC" => Operand::Pair(Registers::RegB),
+            "DE" => Operand::Pair(Registers::RegD),
+            "HL" => Operand::Pair(Registers::RegH),
+            "SP" => Operand::Pair(Registers::RegSP),
+            "PC" => Operand::Pc,
+            name => match Flag::from(name) {
+                Some(flag) => Operand::Flag(flag),
+                None => {
+                    let address = name.strip_prefix('[')?.strip_suffix(']')?;
+                    Operand::Memory(parse_number(address)?)
+                }
+            },
+        };
+        Some(operand)
+    }
+
+    pub fn get(&self, cpu: &Cpu) -> u16 {
+        match *self {
+            Operand::Register(register) => cpu.register(register) as u16,
+            Operand::Pair(register) => cpu.pair(register),
+            Operand::Pc => cpu.pc,
+            Operand::Flag(flag) => flag.get(cpu) as u16,
+            Operand::Memory(address) => cpu.read_memory(address as usize) as u16,
+        }
+    }
+
+    /// The operand's value in hex, with two digits for a byte and four for a word.
+    pub fn format(&self, cpu: &Cpu) -> String {
+        match self {
+            Operand::Pair(_) | Operand::Pc => format!("{:04X}H", self.get(cpu)),
+            _ => format!("{:02X}H", self.get(cpu)),
+        }
+    }
+
+    /// Sets the operand; registers and memory take the low byte of `value`.
+    pub fn set(&self, cpu: &mut Cpu, value: u16) {
+        match *self {
+            Operand::Register(register) => cpu.set_register(register, value as u8),
+            Operand::Pair(register) => cpu.set_pair(register, value),
+            Operand::Pc => cpu.pc = value,
+            Operand::Flag(flag) => flag.set(cpu, value != 0),
+            Operand::Memory(address) => cpu.write_memory(address as usize, value as u8),
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Register(register) => write!(f, "{}", register),
+            Operand::Pair(Registers::RegB) => write!(f, "BC"),
+            Operand::Pair(Registers::RegD) => write!(f, "DE"),
+            Operand::Pair(Registers::RegH) => write!(f, "HL"),
+            Operand::Pair(register) => write!(f, "{}", register),
+            Operand::Pc => write!(f, "PC"),
+            Operand::Flag(flag) => write!(f, "{}", flag.name()),
+            Operand::Memory(address) => write!(f, "[{:04X}]", address),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    const ALL: [Comparison; 6] = [
+        Comparison::Equal,
+        Comparison::NotEqual,
+        Comparison::LessOrEqual,
+        Comparison::GreaterOrEqual,
+        Comparison::Less,
+        Comparison::Greater,
+    ];
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Comparison::Equal => "==",
+            Comparison::NotEqual => "!=",
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+        }
+    }
+}
+
+/// A comparison of a register, register pair, flag or memory byte with a number, such as
+/// `A == 42`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Condition {
+    pub operand: Operand,
+    pub comparison: Comparison,
+    pub value: u16,
+}
+
+impl Condition {
+    /// Parses `<operand> <comparison> <value>`. Operands are registers (`A`-`L`, `M`), pairs
+    /// (`BC`, `DE`, `HL`, `SP`), `PC`, flags (`S`, `Z`, `AC`, `P`, `CY`) or a memory byte
+    /// (`[2050]`); values are hex, with an optional `0x` prefix or `H` suffix.
+    pub fn parse(text: &str) -> Option<Condition> {
+        let (position, comparison) = Comparison::ALL
+            .iter()
+            .filter_map(|comparison| Some((text.find(comparison.symbol())?, *comparison)))
+            .min_by_key(|(position, comparison)| (*position, 2 - comparison.symbol().len()))?;
+        let operand = Operand::parse(&text[..position])?;
+        let value = text[position + comparison.symbol().len()..].trim();
+        Some(Condition {
+            operand,
+            comparison,
+            value: parse_number(value)?,
+        })
+    }
+
+    pub fn holds(&self, cpu: &Cpu) -> bool {
+        let actual = self.operand.get(cpu);
+        match self.comparison {
+            Comparison::Equal => actual == self.value,
+            Comparison::NotEqual => actual != self.value,
+            Comparison::Less => actual < self.value,
+            Comparison::LessOrEqual => actual <= self.value,
+            Comparison::Greater => actual > self.value,
+            Comparison::GreaterOrEqual => actual >= self.value,
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {:X}H",
+            self.operand,
+            self.comparison.symbol(),
+            self.value
+        )
+    }
+}
+
+/// Parses hex, with an optional `0x` prefix or `H` suffix.
+pub fn parse_number(text: &str) -> Option<u16> {
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or_else(|| text.trim_end_matches(['H', 'h']));
+    u16::from_str_radix(digits, 16).ok()
+}
+
+/// Execution stops when `pc` reaches `address` and `condition`, if any, holds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Breakpoint {
+    pub address: u16,
+    pub condition: Option<Condition>,
+}
+
+/// A memory or I/O access of an instruction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    Read(u16),
+    Write(u16),
+    In(u8),
+    Out(u8),
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Access::Read(address) => write!(f, "read of {:04X}H", address),
+            Access::Write(address) => write!(f, "write to {:04X}H", address),
+            Access::In(port) => write!(f, "IN from port {:02X}H", port),
+            Access::Out(port) => write!(f, "OUT to port {:02X}H", port),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Watched {
+    /// An inclusive range of addresses.
+    Memory(u16, u16),
+    /// An inclusive range of ports; reads are `IN` and writes `OUT`.
+    Port(u8, u8),
+}
+
+/// Execution stops after an instruction accesses what is watched.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Watchpoint {
+    pub watched: Watched,
+    pub kind: WatchKind,
+}
+
+impl Watchpoint {
+    pub fn matches(&self, access: Access) -> bool {
+        let (read, write) = match self.kind {
+            WatchKind::Read => (true, false),
+            WatchKind::Write => (false, true),
+            WatchKind::ReadWrite => (true, true),
+        };
+        match (self.watched, access) {
+            (Watched::Memory(start, end), Access::Read(address)) => {
+                read && (start..=end).contains(&address)
+            }
+            (Watched::Memory(start, end), Access::Write(address)) => {
+                write && (start..=end).contains(&address)
+            }
+            (Watched::Port(start, end), Access::In(port)) => read && (start..=end).contains(&port),
+            (Watched::Port(start, end), Access::Out(port)) => {
+                write && (start..=end).contains(&port)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            WatchKind::Read => "read",
+            WatchKind::Write => "write",
+            WatchKind::ReadWrite => "read/write",
+        };
+        match self.watched {
+            Watched::Memory(start, end) if start == end => write!(f, "{} {:04X}H", kind, start),
+            Watched::Memory(start, end) => write!(f, "{} {:04X}H-{:04X}H", kind, start, end),
+            Watched::Port(start, end) if start == end => {
+                write!(f, "{} port {:02X}H", kind, start)
+            }
+            Watched::Port(start, end) => write!(f, "{} ports {:02X}H-{:02X}H", kind, start, end),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::cpu::{Cpu, Registers};
+
+    use super::{Access, Comparison, Condition, Operand, WatchKind, Watched, Watchpoint};
+
+    #[test]
+    fn test_condition() {
+        let condition = Condition::parse("A == 0x42").unwrap();
+        assert_eq!(
+            condition,
+            Condition {
+                operand: Operand::Register(Registers::RegA),
+                comparison: Comparison::Equal,
+                value: 0x42
+            }
+        );
+        assert_eq!(condition.to_string(), "A == 42H");
+        assert_eq!(
+            Condition::parse("[2050]>=10").unwrap().comparison,
+            Comparison::GreaterOrEqual
+        );
+        assert!(Condition::parse("Q == 1").is_none());
+        assert!(Condition::parse("A = 1").is_none());
+
+        let mut cpu = Cpu::new();
+        cpu.accumulator = 0x42;
+        cpu.write_memory(0x2050, 0x05);
+        assert!(condition.holds(&cpu));
+        assert!(Condition::parse("[2050] < 10").unwrap().holds(&cpu));
+        assert!(Condition::parse("z != 0").unwrap().holds(&cpu) == cpu.flags.zero);
+    }
+
+    #[test]
+    fn test_watchpoint() {
+        let watchpoint = Watchpoint {
+            watched: Watched::Memory(0x2050, 0x205F),
+            kind: WatchKind::Write,
+        };
+        assert!(watchpoint.matches(Access::Write(0x2055)));
+        assert!(!watchpoint.matches(Access::Read(0x2055)));
+        assert!(!watchpoint.matches(Access::Write(0x2060)));
+
+        let port = Watchpoint {
+            watched: Watched::Port(0x01, 0x01),
+            kind: WatchKind::ReadWrite,
+        };
+        assert!(port.matches(Access::In(0x01)));
+        assert!(port.matches(Access::Out(0x01)));
+        assert!(!port.matches(Access::Write(0x0001)));
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use super::image::SourceMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CoverageFormat {
+    /// The source annotated with how often each line ran.
+    Text,
+    Html,
+    Lcov,
+}
+
+impl CoverageFormat {
+    pub fn from(name: &str) -> Option<CoverageFormat> {
+        match name.to_lowercase().as_str() {
+            "text" | "txt" => Some(CoverageFormat::Text),
+            "html" => Some(CoverageFormat::Html),
+            "lcov" | "info" => Some(CoverageFormat::Lcov),
+            _ => None,
+        }
+    }
+}
+
+/// Records which instructions `Cpu::eval` executes and which way conditional jumps, calls and
+/// returns go.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Coverage {
+    /// How often the instruction at each address ran.
+    pub executed: BTreeMap<u16, u64>,
+    /// How often the conditional instruction at each address was taken and not taken.
+    pub branches: BTreeMap<u16, (u64, u64)>,
+}
+
+/// How often the instructions on one source line ran.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LineCoverage {
+    pub hits: u64,
+    /// Taken and not taken counts of each conditional instruction on the line.
+    pub branches: Vec<(u64, u64)>,
+}
+
+impl LineCoverage {
+    /// How many of the line's branch outcomes happened at least once.
+    fn branches_hit(&self) -> usize {
+        self.branches
+            .iter()
+            .map(|(taken, not_taken)| (*taken > 0) as usize + (*not_taken > 0) as usize)
+            .sum()
+    }
+}
+
+/// Covered and total counts for a summary.
+fn ratio(covered: usize, total: usize) -> String {
+    if total == 0 {
+        return "0/0".to_string();
+    }
+    format!(
+        "{}/{} ({:.1}%)",
+        covered,
+        total,
+        covered as f64 * 100.0 / total as f64
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts an execution of the instruction at `address`, and which way it went if it is
+    /// conditional.
+    pub fn record(&mut self, address: u16, taken: Option<bool>) {
+        *self.executed.entry(address).or_insert(0) += 1;
+        if let Some(taken) = taken {
+            let (taken_count, not_taken_count) = self.branches.entry(address).or_insert((0, 0));
+            if taken {
+                *taken_count += 1;
+            } else {
+                *not_taken_count += 1;
+            }
+        }
+    }
+
+    /// Coverage of every source line with an instruction, by index into `map.files` and
+    /// 1-based line.
+    pub fn lines(&self, map: &SourceMap) -> BTreeMap<(usize, usize), LineCoverage> {
+        let mut lines: BTreeMap<(usize, usize), LineCoverage> = BTreeMap::new();
+        for (address, entry) in &map.entries {
+            let line = lines.entry((entry.file, entry.location.line)).or_default();
+            line.hits = line
+                .hits
+                .max(self.executed.get(address).copied().unwrap_or(0));
+            if let Some(branch) = self.branches.get(address) {
+                line.branches.push(*branch);
+            }
+        }
+        lines
+    }
+
+    /// Formats the coverage of the program `map` describes, with `sources` holding the text of
+    /// each of `map.files`.
+    pub fn format(&self, format: CoverageFormat, map: &SourceMap, sources: &[String]) -> String {
+        let lines = self.lines(map);
+        match format {
+            CoverageFormat::Text => self.to_text(&lines, map, sources),
+            CoverageFormat::Html => self.to_html(&lines, map, sources),
+            CoverageFormat::Lcov => self.to_lcov(&lines, map),
+        }
+    }
+
+    /// Every line of the source prefixed by its execution count, `#####` for code that never
+    /// ran and `-` for lines without code, followed by a summary.
+    fn to_text(
+        &self,
+        lines: &BTreeMap<(usize, usize), LineCoverage>,
+        map: &SourceMap,
+        sources: &[String],
+    ) -> String {
+        let mut output = String::new();
+        for (file, name) in map.files.iter().enumerate() {
+            if !name.is_empty() {
+                writeln!(output, "{}", name).unwrap();
+            }
+            let source = sources.get(file).map(String::as_str).unwrap_or("");
+            for (index, text) in source.lines().enumerate() {
+                let count = match lines.get(&(file, index + 1)) {
+                    None => "-".to_string(),
+                    Some(line) if line.hits == 0 => "#####".to_string(),
+                    Some(line) => line.hits.to_string(),
+                };
+                write!(output, "{:>8} | {}", count, text).unwrap();
+                if let Some(line) = lines.get(&(file, index + 1)) {
+                    for (taken, not_taken) in &line.branches {
+                        write!(output, "  [taken {}, not taken {}]", taken, not_taken).unwrap();
+                    }
+                }
+                output.push('\n');
+            }
+        }
+        writeln!(output, "{}", self.summary(lines)).unwrap();
+        output
+    }
+
+    /// A standalone page with the source coloured by whether each line ran and took every
+    /// branch.
+    fn to_html(
+        &self,
+        lines: &BTreeMap<(usize, usize), LineCoverage>,
+        map: &SourceMap,
+        sources: &[String],
+    ) -> String {
+        let mut output = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Coverage</title>\n<style>\n\
+             body { font-family: sans-serif; }\n\
+             pre { line-height: 1.4; }\n\
+             .count { color: #888; display: inline-block; width: 6em; text-align: right; margin-right: 1em; }\n\
+             .covered { background: #dfd; }\n\
+             .partial { background: #ffd; }\n\
+             .uncovered { background: #fdd; }\n\
+             </style>\n</head>\n<body>\n",
+        );
+        writeln!(output, "<p>{}</p>", escape_html(&self.summary(lines))).unwrap();
+        for (file, name) in map.files.iter().enumerate() {
+            if !name.is_empty() {
+                writeln!(output, "<h2>{}</h2>", escape_html(name)).unwrap();
+            }
+            output.push_str("<pre>\n");
+            let source = sources.get(file).map(String::as_str).unwrap_or("");
+            for (index, text) in source.lines().enumerate() {
+                let text = escape_html(text);
+                let Some(line) = lines.get(&(file, index + 1)) else {
+                    writeln!(output, "<span class=\"count\"></span>{}", text).unwrap();
+                    continue;
+                };
+                let class = if line.hits == 0 {
+                    "uncovered"
+                } else if line.branches_hit() < line.branches.len() * 2 {
+                    "partial"
+                } else {
+                    "covered"
+                };
+                let branches: Vec<String> = line
+                    .branches
+                    .iter()
+                    .map(|(taken, not_taken)| format!("taken {}, not taken {}", taken, not_taken))
+                    .collect();
+                let title = if branches.is_empty() {
+                    String::new()
+                } else {
+                    format!(" title=\"{}\"", branches.join("; "))
+                };
+                writeln!(
+                    output,
+                    "<span class=\"{}\"{}><span class=\"count\">{}</span>{}</span>",
+                    class, title, line.hits, text
+                )
+                .unwrap();
+            }
+            output.push_str("</pre>\n");
+        }
+        output.push_str("</body>\n</html>\n");
+        output
+    }
+
+    /// One LCOV tracefile record per source file, with every conditional instruction as a
+    /// block of two branches: taken, then not taken.
+    fn to_lcov(&self, lines: &BTreeMap<(usize, usize), LineCoverage>, map: &SourceMap) -> String {
+        let mut output = String::new();
+        for (file, name) in map.files.iter().enumerate() {
+            writeln!(output, "TN:\nSF:{}", name).unwrap();
+            let file_lines: Vec<(usize, &LineCoverage)> = lines
+                .iter()
+                .filter(|((line_file, _), _)| *line_file == file)
+                .map(|((_, line), coverage)| (*line, coverage))
+                .collect();
+            for (line, coverage) in &file_lines {
+                writeln!(output, "DA:{},{}", line, coverage.hits).unwrap();
+            }
+            let mut block = 0;
+            for (line, coverage) in &file_lines {
+                for (taken, not_taken) in &coverage.branches {
+                    for (branch, count) in [taken, not_taken].into_iter().enumerate() {
+                        if coverage.hits == 0 {
+                            writeln!(output, "BRDA:{},{},{},-", line, block, branch).unwrap();
+                        } else {
+                            writeln!(output, "BRDA:{},{},{},{}", line, block, branch, count)
+                                .unwrap();
+                        }
+                    }
+                    block += 1;
+                }
+            }
+            let branches: usize = file_lines.iter().map(|(_, line)| line.branches.len()).sum();
+            writeln!(
+                output,
+                "LF:{}\nLH:{}\nBRF:{}\nBRH:{}\nend_of_record",
+                file_lines.len(),
+                file_lines.iter().filter(|(_, line)| line.hits > 0).count(),
+                branches * 2,
+                file_lines
+                    .iter()
+                    .map(|(_, line)| line.branches_hit())
+                    .sum::<usize>()
+            )
+            .unwrap();
+        }
+        output
+    }
+
+    fn summary(&self, lines: &BTreeMap<(usize, usize), LineCoverage>) -> String {
+        let covered = lines.values().filter(|line| line.hits > 0).count();
+        let branches: usize = lines.values().map(|line| line.branches.len() * 2).sum();
+        let branches_hit: usize = lines.values().map(LineCoverage::branches_hit).sum();
+        format!(
+            "Lines: {}, branches: {}",
+            ratio(covered, lines.len()),
+            ratio(branches_hit, branches)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Coverage, CoverageFormat};
+
+    const SOURCE: &str = "MVI C, 03H\nLOOP: DCR C\nJNZ LOOP\nJC FAIL\nHLT\nFAIL: MVI A, 0FFH\n";
+
+    #[test]
+    fn test_coverage() {
+        let (mut cpu, image) = crate::execute_code(SOURCE);
+        cpu.coverage = Some(Coverage::new());
+        cpu.run(&image, 100);
+        let coverage = cpu.coverage.unwrap();
+        assert_eq!(coverage.executed[&0x0002], 3);
+        assert_eq!(coverage.branches[&0x0003], (2, 1));
+        assert_eq!(coverage.branches[&0x0006], (0, 1));
+        assert!(!coverage.executed.contains_key(&0x000A));
+
+        let sources = vec![SOURCE.to_string()];
+        let text = coverage.format(CoverageFormat::Text, &image.source_map, &sources);
+        assert_eq!(
+            text,
+            "       1 | MVI C, 03H\n       3 | LOOP: DCR C\n       3 | JNZ LOOP  [taken 2, not taken 1]\n       1 | JC FAIL  [taken 0, not taken 1]\n       1 | HLT\n   ##### | FAIL: MVI A, 0FFH\nLines: 5/6 (83.3%), branches: 3/4 (75.0%)\n"
+        );
+
+        let lcov = coverage.format(CoverageFormat::Lcov, &image.source_map, &sources);
+        assert!(lcov.contains("DA:6,0\n"));
+        assert!(lcov.contains("BRDA:4,1,0,0\nBRDA:4,1,1,1\n"));
+        assert!(lcov.ends_with("LF:6\nLH:5\nBRF:4\nBRH:3\nend_of_record\n"));
+
+        let html = coverage.format(CoverageFormat::Html, &image.source_map, &sources);
+        assert!(html.contains("<span class=\"partial\" title=\"taken 0, not taken 1\">"));
+        assert!(html.contains("<span class=\"uncovered\""));
+    }
+}
@@ -1,4 +1,18 @@
-const MEMORY_SIZE: usize = 0xFFFF;
+use std::fmt;
+
+use super::{
+    breakpoints::{Access, Breakpoint, Watchpoint},
+    coverage::Coverage,
+    disassembler::decode,
+    history::History,
+    image::Image,
+    instruction_set::Cycles,
+    parser::{Ins, JumpTarget},
+    profile::{Profiler, Transfer},
+    trace::{TraceEntry, Tracer},
+};
+
+pub const MEMORY_SIZE: usize = 0x10000;
 
 #[derive(PartialEq, Clone, Debug, Copy)]
 pub enum Registers {
@@ -10,6 +24,8 @@ pub enum Registers {
     RegL,
     RegA,
     RegM,
+    RegSP,
+    RegPSW,
 }
 
 impl Registers {
@@ -23,14 +39,33 @@ impl Registers {
             "H" => Registers::RegH,
             "L" => Registers::RegL,
             "M" => Registers::RegM,
+            "SP" => Registers::RegSP,
+            "PSW" => Registers::RegPSW,
             _ => panic!("unknown register"),
         }
     }
 }
 
-#[derive(Clone, Debug)]
-#[allow(dead_code)]
-struct FlagRegisters {
+impl fmt::Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Registers::RegA => "A",
+            Registers::RegB => "B",
+            Registers::RegC => "C",
+            Registers::RegD => "D",
+            Registers::RegE => "E",
+            Registers::RegH => "H",
+            Registers::RegL => "L",
+            Registers::RegM => "M",
+            Registers::RegSP => "SP",
+            Registers::RegPSW => "PSW",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlagRegisters {
     pub sign: bool,
     pub zero: bool,
     pub auxiliary_carry: bool,
@@ -47,13 +82,72 @@ impl FlagRegisters {
             carry: false,
         }
     }
+
+    /// The flags as the low byte of PSW: `S Z - AC - P 1 CY`.
+    pub fn to_byte(&self) -> u8 {
+        (self.sign as u8) << 7
+            | (self.zero as u8) << 6
+            | (self.auxiliary_carry as u8) << 4
+            | (self.parity as u8) << 2
+            | 0x02
+            | self.carry as u8
+    }
+
+    pub fn from_byte(value: u8) -> Self {
+        Self {
+            sign: value & 0x80 != 0,
+            zero: value & 0x40 != 0,
+            auxiliary_carry: value & 0x10 != 0,
+            parity: value & 0x04 != 0,
+            carry: value & 0x01 != 0,
+        }
+    }
+}
+
+/// Why `Cpu::run` stopped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stop {
+    Halted,
+    /// The opcode at this address isn't an 8085 instruction.
+    UndefinedOpcode(u16),
+    /// Execution continued to this address, outside the program.
+    LeftProgram(u16),
+    StepLimit,
+    /// `pc` reached this breakpoint address and its condition held.
+    Breakpoint(u16),
+    /// The last instruction made an access a watchpoint covers.
+    Watchpoint(Access),
+}
+
+impl Stop {
+    /// Says why `cpu`, running `image`, stopped.
+    pub fn describe(&self, cpu: &Cpu, image: &Image) -> String {
+        match *self {
+            Stop::Halted => format!(
+                "Halted at {}",
+                image.source_map.describe(cpu.pc.wrapping_sub(1))
+            ),
+            Stop::UndefinedOpcode(address) => format!(
+                "Undefined opcode {:02X} at {}",
+                cpu.read_memory(address as usize),
+                image.source_map.describe(address)
+            ),
+            Stop::LeftProgram(address) => {
+                format!("Execution left the program at {:04X}H", address)
+            }
+            Stop::StepLimit => "Step limit reached".to_string(),
+            Stop::Breakpoint(address) => {
+                format!("Breakpoint at {}", image.source_map.describe(address))
+            }
+            Stop::Watchpoint(access) => format!("Watchpoint on {}", access),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
-#[allow(dead_code)]
 pub struct Cpu {
     pub pc: u16,
-    sp: usize,
+    pub sp: u16,
     pub accumulator: u8,
     pub b: u8,
     pub c: u8,
@@ -62,7 +156,27 @@ pub struct Cpu {
     pub h: u8,
     pub l: u8,
     pub memory: [u8; MEMORY_SIZE],
-    flags: FlagRegisters,
+    pub flags: FlagRegisters,
+    pub ports: [u8; 256],
+    pub interrupts_enabled: bool,
+    pub interrupt_mask: u8,
+    pub serial_output: bool,
+    pub cycles: u64,
+    pub breakpoints: Vec<Breakpoint>,
+    pub watchpoints: Vec<Watchpoint>,
+    pub tracer: Option<Tracer>,
+    /// Records executed instructions for `step_back` when set.
+    pub history: Option<History>,
+    /// Counts executed instructions and branch outcomes when set.
+    pub coverage: Option<Coverage>,
+    /// Counts executions, T-states and calls when set.
+    pub profiler: Option<Profiler>,
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Cpu {
@@ -79,6 +193,17 @@ impl Cpu {
             l: 0,
             memory: [0; MEMORY_SIZE],
             flags: FlagRegisters::new(),
+            ports: [0; 256],
+            interrupts_enabled: false,
+            interrupt_mask: 0x07,
+            serial_output: false,
+            cycles: 0,
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            tracer: None,
+            history: None,
+            coverage: None,
+            profiler: None,
         }
     }
 
@@ -112,6 +237,10 @@ impl Cpu {
         self.e = 0;
         self.h = 0;
         self.l = 0;
+        self.interrupts_enabled = false;
+        self.interrupt_mask = 0x07;
+        self.serial_output = false;
+        self.cycles = 0;
     }
 
     pub fn reset_memory(&mut self) {
@@ -126,6 +255,13 @@ impl Cpu {
         self.memory[address]
     }
 
+    pub fn load_image(&mut self, image: &Image) {
+        for (address, value) in image.bytes() {
+            self.write_memory(address as usize, value);
+        }
+        self.pc = image.entry;
+    }
+
     #[allow(dead_code)]
     pub fn print(&self) {
         println!(
@@ -171,888 +307,646 @@ impl Cpu {
         self.memory[self.pc as usize]
     }
 
-    pub fn eval(&mut self) -> Option<u16> {
-        match self.memory[self.pc as usize] {
-            // Dad B
-            0x09 => {
-                self.h = self.h.wrapping_add(self.b);
-                self.l = self.l.wrapping_add(self.c);
-                self.pc += 1;
-            }
-
-            // Dad D
-            0x19 => {
-                self.h = self.h.wrapping_add(self.d);
-                self.l = self.l.wrapping_add(self.e);
-                self.pc += 1;
-            }
-
-            // Dad H
-            0x29 => {
-                self.h = self.h.wrapping_add(self.h);
-                self.l = self.l.wrapping_add(self.l);
-                self.pc += 1;
-            }
-
-            // ADC A
-            0xC6 => {
-                self.accumulator =
-                    self.accumulator + self.accumulator + if self.flags.carry { 1 } else { 0 };
-            }
-
-            // ADC B
-            0x8F => {
-                self.accumulator = self.accumulator + self.b + if self.flags.carry { 1 } else { 0 };
-            }
-
-            // ADC C
-            0x88 => {
-                self.accumulator = self.accumulator + self.c + if self.flags.carry { 1 } else { 0 };
-            }
-
-            // ADC D
-            0x89 => {
-                self.accumulator = self.accumulator + self.d + if self.flags.carry { 1 } else { 0 };
-            }
-
-            // ADC E
-            0x8B => {
-                self.accumulator = self.accumulator + self.e + if self.flags.carry { 1 } else { 0 };
-            }
-
-            // ADC H
-            0x8C => {
-                self.accumulator = self.accumulator + self.h + if self.flags.carry { 1 } else { 0 };
-                self.pc += 1;
-            }
-
-            // ADC L
-            0x8D => {
-                self.accumulator = self.accumulator + self.l + if self.flags.carry { 1 } else { 0 };
-            }
-
-            // XCHG
-            0xEB => {
-                std::mem::swap(&mut self.d, &mut self.h);
-                std::mem::swap(&mut self.e, &mut self.l);
-                self.pc += 1;
-            }
-
-            // STAX B
-            0x02 => {
-                let address = u16::from_be_bytes([self.b, self.c]);
-                self.write_memory(address as usize, self.accumulator);
-            }
-
-            // STAX D
-            0x12 => {
-                let address = u16::from_be_bytes([self.d, self.e]);
-                self.write_memory(address as usize, self.accumulator);
-            }
-
-            // LDAX B
-            0x0A => {
-                let address = u16::from_be_bytes([self.b, self.c]);
-                self.accumulator = self.read_memory(address as usize);
-            }
-
-            // LDAX D
-            0x1A => {
-                let address = u16::from_be_bytes([self.d, self.e]);
-                self.accumulator = self.read_memory(address as usize);
-            }
-
-            // LHLD address
-            0x2A => {
-                self.pc += 1;
-                let low_byte_address = self.fetch();
-                self.pc += 1;
-                let high_byte_address = self.fetch();
-                let address = u16::from_be_bytes([high_byte_address, low_byte_address]);
-                let low_byte = self.read_memory(address as usize);
-                let high_byte = self.read_memory((address + 1) as usize);
-                self.l = low_byte;
-                self.h = high_byte;
-                self.pc += 1;
-            }
-
-            // DCR A
-            0x3D => {
-                self.accumulator = self.accumulator.wrapping_sub(1);
-            }
-
-            // DCR B
-            0x05 => {
-                self.b = self.b.wrapping_sub(1);
-
-                if self.b == 0 {
-                    self.flags.zero = true;
-                }
-                self.pc += 1;
-            }
-
-            // DCR C
-            0x0D => {
-                self.c = self.c.wrapping_sub(1);
-                if self.c == 0 {
-                    self.flags.zero = true;
-                }
-                self.pc += 1;
-            }
-
-            // DCR D
-            0x15 => {
-                self.d = self.d.wrapping_sub(1);
-                if self.d == 0 {
-                    self.flags.zero = true;
-                }
-                self.pc += 1;
-            }
-
-            // DCR E
-            0x1D => {
-                self.e = self.e.wrapping_sub(1);
-            }
-
-            // DCR H
-            0x25 => {
-                self.h = self.h.wrapping_sub(1);
-            }
-
-            // DCR L
-            0x2D => {
-                self.l = self.l.wrapping_sub(1);
-            }
-
-            // INR A
-            0x3C => {
-                self.accumulator = self.accumulator.wrapping_add(1);
-            }
-
-            // INR B
-            0x04 => {
-                self.b = self.b.wrapping_add(1);
+    pub fn register(&self, register: Registers) -> u8 {
+        match register {
+            Registers::RegA => self.accumulator,
+            Registers::RegB => self.b,
+            Registers::RegC => self.c,
+            Registers::RegD => self.d,
+            Registers::RegE => self.e,
+            Registers::RegH => self.h,
+            Registers::RegL => self.l,
+            Registers::RegM => self.read_memory(self.pair(Registers::RegH) as usize),
+            Registers::RegSP | Registers::RegPSW => panic!("{} is a register pair", register),
+        }
+    }
 
-                if self.b == 0 {
-                    self.flags.zero = true;
-                }
-                self.pc += 1;
-            }
+    pub fn set_register(&mut self, register: Registers, value: u8) {
+        match register {
+            Registers::RegA => self.accumulator = value,
+            Registers::RegB => self.b = value,
+            Registers::RegC => self.c = value,
+            Registers::RegD => self.d = value,
+            Registers::RegE => self.e = value,
+            Registers::RegH => self.h = value,
+            Registers::RegL => self.l = value,
+            Registers::RegM => self.write_memory(self.pair(Registers::RegH) as usize, value),
+            Registers::RegSP | Registers::RegPSW => panic!("{} is a register pair", register),
+        }
+    }
 
-            // INR C
-            0x0C => {
-                self.c = self.c.wrapping_add(1);
-            }
+    /// Reads a register pair named by its first register (`B` for BC, `D` for DE, `H` for HL),
+    /// `SP` or `PSW`.
+    pub fn pair(&self, register: Registers) -> u16 {
+        match register {
+            Registers::RegB => u16::from_be_bytes([self.b, self.c]),
+            Registers::RegD => u16::from_be_bytes([self.d, self.e]),
+            Registers::RegH => u16::from_be_bytes([self.h, self.l]),
+            Registers::RegSP => self.sp,
+            Registers::RegPSW => u16::from_be_bytes([self.accumulator, self.flags.to_byte()]),
+            _ => panic!("{} is not a register pair", register),
+        }
+    }
 
-            // INR D
-            0x14 => {
-                self.d = self.d.wrapping_add(1);
-            }
+    pub fn set_pair(&mut self, register: Registers, value: u16) {
+        let [high, low] = value.to_be_bytes();
+        match register {
+            Registers::RegB => (self.b, self.c) = (high, low),
+            Registers::RegD => (self.d, self.e) = (high, low),
+            Registers::RegH => (self.h, self.l) = (high, low),
+            Registers::RegSP => self.sp = value,
+            Registers::RegPSW => {
+                self.accumulator = high;
+                self.flags = FlagRegisters::from_byte(low);
+            }
+            _ => panic!("{} is not a register pair", register),
+        }
+    }
 
-            // INR E
-            0x1C => {
-                self.e = self.e.wrapping_add(1);
-            }
+    fn read_word(&self, address: u16) -> u16 {
+        u16::from_le_bytes([
+            self.read_memory(address as usize),
+            self.read_memory(address.wrapping_add(1) as usize),
+        ])
+    }
 
-            // INR H
-            0x24 => {
-                self.h = self.h.wrapping_add(1);
-            }
+    fn write_word(&mut self, address: u16, value: u16) {
+        let [low, high] = value.to_le_bytes();
+        self.write_memory(address as usize, low);
+        self.write_memory(address.wrapping_add(1) as usize, high);
+    }
 
-            // INR L
-            0x2C => {
-                self.l = self.l.wrapping_add(1);
-            }
+    fn push(&mut self, value: u16) {
+        self.sp = self.sp.wrapping_sub(2);
+        self.write_word(self.sp, value);
+    }
 
-            // INX B
-            0x03 => {
-                let address = u16::from_be_bytes([self.b, self.c]);
-                let new_address = address.wrapping_add(1);
-                self.b = (new_address >> 8) as u8;
-                self.c = new_address as u8;
-            }
+    fn pop(&mut self) -> u16 {
+        let value = self.read_word(self.sp);
+        self.sp = self.sp.wrapping_add(2);
+        value
+    }
 
-            // INX D
-            0x13 => {
-                let address = u16::from_be_bytes([self.d, self.e]);
-                let new_address = address.wrapping_add(1);
-                self.d = (new_address >> 8) as u8;
-                self.e = new_address as u8;
-            }
+    fn set_result_flags(&mut self, value: u8) {
+        self.flags.sign = value & 0x80 != 0;
+        self.flags.zero = value == 0;
+        self.flags.parity = value.count_ones().is_multiple_of(2);
+    }
 
-            // INX H
-            0x23 => {
-                let address = u16::from_be_bytes([self.h, self.l]);
-                let new_address = address.wrapping_add(1);
+    fn add(&mut self, value: u8, carry: bool) -> u8 {
+        let result = self.accumulator as u16 + value as u16 + carry as u16;
+        self.flags.auxiliary_carry =
+            (self.accumulator & 0x0F) + (value & 0x0F) + carry as u8 > 0x0F;
+        self.flags.carry = result > 0xFF;
+        self.set_result_flags(result as u8);
+        result as u8
+    }
 
-                self.h = (new_address >> 8) as u8;
-                self.l = new_address as u8;
-                self.pc += 1;
-            }
+    fn subtract(&mut self, value: u8, borrow: bool) -> u8 {
+        let result = self.add(!value, !borrow);
+        self.flags.carry = !self.flags.carry;
+        result
+    }
 
-            // LDA address
-            0x3A => {
-                self.pc += 1;
-                let low_byte_address = self.fetch();
-                self.pc += 1;
-                let high_byte_address = self.fetch();
-                let address = u16::from_be_bytes([high_byte_address, low_byte_address]);
-                self.accumulator = self.read_memory(address as usize);
-                self.pc += 1;
-            }
+    fn logic(&mut self, result: u8, auxiliary_carry: bool) {
+        self.accumulator = result;
+        self.flags.carry = false;
+        self.flags.auxiliary_carry = auxiliary_carry;
+        self.set_result_flags(result);
+    }
 
-            // SUB A
-            0x97 => {
-                self.accumulator = 0;
-            }
+    fn decimal_adjust(&mut self) {
+        let low = self.accumulator & 0x0F;
+        let high = self.accumulator >> 4;
+        let mut correction = 0;
+        let mut carry = self.flags.carry;
 
-            // SUB B
-            0x90 => {
-                self.accumulator -= self.b;
-                self.pc += 1;
-            }
+        if self.flags.auxiliary_carry || low > 9 {
+            correction |= 0x06;
+        }
+        if self.flags.carry || high > 9 || (high >= 9 && low > 9) {
+            correction |= 0x60;
+            carry = true;
+        }
+        self.accumulator = self.add(correction, false);
+        self.flags.carry = carry;
+    }
 
-            // SUB C
-            0x91 => {
-                self.accumulator -= self.c;
-                self.pc += 1;
-            }
+    fn condition(&self, ins: &Ins) -> bool {
+        match ins {
+            Ins::Jnz(_) | Ins::Cnz(_) | Ins::Rnz => !self.flags.zero,
+            Ins::Jz(_) | Ins::Cz(_) | Ins::Rz => self.flags.zero,
+            Ins::Jnc(_) | Ins::Cnc(_) | Ins::Rnc => !self.flags.carry,
+            Ins::Jc(_) | Ins::Cc(_) | Ins::Rc => self.flags.carry,
+            Ins::Jpo(_) | Ins::Cpo(_) | Ins::Rpo => !self.flags.parity,
+            Ins::Jpe(_) | Ins::Cpe(_) | Ins::Rpe => self.flags.parity,
+            Ins::Jp(_) | Ins::Cp(_) | Ins::Rp => !self.flags.sign,
+            Ins::Jm(_) | Ins::Cm(_) | Ins::Rm => self.flags.sign,
+            _ => true,
+        }
+    }
 
-            // SUB D
-            0x92 => {
-                self.accumulator -= self.d;
-                self.pc += 1;
-            }
+    /// The call or return `ins` makes in the current state, if any.
+    fn transfer(&self, ins: &Ins) -> Option<Transfer> {
+        match ins {
+            Ins::Call(_)
+            | Ins::Rst(_)
+            | Ins::Cnz(_)
+            | Ins::Cz(_)
+            | Ins::Cnc(_)
+            | Ins::Cc(_)
+            | Ins::Cpo(_)
+            | Ins::Cpe(_)
+            | Ins::Cp(_)
+            | Ins::Cm(_) => self.condition(ins).then_some(Transfer::Call),
+            Ins::Ret
+            | Ins::Rnz
+            | Ins::Rz
+            | Ins::Rnc
+            | Ins::Rc
+            | Ins::Rpo
+            | Ins::Rpe
+            | Ins::Rp
+            | Ins::Rm => self.condition(ins).then_some(Transfer::Return),
+            _ => None,
+        }
+    }
 
-            // SUB E
-            0x93 => {
-                self.accumulator -= self.e;
-                self.pc += 1;
-            }
+    /// Whether `ins` takes its longer timing: it has a memory operand, or its condition holds.
+    fn long_timing(&self, ins: &Ins) -> bool {
+        match *ins {
+            Ins::Mov(destination, source) => {
+                destination == Registers::RegM || source == Registers::RegM
+            }
+            Ins::Mvi(register, _)
+            | Ins::Add(register)
+            | Ins::Adc(register)
+            | Ins::Sub(register)
+            | Ins::Sbb(register)
+            | Ins::Ana(register)
+            | Ins::Xra(register)
+            | Ins::Ora(register)
+            | Ins::Cmp(register)
+            | Ins::Inr(register)
+            | Ins::Dcr(register) => register == Registers::RegM,
+            _ => self.condition(ins),
+        }
+    }
 
-            // SUB H
-            0x94 => {
-                self.accumulator -= self.h;
-                self.pc += 1;
+    /// Executes the instruction at `pc` and returns the new `pc`, or `None` once the CPU halts
+    /// or reaches an opcode the 8085 doesn't define.
+    pub fn eval(&mut self) -> Option<u16> {
+        let (ins, length) = decode(&self.memory, self.pc)?;
+        let address = self.pc;
+        let traced = self
+            .tracer
+            .as_ref()
+            .is_some_and(|tracer| tracer.traces(address))
+            .then(|| {
+                // Read before executing, as the instruction may overwrite itself
+                let bytes: Vec<u8> = (0..length as u16)
+                    .map(|offset| self.read_memory(address.wrapping_add(offset) as usize))
+                    .collect();
+                (bytes, ins.to_string(), self.accesses(&ins))
+            });
+        if let Some(mut history) = self.history.take() {
+            history.record(self, &self.accesses(&ins));
+            self.history = Some(history);
+        }
+        if self.coverage.is_some() {
+            let taken =
+                matches!(ins.info().cycles, Cycles::Conditional(..)).then(|| self.condition(&ins));
+            if let Some(coverage) = self.coverage.as_mut() {
+                coverage.record(address, taken);
             }
+        }
+        let transfer = self
+            .profiler
+            .is_some()
+            .then(|| self.transfer(&ins))
+            .flatten();
+        self.pc = self.pc.wrapping_add(length as u16);
+        let halted = ins == Ins::Hlt;
+        let t_states = ins.info().cycles.t_states(self.long_timing(&ins));
+        self.execute(ins);
+        self.cycles += t_states as u64;
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record(address, t_states as u64, transfer, self.pc, self.cycles);
+        }
 
-            // SUB L
-            0x95 => {
-                self.accumulator -= self.l;
-                self.pc += 1;
+        if let Some((bytes, text, accesses)) = traced {
+            let writes = accesses
+                .into_iter()
+                .filter_map(|access| match access {
+                    Access::Write(address) => Some((address, self.read_memory(address as usize))),
+                    _ => None,
+                })
+                .collect();
+            let entry = TraceEntry::new(self, address, bytes, text, writes);
+            if let Some(tracer) = self.tracer.as_mut() {
+                tracer.entries.push(entry);
             }
+        }
 
-            // SUB M
-            0x96 => {
-                let address = u16::from_be_bytes([self.h, self.l]);
-                let value = self.read_memory(address as usize);
-                self.accumulator -= value;
-                self.pc += 1;
-            }
+        if halted {
+            return None;
+        }
+        Some(self.pc)
+    }
 
-            // ADD A
-            0x87 => {
-                if let Some(result) = self.accumulator.checked_add(self.accumulator) {
-                    self.accumulator = result;
-                } else {
-                    self.flags.carry = true;
+    /// The memory and I/O accesses `ins` makes when executed in the current state, besides
+    /// fetching itself.
+    pub fn accesses(&self, ins: &Ins) -> Vec<Access> {
+        let hl = self.pair(Registers::RegH);
+        let word = |address: u16| [address, address.wrapping_add(1)];
+        let stack_push = word(self.sp.wrapping_sub(2));
+        let stack_pop = word(self.sp);
+        let memory_operand = |register: &Registers| *register == Registers::RegM;
+
+        match ins {
+            Ins::Mov(destination, source) => {
+                let mut accesses = Vec::new();
+                if memory_operand(source) {
+                    accesses.push(Access::Read(hl));
                 }
-                self.pc += 1;
-            }
-
-            // ADD B
-            0x80 => {
-                if let Some(result) = self.accumulator.checked_add(self.b) {
-                    self.accumulator = result;
-                } else {
-                    self.flags.carry = true;
+                if memory_operand(destination) {
+                    accesses.push(Access::Write(hl));
                 }
-                self.pc += 1;
-            }
+                accesses
+            }
+            Ins::Mvi(register, _) if memory_operand(register) => vec![Access::Write(hl)],
+            Ins::Add(register)
+            | Ins::Adc(register)
+            | Ins::Sub(register)
+            | Ins::Sbb(register)
+            | Ins::Ana(register)
+            | Ins::Xra(register)
+            | Ins::Ora(register)
+            | Ins::Cmp(register)
+                if memory_operand(register) =>
+            {
+                vec![Access::Read(hl)]
+            }
+            Ins::Inr(register) | Ins::Dcr(register) if memory_operand(register) => {
+                vec![Access::Read(hl), Access::Write(hl)]
+            }
+            Ins::Lda(address) => vec![Access::Read(*address)],
+            Ins::Sta(address) => vec![Access::Write(*address)],
+            Ins::Lhld(address) => word(*address).map(Access::Read).to_vec(),
+            Ins::Shld(address) => word(*address).map(Access::Write).to_vec(),
+            Ins::Ldax(register) => vec![Access::Read(self.pair(*register))],
+            Ins::Stax(register) => vec![Access::Write(self.pair(*register))],
+            Ins::Push(_) | Ins::Call(_) | Ins::Rst(_) => stack_push.map(Access::Write).to_vec(),
+            Ins::Cnz(_)
+            | Ins::Cz(_)
+            | Ins::Cnc(_)
+            | Ins::Cc(_)
+            | Ins::Cpo(_)
+            | Ins::Cpe(_)
+            | Ins::Cp(_)
+            | Ins::Cm(_)
+                if self.condition(ins) =>
+            {
+                stack_push.map(Access::Write).to_vec()
+            }
+            Ins::Pop(_) | Ins::Ret => stack_pop.map(Access::Read).to_vec(),
+            Ins::Rnz | Ins::Rz | Ins::Rnc | Ins::Rc | Ins::Rpo | Ins::Rpe | Ins::Rp | Ins::Rm
+                if self.condition(ins) =>
+            {
+                stack_pop.map(Access::Read).to_vec()
+            }
+            Ins::Xthl => {
+                let mut accesses = stack_pop.map(Access::Read).to_vec();
+                accesses.extend(stack_pop.map(Access::Write));
+                accesses
+            }
+            Ins::In(port) => vec![Access::In(*port)],
+            Ins::Out(port) => vec![Access::Out(*port)],
+            _ => Vec::new(),
+        }
+    }
 
-            // ADD C
-            0x81 => {
-                if let Some(result) = self.accumulator.checked_add(self.c) {
-                    self.accumulator = result;
-                } else {
-                    self.flags.carry = true;
-                }
-            }
+    /// Executes the instruction at `pc` like `eval`, and reports whatever ends or interrupts
+    /// execution: HLT, an undefined opcode, an access a watchpoint covers or arriving at a
+    /// breakpoint whose condition holds.
+    pub fn step(&mut self) -> Option<Stop> {
+        let Some((ins, _)) = decode(&self.memory, self.pc) else {
+            return Some(Stop::UndefinedOpcode(self.pc));
+        };
+        let accesses = if self.watchpoints.is_empty() {
+            Vec::new()
+        } else {
+            self.accesses(&ins)
+        };
+        if self.eval().is_none() {
+            return Some(Stop::Halted);
+        }
 
-            // ADD D
-            0x82 => {
-                if let Some(result) = self.accumulator.checked_add(self.d) {
-                    self.accumulator = result;
-                } else {
-                    self.flags.carry = true;
-                }
-            }
+        let watched = accesses.into_iter().find(|access| {
+            self.watchpoints
+                .iter()
+                .any(|watchpoint| watchpoint.matches(*access))
+        });
+        if let Some(access) = watched {
+            return Some(Stop::Watchpoint(access));
+        }
+        self.at_breakpoint().then_some(Stop::Breakpoint(self.pc))
+    }
 
-            // ADD E
-            0x83 => {
-                if let Some(result) = self.accumulator.checked_add(self.e) {
-                    self.accumulator = result;
-                } else {
-                    self.flags.carry = true;
-                }
-            }
+    /// Whether `pc` is at a breakpoint whose condition holds.
+    fn at_breakpoint(&self) -> bool {
+        self.breakpoints.iter().any(|breakpoint| {
+            breakpoint.address == self.pc
+                && breakpoint
+                    .condition
+                    .as_ref()
+                    .is_none_or(|condition| condition.holds(self))
+        })
+    }
 
-            // ADD H
-            0x84 => {
-                if let Some(result) = self.accumulator.checked_add(self.h) {
-                    self.accumulator = result;
-                } else {
-                    self.flags.carry = true;
-                }
-            }
+    /// Undoes the last instruction recorded in `history`, returning `false` when there is none.
+    pub fn step_back(&mut self) -> bool {
+        let Some(mut history) = self.history.take() else {
+            return false;
+        };
+        let undone = history.undo(self);
+        self.history = Some(history);
+        undone
+    }
 
-            // ADD L
-            0x85 => {
-                if let Some(result) = self.accumulator.checked_add(self.l) {
-                    self.accumulator = result;
-                } else {
-                    self.flags.carry = true;
-                }
-                self.pc += 1;
+    /// Undoes instructions until `pc` is back at a breakpoint whose condition holds or the
+    /// history runs out. Returns whether a breakpoint stopped it along with the number of
+    /// instructions undone.
+    pub fn run_back(&mut self) -> (bool, u64) {
+        let mut steps = 0;
+        while self.step_back() {
+            steps += 1;
+            if self.at_breakpoint() {
+                return (true, steps);
             }
+        }
+        (false, steps)
+    }
 
-            // ADD M
-            0x86 => {
-                let address = u16::from_be_bytes([self.h, self.l]);
-                let value = self.read_memory(address as usize);
-
-                if let Some(result) = self.accumulator.checked_add(value) {
-                    self.accumulator = result;
-                } else {
-                    self.flags.carry = true;
-                }
-
-                self.pc += 1;
+    /// Steps until something stops execution, `pc` leaves `image` or `max_steps` instructions
+    /// ran, and returns why it stopped along with the number of instructions executed.
+    pub fn run(&mut self, image: &Image, max_steps: u64) -> (Stop, u64) {
+        let mut steps = 0;
+        while steps < max_steps {
+            if decode(&self.memory, self.pc).is_none() {
+                return (Stop::UndefinedOpcode(self.pc), steps);
             }
-            // LXI B, value
-            0x01 => {
-                let high_byte = self.fetch();
-                let low_byte = self.fetch();
-                self.b = high_byte;
-                self.c = low_byte;
+            steps += 1;
+            if let Some(stop) = self.step() {
+                return (stop, steps);
             }
-
-            // LXI D, value
-            0x11 => {
-                let high_byte = self.fetch();
-                let low_byte = self.fetch();
-                self.d = high_byte;
-                self.e = low_byte;
-            }
-
-            // LXI H, value
-            0x21 => {
-                self.pc += 1;
-                let high_byte = self.fetch();
-                self.pc += 1;
-                let low_byte = self.fetch();
-                self.h = low_byte;
-                self.l = high_byte;
-                self.pc += 1;
+            if !image.contains(self.pc) {
+                return (Stop::LeftProgram(self.pc), steps);
             }
+        }
+        (Stop::StepLimit, steps)
+    }
 
-            // MOV A, A
-            0x7F => {
-                self.pc += 1;
+    /// Executes an already fetched instruction, with `pc` pointing past it.
+    fn execute(&mut self, ins: Ins) {
+        match ins {
+            Ins::Mov(destination, source) => {
+                let value = self.register(source);
+                self.set_register(destination, value);
             }
-            // MOV A, B
-            0x78 => {
-                self.accumulator = self.b;
-                self.pc += 1;
+            Ins::Mvi(register, value) => {
+                self.set_register(register, value);
             }
-            // MOV A, C
-            0x79 => {
-                self.accumulator = self.c;
-                self.pc += 1;
+            Ins::Lxi(register, value) => {
+                self.set_pair(register, value);
             }
-
-            // MOV A, D
-            0x7A => {
-                self.accumulator = self.d;
-                self.pc += 1;
-            }
-            // MOV A, E
-            0x7B => {
-                self.accumulator = self.e;
-                self.pc += 1;
-            }
-            // MOV A, H
-            0x7C => {
-                self.accumulator = self.h;
-                self.pc += 1;
-            }
-            // MOV A, L
-            0x7D => {
-                self.accumulator = self.l;
-                self.pc += 1;
-            }
-            // MOV A, M
-            0x7E => {
-                let address = u16::from_be_bytes([self.h, self.l]);
+            Ins::Lda(address) => {
                 self.accumulator = self.read_memory(address as usize);
-                self.pc += 1;
-            }
-            // MOV B, A
-            0x47 => {
-                self.b = self.accumulator;
-                self.pc += 1;
-            }
-            // MOV B, B
-            0x40 => {
-                self.pc += 1;
-            }
-            // MOV B, C
-            0x41 => {
-                self.b = self.c;
-                self.pc += 1;
-            }
-            // MOV B, D
-            0x42 => {
-                self.b = self.d;
-                self.pc += 1;
-            }
-            // MOV B, E
-            0x43 => {
-                self.b = self.e;
-                self.pc += 1;
-            }
-            // MOV B, H
-            0x44 => {
-                self.b = self.h;
-                self.pc += 1;
-            }
-            // MOV B, L
-            0x45 => {
-                self.b = self.l;
-                self.pc += 1;
-            }
-            // MOV B, M
-            0x46 => {
-                let address = u16::from_be_bytes([self.h, self.l]);
-                self.b = self.read_memory(address as usize);
-                self.pc += 1;
-            }
-            // MOV C, A
-            0x4F => {
-                self.c = self.accumulator;
-                self.pc += 1;
-            }
-            // MOV C, B
-            0x48 => {
-                self.c = self.b;
-                self.pc += 1;
-            }
-            // MOV C, C
-            0x49 => {
-                self.pc += 1;
-            }
-            // MOV C, D
-            0x4A => {
-                self.c = self.d;
-                self.pc += 1;
-            }
-            // MOV C, E
-            0x4B => {
-                self.c = self.e;
-                self.pc += 1;
-            }
-            // MOV C, H
-            0x4C => {
-                self.c = self.h;
-                self.pc += 1;
-            }
-            // MOV C, L
-            0x4D => {
-                self.c = self.l;
-                self.pc += 1;
-            }
-            // MOV D, A
-            0x57 => {
-                self.d = self.accumulator;
-            }
-            // MOV D, B
-            0x50 => {
-                self.d = self.b;
-            }
-            // MOV D, C
-            0x51 => {
-                self.d = self.c;
             }
-
-            // MOV D, D
-            0x52 => {
-                self.pc += 1;
+            Ins::Sta(address) => {
+                self.write_memory(address as usize, self.accumulator);
             }
-            // MOV D, E
-            0x53 => {
-                self.d = self.e;
+            Ins::Lhld(address) => {
+                let value = self.read_word(address);
+                self.set_pair(Registers::RegH, value);
             }
-            // MOV D, H
-            0x54 => {
-                self.d = self.h;
+            Ins::Shld(address) => {
+                self.write_word(address, self.pair(Registers::RegH));
             }
-            // MOV D, L
-            0x55 => {
-                self.d = self.l;
+            Ins::Ldax(register) => {
+                self.accumulator = self.read_memory(self.pair(register) as usize);
             }
-
-            // MOV E, A
-            0x5F => {
-                self.e = self.accumulator;
+            Ins::Stax(register) => {
+                self.write_memory(self.pair(register) as usize, self.accumulator);
             }
-
-            // MOV E, B
-            0x58 => {
-                self.e = self.b;
+            Ins::Xchg => {
+                std::mem::swap(&mut self.d, &mut self.h);
+                std::mem::swap(&mut self.e, &mut self.l);
             }
 
-            // MOV E, C
-            0x59 => {
-                self.e = self.c;
+            Ins::Add(register) | Ins::Adc(register) => {
+                let carry = matches!(ins, Ins::Adc(_)) && self.flags.carry;
+                self.accumulator = self.add(self.register(register), carry);
             }
-
-            // MOV E, D
-            0x5A => {
-                self.e = self.d;
+            Ins::Sub(register) | Ins::Sbb(register) => {
+                let borrow = matches!(ins, Ins::Sbb(_)) && self.flags.carry;
+                self.accumulator = self.subtract(self.register(register), borrow);
             }
-
-            // MOV E, E
-            0x5B => {
-                self.pc += 1;
+            Ins::Cmp(register) => {
+                self.subtract(self.register(register), false);
             }
-
-            // MOV E, H
-            0x5C => {
-                self.e = self.h;
+            Ins::Ana(register) => {
+                self.logic(self.accumulator & self.register(register), true);
             }
-
-            // MOV E, L
-            0x5D => {
-                self.e = self.l;
+            Ins::Xra(register) => {
+                self.logic(self.accumulator ^ self.register(register), false);
             }
-
-            // MOV H, A
-            0x67 => {
-                self.h = self.accumulator;
-                self.pc += 1;
+            Ins::Ora(register) => {
+                self.logic(self.accumulator | self.register(register), false);
             }
-
-            // MOV H, B
-            0x60 => {
-                self.h = self.b;
+            Ins::Adi(value) => {
+                self.accumulator = self.add(value, false);
             }
-
-            // MOV H, C
-            0x61 => {
-                self.h = self.c;
+            Ins::Aci(value) => {
+                self.accumulator = self.add(value, self.flags.carry);
             }
-
-            // MOV H, D
-            0x62 => {
-                self.h = self.d;
+            Ins::Sui(value) => {
+                self.accumulator = self.subtract(value, false);
             }
-
-            // MOV H, E
-            0x63 => {
-                self.h = self.e;
+            Ins::Sbi(value) => {
+                self.accumulator = self.subtract(value, self.flags.carry);
             }
-
-            // MOV H, L
-            0x65 => {
-                self.h = self.l;
+            Ins::Cpi(value) => {
+                self.subtract(value, false);
             }
-
-            // MOV L, A
-            0x6F => {
-                self.l = self.accumulator;
-                self.pc += 1;
+            Ins::Ani(value) => {
+                self.logic(self.accumulator & value, true);
             }
-
-            // MOV L, B
-            0x68 => {
-                self.l = self.b;
+            Ins::Xri(value) => {
+                self.logic(self.accumulator ^ value, false);
             }
-
-            // MOV L, C
-            0x69 => {
-                self.l = self.c;
+            Ins::Ori(value) => {
+                self.logic(self.accumulator | value, false);
             }
 
-            // MOV L, D
-            0x6A => {
-                self.l = self.d;
+            Ins::Inr(register) => {
+                let value = self.register(register);
+                let result = value.wrapping_add(1);
+                self.flags.auxiliary_carry = value & 0x0F == 0x0F;
+                self.set_result_flags(result);
+                self.set_register(register, result);
             }
-
-            // MOV L, E
-            0x6B => {
-                self.l = self.e;
+            Ins::Dcr(register) => {
+                let value = self.register(register);
+                let result = value.wrapping_sub(1);
+                self.flags.auxiliary_carry = value & 0x0F != 0;
+                self.set_result_flags(result);
+                self.set_register(register, result);
             }
-
-            // MOV L, H
-            0x6C => {
-                self.l = self.h;
+            Ins::Inx(register) => {
+                self.set_pair(register, self.pair(register).wrapping_add(1));
             }
-
-            // MOV L, L
-            0x6D => {
-                self.pc += 1;
+            Ins::Dcx(register) => {
+                self.set_pair(register, self.pair(register).wrapping_sub(1));
             }
-
-            // MOV M, A
-            0x77 => {
-                let address = u16::from_be_bytes([self.h, self.l]);
-                self.write_memory(address as usize, self.accumulator);
-                self.pc += 1;
+            Ins::Dad(register) => {
+                let (result, carry) = self
+                    .pair(Registers::RegH)
+                    .overflowing_add(self.pair(register));
+                self.set_pair(Registers::RegH, result);
+                self.flags.carry = carry;
             }
-
-            // MOV M, B
-            0x70 => {
-                let address = u16::from_be_bytes([self.h, self.l]);
-                self.write_memory(address as usize, self.b);
-                self.pc += 1;
-            }
-
-            // MVI A, value
-            0x3E => {
-                self.pc += 1;
-                self.accumulator = self.fetch();
-                self.pc += 1;
+            Ins::Daa => {
+                self.decimal_adjust();
             }
 
-            // MVI B, value
-            0x06 => {
-                self.pc += 1;
-                self.b = self.fetch();
-                self.pc += 1;
-            }
-            // MVI C, value
-            0x0E => {
-                self.pc += 1;
-                self.c = self.fetch();
-                self.pc += 1;
-            }
-            // MVI D, value
-            0x16 => {
-                self.d = self.fetch();
-            }
-            // MVI E, value
-            0x1E => {
-                self.e = self.fetch();
-            }
-            // MVI H, value
-            0x26 => {
-                self.pc += 1;
-                self.h = self.fetch();
-                self.pc += 1;
-            }
-            // MVI L, value
-            0x2E => {
-                self.pc += 1;
-                self.l = self.fetch();
-                self.pc += 1;
-            }
-
-            // ANA A
-            0xA7 => {
-                self.accumulator &= self.accumulator;
-            }
-            // ANA B
-            0xA0 => {
-                self.accumulator &= self.b;
+            Ins::Cma => {
+                self.accumulator = !self.accumulator;
             }
-            // ANA C
-            0xA1 => {
-                self.accumulator &= self.c;
+            Ins::Cmc => {
+                self.flags.carry = !self.flags.carry;
             }
-            // ANA D
-            0xA2 => {
-                self.accumulator &= self.d;
+            Ins::Stc => {
+                self.flags.carry = true;
             }
-            // ANA E
-            0xA3 => {
-                self.accumulator &= self.e;
+            Ins::Rlc => {
+                self.flags.carry = self.accumulator & 0x80 != 0;
+                self.accumulator = self.accumulator.rotate_left(1);
             }
-            // ANA H
-            0xA4 => {
-                self.accumulator &= self.h;
+            Ins::Rrc => {
+                self.flags.carry = self.accumulator & 0x01 != 0;
+                self.accumulator = self.accumulator.rotate_right(1);
             }
-            // ANA L
-            0xA5 => {
-                self.accumulator &= self.l;
+            Ins::Ral => {
+                let carry = self.flags.carry as u8;
+                self.flags.carry = self.accumulator & 0x80 != 0;
+                self.accumulator = self.accumulator << 1 | carry;
             }
-            // ANA M
-            0xA6 => {
-                let address = u16::from_be_bytes([self.h, self.l]);
-                self.accumulator &= self.read_memory(address as usize);
+            Ins::Rar => {
+                let carry = self.flags.carry as u8;
+                self.flags.carry = self.accumulator & 0x01 != 0;
+                self.accumulator = self.accumulator >> 1 | carry << 7;
             }
 
-            // ORA A
-            0xB7 => {
-                self.accumulator |= self.accumulator;
+            Ins::Jmp(target) => {
+                self.pc = address(target);
             }
-            // ORA B
-            0xB0 => {
-                self.accumulator |= self.b;
+            Ins::Jnz(target)
+            | Ins::Jz(target)
+            | Ins::Jnc(target)
+            | Ins::Jc(target)
+            | Ins::Jpo(target)
+            | Ins::Jpe(target)
+            | Ins::Jp(target)
+            | Ins::Jm(target) => {
+                if self.condition(&ins) {
+                    self.pc = address(target);
+                }
             }
-            // ORA C
-            0xB1 => {
-                self.accumulator |= self.c;
+            Ins::Call(target) => {
+                self.push(self.pc);
+                self.pc = address(target);
+            }
+            Ins::Cnz(target)
+            | Ins::Cz(target)
+            | Ins::Cnc(target)
+            | Ins::Cc(target)
+            | Ins::Cpo(target)
+            | Ins::Cpe(target)
+            | Ins::Cp(target)
+            | Ins::Cm(target) => {
+                if self.condition(&ins) {
+                    self.push(self.pc);
+                    self.pc = address(target);
+                }
             }
-            // ORA D
-            0xB2 => {
-                self.accumulator |= self.d;
+            Ins::Ret => {
+                self.pc = self.pop();
             }
-            // ORA E
-            0xB3 => {
-                self.accumulator |= self.e;
+            Ins::Rnz | Ins::Rz | Ins::Rnc | Ins::Rc | Ins::Rpo | Ins::Rpe | Ins::Rp | Ins::Rm => {
+                if self.condition(&ins) {
+                    self.pc = self.pop();
+                }
             }
-            // ORA H
-            0xB4 => {
-                self.accumulator |= self.h;
+            Ins::Rst(vector) => {
+                self.push(self.pc);
+                self.pc = vector as u16 * 8;
             }
-            // ORA L
-            0xB5 => {
-                self.accumulator |= self.l;
+            Ins::Pchl => {
+                self.pc = self.pair(Registers::RegH);
             }
 
-            // CMA
-            0x2F => {
-                self.accumulator = !self.accumulator;
+            Ins::Push(register) => {
+                self.push(self.pair(register));
             }
-
-            // CMP A
-            0xBF => {
-                self.flags.sign = (self.accumulator.wrapping_sub(self.accumulator) & 0x80) != 0;
+            Ins::Pop(register) => {
+                let value = self.pop();
+                self.set_pair(register, value);
             }
-
-            // CMP B
-            0xB8 => {
-                self.flags.zero = self.accumulator == self.b;
-                self.flags.sign = (self.accumulator.wrapping_sub(self.b) & 0x80) != 0;
-                self.flags.carry = self.b > self.accumulator;
+            Ins::Xthl => {
+                let value = self.read_word(self.sp);
+                self.write_word(self.sp, self.pair(Registers::RegH));
+                self.set_pair(Registers::RegH, value);
             }
-
-            // CMP C
-            0xB9 => {
-                self.flags.zero = self.accumulator == self.c;
-                self.flags.sign = (self.accumulator.wrapping_sub(self.c) & 0x80) != 0;
-                self.flags.carry = self.c > self.accumulator;
-            }
-            // CMP D
-            0xBA => {
-                self.flags.zero = self.accumulator == self.d;
-                self.flags.sign = (self.accumulator.wrapping_sub(self.d) & 0x80) != 0;
-                self.flags.carry = self.d > self.accumulator;
-            }
-            // CMP E
-            0xBB => {
-                self.flags.zero = self.accumulator == self.e;
-                self.flags.sign = (self.accumulator.wrapping_sub(self.e) & 0x80) != 0;
-                self.flags.carry = self.e > self.accumulator;
-            }
-            // CMP H
-            0xBC => {
-                self.flags.zero = self.accumulator == self.h;
-                self.flags.sign = (self.accumulator.wrapping_sub(self.h) & 0x80) != 0;
-                self.flags.carry = self.h > self.accumulator;
+            Ins::Sphl => {
+                self.sp = self.pair(Registers::RegH);
             }
 
-            // CMP L
-            0xBD => {
-                self.flags.zero = self.accumulator == self.l;
-                self.flags.sign = (self.accumulator.wrapping_sub(self.l) & 0x80) != 0;
-                self.flags.carry = self.l > self.accumulator;
+            Ins::In(port) => {
+                self.accumulator = self.ports[port as usize];
             }
-
-            // CMP M
-            0xBE => {
-                let address = u16::from_be_bytes([self.h, self.l]);
-                self.flags.zero = self.accumulator == self.read_memory(address as usize);
-                self.flags.sign = (self
-                    .accumulator
-                    .wrapping_sub(self.read_memory(address as usize))
-                    & 0x80)
-                    != 0;
-                self.flags.carry = self.read_memory(address as usize) > self.accumulator;
-                self.pc += 1;
+            Ins::Out(port) => {
+                self.ports[port as usize] = self.accumulator;
             }
-
-            // SHLD address
-            0x22 => {
-                self.pc += 1;
-                let low_byte_address = self.fetch();
-                self.pc += 1;
-                let high_byte_address = self.fetch();
-                let address = u16::from_be_bytes([high_byte_address, low_byte_address]);
-                self.write_memory(address as usize, self.l);
-                self.write_memory((address + 1) as usize, self.h);
-                self.pc += 1;
-            }
-            // STA Address
-            0x32 => {
-                self.pc += 1;
-                let low_byte_address = self.fetch();
-                self.pc += 1;
-                let high_byte_address = self.fetch();
-                let address = u16::from_be_bytes([high_byte_address, low_byte_address]);
-                self.write_memory(address as usize, self.accumulator);
-                self.pc += 1;
+            Ins::Ei => {
+                self.interrupts_enabled = true;
             }
-
-            // JMP Address
-            0xC3 => {
-                self.pc += 1;
-                let low_byte_address = self.fetch();
-                self.pc += 1;
-                let high_byte_address = self.fetch();
-                let address = u16::from_be_bytes([high_byte_address, low_byte_address]);
-                self.pc = address;
+            Ins::Di => {
+                self.interrupts_enabled = false;
             }
-
-            // JNZ address
-            0xC2 => {
-                self.pc += 1;
-                let low_byte_address = self.fetch();
-                self.pc += 1;
-                let high_byte_address = self.fetch();
-                let address = u16::from_be_bytes([high_byte_address, low_byte_address]);
-                if !self.flags.zero {
-                    self.pc = address;
-                } else {
-                    self.pc += 1;
-                }
+            Ins::Rim => {
+                self.accumulator = (self.serial_output as u8) << 7
+                    | (self.interrupts_enabled as u8) << 3
+                    | self.interrupt_mask;
             }
-
-            // JNC Address
-            0xD2 => {
-                self.pc += 1;
-                let low_byte_address = self.fetch();
-                self.pc += 1;
-                let high_byte_address = self.fetch();
-                let address = u16::from_be_bytes([high_byte_address, low_byte_address]);
-                if !self.flags.carry {
-                    self.pc = address;
-                } else {
-                    self.pc += 1;
+            Ins::Sim => {
+                if self.accumulator & 0x08 != 0 {
+                    self.interrupt_mask = self.accumulator & 0x07;
+                }
+                if self.accumulator & 0x40 != 0 {
+                    self.serial_output = self.accumulator & 0x80 != 0;
                 }
             }
-
-            // ANI value
-            0xE6 => {
-                self.pc += 1;
-                let value = self.fetch();
-                self.accumulator = self.accumulator & value;
-                self.pc += 1;
-            }
-            0x76 => return None,
-            _ => {}
+            Ins::Nop => {}
+            Ins::Hlt => {}
+            Ins::Org(_) | Ins::Section(_) | Ins::Public(_) | Ins::Extrn(_) => {}
         }
+    }
+}
 
-        Some(self.pc)
+fn address(target: JumpTarget) -> u16 {
+    match target {
+        JumpTarget::Address(address) => address,
+        JumpTarget::Label(label) => panic!("unresolved label {} at run time", label),
     }
 }
@@ -0,0 +1,359 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::RangeInclusive;
+
+use super::{
+    cpu::Registers,
+    parser::{format_number, Ins, JumpTarget},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    /// `None` when the bytes aren't a documented 8085 instruction.
+    pub ins: Option<Ins<'static>>,
+}
+
+fn register(code: u8) -> Registers {
+    match code & 0x07 {
+        0 => Registers::RegB,
+        1 => Registers::RegC,
+        2 => Registers::RegD,
+        3 => Registers::RegE,
+        4 => Registers::RegH,
+        5 => Registers::RegL,
+        6 => Registers::RegM,
+        _ => Registers::RegA,
+    }
+}
+
+fn pair(opcode: u8, fourth: Registers) -> Registers {
+    match (opcode >> 4) & 0x03 {
+        0 => Registers::RegB,
+        1 => Registers::RegD,
+        2 => Registers::RegH,
+        _ => fourth,
+    }
+}
+
+/// Decodes the instruction at `address` and returns it with its length in bytes. Returns `None`
+/// for undocumented opcodes and for instructions whose operands run past the end of `memory`.
+pub fn decode(memory: &[u8], address: u16) -> Option<(Ins<'static>, u8)> {
+    let byte = |offset: usize| memory.get(address as usize + offset).copied();
+    let opcode = byte(0)?;
+    let data = || byte(1);
+    let word = || Some(u16::from_le_bytes([byte(1)?, byte(2)?]));
+    let target = || word().map(JumpTarget::Address);
+
+    let ins = match opcode {
+        0x76 => Ins::Hlt,
+        0x40..=0x7F => Ins::Mov(register(opcode >> 3), register(opcode)),
+        0x80..=0x87 => Ins::Add(register(opcode)),
+        0x88..=0x8F => Ins::Adc(register(opcode)),
+        0x90..=0x97 => Ins::Sub(register(opcode)),
+        0x98..=0x9F => Ins::Sbb(register(opcode)),
+        0xA0..=0xA7 => Ins::Ana(register(opcode)),
+        0xA8..=0xAF => Ins::Xra(register(opcode)),
+        0xB0..=0xB7 => Ins::Ora(register(opcode)),
+        0xB8..=0xBF => Ins::Cmp(register(opcode)),
+
+        0x00 => Ins::Nop,
+        0x20 => Ins::Rim,
+        0x30 => Ins::Sim,
+        0x01 | 0x11 | 0x21 | 0x31 => Ins::Lxi(pair(opcode, Registers::RegSP), word()?),
+        0x09 | 0x19 | 0x29 | 0x39 => Ins::Dad(pair(opcode, Registers::RegSP)),
+        0x03 | 0x13 | 0x23 | 0x33 => Ins::Inx(pair(opcode, Registers::RegSP)),
+        0x0B | 0x1B | 0x2B | 0x3B => Ins::Dcx(pair(opcode, Registers::RegSP)),
+        0x02 | 0x12 => Ins::Stax(pair(opcode, Registers::RegSP)),
+        0x0A | 0x1A => Ins::Ldax(pair(opcode, Registers::RegSP)),
+        0x22 => Ins::Shld(word()?),
+        0x2A => Ins::Lhld(word()?),
+        0x32 => Ins::Sta(word()?),
+        0x3A => Ins::Lda(word()?),
+        0x04 | 0x0C | 0x14 | 0x1C | 0x24 | 0x2C | 0x34 | 0x3C => Ins::Inr(register(opcode >> 3)),
+        0x05 | 0x0D | 0x15 | 0x1D | 0x25 | 0x2D | 0x35 | 0x3D => Ins::Dcr(register(opcode >> 3)),
+        0x06 | 0x0E | 0x16 | 0x1E | 0x26 | 0x2E | 0x36 | 0x3E => {
+            Ins::Mvi(register(opcode >> 3), data()?)
+        }
+        0x07 => Ins::Rlc,
+        0x0F => Ins::Rrc,
+        0x17 => Ins::Ral,
+        0x1F => Ins::Rar,
+        0x27 => Ins::Daa,
+        0x2F => Ins::Cma,
+        0x37 => Ins::Stc,
+        0x3F => Ins::Cmc,
+
+        0xC0 => Ins::Rnz,
+        0xC8 => Ins::Rz,
+        0xD0 => Ins::Rnc,
+        0xD8 => Ins::Rc,
+        0xE0 => Ins::Rpo,
+        0xE8 => Ins::Rpe,
+        0xF0 => Ins::Rp,
+        0xF8 => Ins::Rm,
+        0xC2 => Ins::Jnz(target()?),
+        0xCA => Ins::Jz(target()?),
+        0xD2 => Ins::Jnc(target()?),
+        0xDA => Ins::Jc(target()?),
+        0xE2 => Ins::Jpo(target()?),
+        0xEA => Ins::Jpe(target()?),
+        0xF2 => Ins::Jp(target()?),
+        0xFA => Ins::Jm(target()?),
+        0xC4 => Ins::Cnz(target()?),
+        0xCC => Ins::Cz(target()?),
+        0xD4 => Ins::Cnc(target()?),
+        0xDC => Ins::Cc(target()?),
+        0xE4 => Ins::Cpo(target()?),
+        0xEC => Ins::Cpe(target()?),
+        0xF4 => Ins::Cp(target()?),
+        0xFC => Ins::Cm(target()?),
+        0xC1 | 0xD1 | 0xE1 | 0xF1 => Ins::Pop(pair(opcode, Registers::RegPSW)),
+        0xC5 | 0xD5 | 0xE5 | 0xF5 => Ins::Push(pair(opcode, Registers::RegPSW)),
+        0xC7 | 0xCF | 0xD7 | 0xDF | 0xE7 | 0xEF | 0xF7 | 0xFF => Ins::Rst((opcode >> 3) & 0x07),
+        0xC3 => Ins::Jmp(target()?),
+        0xCD => Ins::Call(target()?),
+        0xC9 => Ins::Ret,
+        0xC6 => Ins::Adi(data()?),
+        0xCE => Ins::Aci(data()?),
+        0xD6 => Ins::Sui(data()?),
+        0xDE => Ins::Sbi(data()?),
+        0xE6 => Ins::Ani(data()?),
+        0xEE => Ins::Xri(data()?),
+        0xF6 => Ins::Ori(data()?),
+        0xFE => Ins::Cpi(data()?),
+        0xD3 => Ins::Out(data()?),
+        0xDB => Ins::In(data()?),
+        0xE3 => Ins::Xthl,
+        0xE9 => Ins::Pchl,
+        0xEB => Ins::Xchg,
+        0xF3 => Ins::Di,
+        0xF9 => Ins::Sphl,
+        0xFB => Ins::Ei,
+        _ => return None,
+    };
+    let length = instruction_length(opcode);
+    Some((ins, length))
+}
+
+/// Length in bytes of the instruction starting with `opcode`. Undocumented opcodes count as one
+/// byte.
+pub fn instruction_length(opcode: u8) -> u8 {
+    match opcode {
+        0x01 | 0x11 | 0x21 | 0x31 | 0x22 | 0x2A | 0x32 | 0x3A | 0xC3 | 0xCD => 3,
+        0xC2 | 0xCA | 0xD2 | 0xDA | 0xE2 | 0xEA | 0xF2 | 0xFA => 3,
+        0xC4 | 0xCC | 0xD4 | 0xDC | 0xE4 | 0xEC | 0xF4 | 0xFC => 3,
+        0x06 | 0x0E | 0x16 | 0x1E | 0x26 | 0x2E | 0x36 | 0x3E => 2,
+        0xC6 | 0xCE | 0xD6 | 0xDE | 0xE6 | 0xEE | 0xF6 | 0xFE | 0xD3 | 0xDB => 2,
+        _ => 1,
+    }
+}
+
+fn decode_line(memory: &[u8], address: u16) -> Line {
+    match decode(memory, address) {
+        Some((ins, length)) => Line {
+            address,
+            bytes: (0..length as usize)
+                .map(|offset| memory[address as usize + offset])
+                .collect(),
+            ins: Some(ins),
+        },
+        None => Line {
+            address,
+            bytes: memory.get(address as usize).copied().into_iter().collect(),
+            ins: None,
+        },
+    }
+}
+
+/// Decodes `range` front to back, treating every byte as the start of the next instruction.
+pub fn linear_sweep(memory: &[u8], range: RangeInclusive<u16>) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut address = *range.start() as usize;
+
+    while address <= *range.end() as usize && address < memory.len() {
+        let line = decode_line(memory, address as u16);
+        address += line.bytes.len().max(1);
+        lines.push(line);
+    }
+    lines
+}
+
+/// Follows the control flow from `entries`, decoding only bytes that can be reached as code.
+/// Jumps, calls and restarts are followed when their target lies inside `range`; `PCHL` and
+/// returns end a path.
+pub fn recursive_descent(memory: &[u8], entries: &[u16], range: RangeInclusive<u16>) -> Vec<Line> {
+    let mut lines: BTreeMap<u16, Line> = BTreeMap::new();
+    let mut pending: Vec<u16> = entries.to_vec();
+
+    while let Some(mut address) = pending.pop() {
+        while range.contains(&address) && !lines.contains_key(&address) {
+            let line = decode_line(memory, address);
+            let next = address.wrapping_add(line.bytes.len().max(1) as u16);
+            let ins = line.ins.clone();
+            lines.insert(address, line);
+
+            match ins {
+                Some(Ins::Jmp(JumpTarget::Address(target))) => {
+                    pending.push(target);
+                    break;
+                }
+                Some(
+                    Ins::Jnz(JumpTarget::Address(target))
+                    | Ins::Jz(JumpTarget::Address(target))
+                    | Ins::Jnc(JumpTarget::Address(target))
+                    | Ins::Jc(JumpTarget::Address(target))
+                    | Ins::Jpo(JumpTarget::Address(target))
+                    | Ins::Jpe(JumpTarget::Address(target))
+                    | Ins::Jp(JumpTarget::Address(target))
+                    | Ins::Jm(JumpTarget::Address(target))
+                    | Ins::Call(JumpTarget::Address(target))
+                    | Ins::Cnz(JumpTarget::Address(target))
+                    | Ins::Cz(JumpTarget::Address(target))
+                    | Ins::Cnc(JumpTarget::Address(target))
+                    | Ins::Cc(JumpTarget::Address(target))
+                    | Ins::Cpo(JumpTarget::Address(target))
+                    | Ins::Cpe(JumpTarget::Address(target))
+                    | Ins::Cp(JumpTarget::Address(target))
+                    | Ins::Cm(JumpTarget::Address(target)),
+                ) => pending.push(target),
+                Some(Ins::Rst(vector)) => pending.push(vector as u16 * 8),
+                Some(Ins::Ret | Ins::Pchl | Ins::Hlt) | None => break,
+                _ => {}
+            }
+            address = next;
+        }
+    }
+    lines.into_values().collect()
+}
+
+/// Renders `ins` in Intel syntax, replacing jump and call addresses that have a name in
+/// `symbols`.
+pub fn render<'a>(ins: &Ins<'a>, symbols: &HashMap<u16, &'a str>) -> String {
+    let named = |target: &JumpTarget<'a>| -> JumpTarget<'a> {
+        match target {
+            JumpTarget::Address(address) => symbols
+                .get(address)
+                .map_or(*target, |label| JumpTarget::Label(label)),
+            JumpTarget::Label(_) => *target,
+        }
+    };
+    let ins = match ins {
+        Ins::Jmp(target) => Ins::Jmp(named(target)),
+        Ins::Jnz(target) => Ins::Jnz(named(target)),
+        Ins::Jz(target) => Ins::Jz(named(target)),
+        Ins::Jnc(target) => Ins::Jnc(named(target)),
+        Ins::Jc(target) => Ins::Jc(named(target)),
+        Ins::Jpo(target) => Ins::Jpo(named(target)),
+        Ins::Jpe(target) => Ins::Jpe(named(target)),
+        Ins::Jp(target) => Ins::Jp(named(target)),
+        Ins::Jm(target) => Ins::Jm(named(target)),
+        Ins::Call(target) => Ins::Call(named(target)),
+        Ins::Cnz(target) => Ins::Cnz(named(target)),
+        Ins::Cz(target) => Ins::Cz(named(target)),
+        Ins::Cnc(target) => Ins::Cnc(named(target)),
+        Ins::Cc(target) => Ins::Cc(named(target)),
+        Ins::Cpo(target) => Ins::Cpo(named(target)),
+        Ins::Cpe(target) => Ins::Cpe(named(target)),
+        Ins::Cp(target) => Ins::Cp(named(target)),
+        Ins::Cm(target) => Ins::Cm(named(target)),
+        ins => ins.clone(),
+    };
+    ins.to_string()
+}
+
+/// Reverses an assembler symbol table so addresses can be looked up by value.
+pub fn symbols_by_address(symbols: &HashMap<String, u16>) -> HashMap<u16, &str> {
+    let mut by_address = HashMap::new();
+    for (label, address) in symbols {
+        let entry = by_address.entry(*address).or_insert(label.as_str());
+        if label.as_str() < *entry {
+            *entry = label.as_str();
+        }
+    }
+    by_address
+}
+
+/// Renders `lines` as a listing of `address: bytes  instruction`, with a `LABEL:` line in front
+/// of every address that has a symbol.
+pub fn listing(lines: &[Line], symbols: &HashMap<u16, &str>) -> String {
+    let mut output = String::new();
+    for line in lines {
+        if let Some(label) = symbols.get(&line.address) {
+            output.push_str(&format!("{}:\n", label));
+        }
+        let bytes: Vec<String> = line
+            .bytes
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        let text = match &line.ins {
+            Some(ins) => render(ins, symbols),
+            None => format!("DB {}", format_number(line.bytes[0])),
+        };
+        output.push_str(&format!(
+            "{:04X}: {:<9} {}\n",
+            line.address,
+            bytes.join(" "),
+            text
+        ));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::core::{
+        cpu::Registers,
+        parser::{Ins, JumpTarget},
+    };
+
+    use super::{decode, linear_sweep, listing, recursive_descent};
+
+    #[test]
+    fn test_decode() {
+        let memory = [0x21, 0x50, 0x20, 0x70, 0xC2, 0x03, 0x00, 0x08, 0x3E];
+        assert_eq!(
+            decode(&memory, 0),
+            Some((Ins::Lxi(Registers::RegH, 0x2050), 3))
+        );
+        assert_eq!(
+            decode(&memory, 3),
+            Some((Ins::Mov(Registers::RegM, Registers::RegB), 1))
+        );
+        assert_eq!(
+            decode(&memory, 4),
+            Some((Ins::Jnz(JumpTarget::Address(0x0003)), 3))
+        );
+        assert_eq!(decode(&memory, 7), None);
+        assert_eq!(decode(&memory, 8), None);
+    }
+
+    #[test]
+    fn test_listing() {
+        let memory = [0x21, 0x50, 0x20, 0x70, 0xC2, 0x03, 0x00, 0x08, 0x76];
+        let symbols = HashMap::from([(0x0003, "X")]);
+        assert_eq!(
+            listing(&linear_sweep(&memory, 0..=8), &symbols),
+            "0000: 21 50 20  LXI H, 2050H\n\
+             X:\n\
+             0003: 70        MOV M, B\n\
+             0004: C2 03 00  JNZ X\n\
+             0007: 08        DB 08H\n\
+             0008: 76        HLT\n"
+        );
+    }
+
+    #[test]
+    fn test_recursive_descent() {
+        // JMP over a data byte, then loop back with JNZ
+        let memory = [0xC3, 0x04, 0x00, 0xFF, 0x05, 0xC2, 0x04, 0x00, 0x76];
+        let addresses: Vec<u16> = recursive_descent(&memory, &[0], 0..=8)
+            .iter()
+            .map(|line| line.address)
+            .collect();
+        assert_eq!(addresses, vec![0x0000, 0x0004, 0x0005, 0x0008]);
+    }
+}
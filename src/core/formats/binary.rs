@@ -0,0 +1,60 @@
+use crate::core::image::Image;
+
+/// Lays `image` out as a flat binary starting at `base`. Gaps between segments are padded with
+/// `fill`; bytes below `base` are dropped.
+pub fn to_binary(image: &Image, base: u16, fill: u8) -> Vec<u8> {
+    let mut output = Vec::new();
+    for (address, value) in image.bytes() {
+        if address < base {
+            continue;
+        }
+        let index = (address - base) as usize;
+        if index >= output.len() {
+            output.resize(index, fill);
+            output.push(value);
+        } else {
+            output[index] = value;
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::{assembler::assemble, image::Image, parser::parse_source};
+
+    use super::to_binary;
+
+    #[test]
+    fn test_to_binary() {
+        let mut image = Image::new();
+        image.push(0x2000, &[0x3E, 0x99]);
+        image.push(0x2004, &[0x76]);
+
+        assert_eq!(
+            to_binary(&image, 0x2000, 0xFF),
+            vec![0x3E, 0x99, 0xFF, 0xFF, 0x76]
+        );
+        assert_eq!(
+            to_binary(&image, 0x1FFE, 0x00),
+            vec![0x00, 0x00, 0x3E, 0x99, 0x00, 0x00, 0x76]
+        );
+        assert_eq!(
+            to_binary(&image, 0x2001, 0xFF),
+            vec![0x99, 0xFF, 0xFF, 0x76]
+        );
+    }
+
+    #[test]
+    fn test_to_binary_out_of_order() {
+        let source = "ORG 3000H\nMVI A, 01H\nHLT\nORG 2000H\nMVI B, 02H\nHLT\n";
+        let image = assemble(&parse_source(source).unwrap());
+        let base = image.lowest_address().unwrap();
+        assert_eq!(base, 0x2000);
+
+        let binary = to_binary(&image, base, 0xFF);
+        assert_eq!(binary.len(), 0x1003);
+        assert_eq!(&binary[..3], &[0x06, 0x02, 0x76]);
+        assert_eq!(&binary[0x1000..], &[0x3E, 0x01, 0x76]);
+    }
+}
@@ -0,0 +1,41 @@
+use crate::core::image::Image;
+
+pub const DEFAULT_BYTES_PER_LINE: usize = 8;
+
+/// Renders every segment of `image` as `address: bytes` lines, the way they are keyed into a
+/// trainer kit.
+pub fn to_hex_dump(image: &Image, bytes_per_line: usize) -> String {
+    let bytes_per_line = bytes_per_line.max(1);
+    let mut output = String::new();
+
+    for segment in &image.segments {
+        for (index, chunk) in segment.bytes.chunks(bytes_per_line).enumerate() {
+            let address = segment
+                .address
+                .wrapping_add((index * bytes_per_line) as u16);
+            let values: Vec<String> = chunk.iter().map(|byte| format!("{:02X}", byte)).collect();
+            output.push_str(&format!("{:04X}: {}\n", address, values.join(" ")));
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::image::Image;
+
+    use super::to_hex_dump;
+
+    #[test]
+    fn test_to_hex_dump() {
+        let mut image = Image::new();
+        image.push(0x2000, &[0x3E, 0x99, 0x06, 0x98, 0xA0, 0x76]);
+        assert_eq!(to_hex_dump(&image, 4), "2000: 3E 99 06 98\n2004: A0 76\n");
+
+        image.push(0x3000, &[0x76]);
+        assert_eq!(
+            to_hex_dump(&image, 8),
+            "2000: 3E 99 06 98 A0 76\n3000: 76\n"
+        );
+    }
+}
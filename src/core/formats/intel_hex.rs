@@ -0,0 +1,225 @@
+use std::fmt;
+
+use crate::core::image::Image;
+
+pub const DEFAULT_RECORD_LENGTH: usize = 16;
+
+const DATA_RECORD: u8 = 0x00;
+const END_OF_FILE_RECORD: u8 = 0x01;
+const EXTENDED_SEGMENT_ADDRESS_RECORD: u8 = 0x02;
+const START_SEGMENT_ADDRESS_RECORD: u8 = 0x03;
+const EXTENDED_LINEAR_ADDRESS_RECORD: u8 = 0x04;
+const START_LINEAR_ADDRESS_RECORD: u8 = 0x05;
+
+#[derive(Debug, PartialEq)]
+pub enum IntelHexError {
+    MissingStartCode(usize),
+    InvalidDigit(usize),
+    InvalidLength(usize),
+    ChecksumMismatch(usize),
+    AddressOutOfRange(usize),
+    UnsupportedRecord(usize, u8),
+    MissingEndOfFile,
+}
+
+impl fmt::Display for IntelHexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntelHexError::MissingStartCode(line) => {
+                write!(f, "Line {}: record doesn't start with ':'", line)
+            }
+            IntelHexError::InvalidDigit(line) => write!(f, "Line {}: invalid hex digit", line),
+            IntelHexError::InvalidLength(line) => {
+                write!(f, "Line {}: record length doesn't match its data", line)
+            }
+            IntelHexError::ChecksumMismatch(line) => write!(f, "Line {}: checksum mismatch", line),
+            IntelHexError::AddressOutOfRange(line) => {
+                write!(f, "Line {}: address is outside the 64K address space", line)
+            }
+            IntelHexError::UnsupportedRecord(line, kind) => {
+                write!(f, "Line {}: unsupported record type {:02X}", line, kind)
+            }
+            IntelHexError::MissingEndOfFile => write!(f, "Missing end of file record"),
+        }
+    }
+}
+
+fn record(kind: u8, address: u16, data: &[u8]) -> String {
+    let [high, low] = address.to_be_bytes();
+    let mut bytes = vec![data.len() as u8, high, low, kind];
+    bytes.extend_from_slice(data);
+    let checksum = bytes
+        .iter()
+        .fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+        .wrapping_neg();
+    bytes.push(checksum);
+
+    let mut line = String::from(":");
+    for byte in bytes {
+        line.push_str(&format!("{:02X}", byte));
+    }
+    line.push('\n');
+    line
+}
+
+/// Encodes every segment of `image` as Intel HEX, with at most `record_length` data bytes per
+/// record. When `start_address` is given, a start segment address record is emitted before the
+/// end of file record.
+pub fn to_intel_hex(image: &Image, record_length: usize, start_address: Option<u16>) -> String {
+    let record_length = record_length.clamp(1, 0xFF);
+    let mut output = String::new();
+
+    for segment in &image.segments {
+        for (index, chunk) in segment.bytes.chunks(record_length).enumerate() {
+            let address = segment.address.wrapping_add((index * record_length) as u16);
+            output.push_str(&record(DATA_RECORD, address, chunk));
+        }
+    }
+
+    if let Some(start_address) = start_address {
+        let [high, low] = start_address.to_be_bytes();
+        output.push_str(&record(
+            START_SEGMENT_ADDRESS_RECORD,
+            0,
+            &[0x00, 0x00, high, low],
+        ));
+    }
+    output.push_str(&record(END_OF_FILE_RECORD, 0, &[]));
+    output
+}
+
+fn decode_line(line: &str, number: usize) -> Result<Vec<u8>, IntelHexError> {
+    let digits = line
+        .strip_prefix(':')
+        .ok_or(IntelHexError::MissingStartCode(number))?;
+    if digits.len() % 2 != 0 || !digits.is_ascii() {
+        return Err(IntelHexError::InvalidDigit(number));
+    }
+
+    let bytes = (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| IntelHexError::InvalidDigit(number))?;
+
+    if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+        return Err(IntelHexError::InvalidLength(number));
+    }
+    if bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0 {
+        return Err(IntelHexError::ChecksumMismatch(number));
+    }
+    Ok(bytes)
+}
+
+/// Reads the data records of an Intel HEX file into an image. The entry point is the start
+/// address record if there is one, otherwise the address of the first data record.
+pub fn read_intel_hex(source: &str) -> Result<Image, IntelHexError> {
+    let mut image = Image::new();
+    let mut start_address = None;
+
+    for (index, line) in source.lines().enumerate() {
+        let number = index + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let bytes = decode_line(line, number)?;
+        let address = u16::from_be_bytes([bytes[1], bytes[2]]);
+        let data = &bytes[4..bytes.len() - 1];
+
+        match bytes[3] {
+            DATA_RECORD => {
+                if address as usize + data.len() > 0x10000 {
+                    return Err(IntelHexError::AddressOutOfRange(number));
+                }
+                image.push(address, data);
+            }
+            END_OF_FILE_RECORD => {
+                image.entry = start_address
+                    .or(image.segments.first().map(|segment| segment.address))
+                    .unwrap_or(0);
+                return Ok(image);
+            }
+            EXTENDED_SEGMENT_ADDRESS_RECORD | EXTENDED_LINEAR_ADDRESS_RECORD => {
+                if data.iter().any(|byte| *byte != 0) {
+                    return Err(IntelHexError::AddressOutOfRange(number));
+                }
+            }
+            START_SEGMENT_ADDRESS_RECORD | START_LINEAR_ADDRESS_RECORD if data.len() == 4 => {
+                let high = u16::from_be_bytes([data[0], data[1]]) as u32;
+                let low = u16::from_be_bytes([data[2], data[3]]) as u32;
+                // A segment start address is CS:IP, a linear one a plain 32-bit address.
+                let start = match bytes[3] {
+                    START_SEGMENT_ADDRESS_RECORD => (high << 4) + low,
+                    _ => (high << 16) | low,
+                };
+                start_address = Some(
+                    u16::try_from(start).map_err(|_| IntelHexError::AddressOutOfRange(number))?,
+                );
+            }
+            kind => return Err(IntelHexError::UnsupportedRecord(number, kind)),
+        }
+    }
+    Err(IntelHexError::MissingEndOfFile)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::{cpu::Cpu, image::Image};
+
+    use super::{read_intel_hex, to_intel_hex, IntelHexError};
+
+    #[test]
+    fn test_to_intel_hex() {
+        let mut image = Image::new();
+        image.push(
+            0x2000,
+            &[0x21, 0x01, 0x25, 0x7E, 0x23, 0x86, 0x23, 0x77, 0x76],
+        );
+        assert_eq!(
+            to_intel_hex(&image, 4, Some(0x2000)),
+            ":042000002101257E17\n\
+             :042004002386237795\n\
+             :012008007661\n\
+             :0400000300002000D9\n\
+             :00000001FF\n"
+        );
+    }
+
+    #[test]
+    fn test_read_intel_hex() {
+        let bytes = [0x3E, 0x99, 0x06, 0x98, 0xA0, 0x76];
+        let mut image = Image::new();
+        image.push(0x1000, &bytes);
+        image.push(0xFFFA, &bytes);
+        image.entry = 0xFFFA;
+
+        let read = read_intel_hex(&to_intel_hex(&image, 16, Some(0xFFFA))).unwrap();
+        assert_eq!(read, image);
+
+        let mut cpu = Cpu::new();
+        cpu.load_image(&read);
+        assert_eq!(&cpu.memory[0xFFFA..], &bytes);
+        assert_eq!(cpu.pc, 0xFFFA);
+
+        assert_eq!(
+            read_intel_hex(":0120080076E1\n:00000001FF\n"),
+            Err(IntelHexError::ChecksumMismatch(1))
+        );
+        assert_eq!(
+            read_intel_hex(":012008007661\n"),
+            Err(IntelHexError::MissingEndOfFile)
+        );
+        assert_eq!(
+            read_intel_hex(":0400000500012000D6\n:00000001FF\n"),
+            Err(IntelHexError::AddressOutOfRange(1))
+        );
+        assert_eq!(
+            read_intel_hex(":0400000300012000D8\n:00000001FF\n")
+                .unwrap()
+                .entry,
+            0x2010
+        );
+    }
+}
@@ -0,0 +1,28 @@
+pub mod binary;
+pub mod hex_dump;
+pub mod intel_hex;
+pub mod srec;
+pub mod symbols;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    IntelHex,
+    Binary,
+    SRecord,
+    HexDump,
+    /// A relocatable object for the linker rather than an image.
+    Object,
+}
+
+impl OutputFormat {
+    pub fn from(name: &str) -> Option<OutputFormat> {
+        match name.to_lowercase().as_str() {
+            "hex" | "ihex" => Some(OutputFormat::IntelHex),
+            "bin" | "binary" => Some(OutputFormat::Binary),
+            "srec" | "s19" => Some(OutputFormat::SRecord),
+            "dump" => Some(OutputFormat::HexDump),
+            "obj" | "object" => Some(OutputFormat::Object),
+            _ => None,
+        }
+    }
+}
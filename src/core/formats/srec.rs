@@ -0,0 +1,61 @@
+use crate::core::image::Image;
+
+pub const DEFAULT_RECORD_LENGTH: usize = 16;
+
+fn record(kind: char, address: u16, data: &[u8]) -> String {
+    let [high, low] = address.to_be_bytes();
+    let mut bytes = vec![data.len() as u8 + 3, high, low];
+    bytes.extend_from_slice(data);
+    let checksum = !bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+    bytes.push(checksum);
+
+    let mut line = format!("S{}", kind);
+    for byte in bytes {
+        line.push_str(&format!("{:02X}", byte));
+    }
+    line.push('\n');
+    line
+}
+
+/// Encodes every segment of `image` as Motorola S-records: an S0 header carrying `header`, S1
+/// data records of at most `record_length` bytes, an S5 record count and an S9 record holding the
+/// image's entry point.
+pub fn to_srec(image: &Image, record_length: usize, header: &str) -> String {
+    let record_length = record_length.clamp(1, 0xFC);
+    let mut output = record('0', 0, &header.as_bytes()[..header.len().min(0xFC)]);
+
+    let mut count = 0;
+    for segment in &image.segments {
+        for (index, chunk) in segment.bytes.chunks(record_length).enumerate() {
+            let address = segment.address.wrapping_add((index * record_length) as u16);
+            output.push_str(&record('1', address, chunk));
+            count += 1;
+        }
+    }
+
+    output.push_str(&record('5', count as u16, &[]));
+    output.push_str(&record('9', image.entry, &[]));
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::image::Image;
+
+    use super::to_srec;
+
+    #[test]
+    fn test_to_srec() {
+        let mut image = Image::new();
+        image.push(0x2000, &[0x3E, 0x99, 0x06, 0x98, 0xA0, 0x76]);
+        image.entry = 0x2000;
+        assert_eq!(
+            to_srec(&image, 4, "HDR"),
+            "S00600004844521B\n\
+             S10720003E99069863\n\
+             S1052004A076C0\n\
+             S5030002FA\n\
+             S9032000DC\n"
+        );
+    }
+}
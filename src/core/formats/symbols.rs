@@ -0,0 +1,166 @@
+use std::fmt;
+
+use crate::core::image::Image;
+
+#[derive(Debug, PartialEq)]
+pub enum SymbolFileError {
+    InvalidLine(usize),
+}
+
+impl fmt::Display for SymbolFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SymbolFileError::InvalidLine(line) => write!(f, "Line {}: invalid symbol", line),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolEntry {
+    pub name: String,
+    pub address: u16,
+    pub line: Option<usize>,
+    pub file: Option<String>,
+}
+
+/// The symbols of `image` ordered by address, with their line in `file` when it is known.
+pub fn symbol_entries(image: &Image, file: Option<&str>) -> Vec<SymbolEntry> {
+    let mut entries: Vec<SymbolEntry> = image
+        .symbols
+        .iter()
+        .map(|(name, address)| {
+            let line = image.symbol_lines.get(name).copied();
+            SymbolEntry {
+                name: name.clone(),
+                address: *address,
+                line,
+                file: file.filter(|_| line.is_some()).map(str::to_string),
+            }
+        })
+        .collect();
+    entries.sort_by(|a, b| (a.address, &a.name).cmp(&(b.address, &b.name)));
+    entries
+}
+
+/// Writes the symbol file format: one symbol per line as `<address> <name>`, followed by
+/// `<line> <file>` when the symbol's source is known. Addresses are hex, lines decimal, the file
+/// name runs to the end of the line and `;` starts a comment.
+pub fn to_symbol_file(image: &Image, file: Option<&str>) -> String {
+    let mut output = String::from("; address name [line file]\n");
+    for entry in symbol_entries(image, file) {
+        output.push_str(&format!("{:04X} {}", entry.address, entry.name));
+        if let (Some(line), Some(file)) = (entry.line, &entry.file) {
+            output.push_str(&format!(" {} {}", line, file));
+        }
+        output.push('\n');
+    }
+    output
+}
+
+pub fn read_symbol_file(source: &str) -> Result<Vec<SymbolEntry>, SymbolFileError> {
+    let mut entries = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line = line.split(';').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let invalid = || SymbolFileError::InvalidLine(index + 1);
+        let mut fields = line.splitn(4, char::is_whitespace);
+        let address = fields
+            .next()
+            .and_then(|address| u16::from_str_radix(address, 16).ok())
+            .ok_or_else(invalid)?;
+        let name = fields.next().ok_or_else(invalid)?.to_string();
+        let source_line = match fields.next() {
+            Some(number) => Some(number.parse().map_err(|_| invalid())?),
+            None => None,
+        };
+        let file = match (source_line, fields.next()) {
+            (Some(_), Some(file)) => Some(file.trim().to_string()),
+            (Some(_), None) => return Err(invalid()),
+            _ => None,
+        };
+        entries.push(SymbolEntry {
+            name,
+            address,
+            line: source_line,
+            file,
+        });
+    }
+    Ok(entries)
+}
+
+/// Writes a NoICE command file that defines every symbol and maps every assembled instruction to
+/// its source line. Source without a file name is attributed to `file`; without a source map,
+/// only the symbols' lines are mapped.
+pub fn to_noice(image: &Image, file: Option<&str>) -> String {
+    let entries = symbol_entries(image, file);
+    let mut output = String::new();
+    for entry in &entries {
+        output.push_str(&format!("DEF {} 0x{:04X}\n", entry.name, entry.address));
+    }
+
+    if image.source_map.entries.is_empty() {
+        if let Some(file) = file {
+            output.push_str(&format!("FILE {}\n", file));
+            for entry in &entries {
+                if let Some(line) = entry.line {
+                    output.push_str(&format!("LINE {} 0x{:04X}\n", line, entry.address));
+                }
+            }
+            output.push_str("ENDFILE\n");
+        }
+        return output;
+    }
+    for (index, name) in image.source_map.files.iter().enumerate() {
+        let name = match (name.as_str(), file) {
+            ("", Some(file)) => file,
+            ("", None) => continue,
+            (name, _) => name,
+        };
+        output.push_str(&format!("FILE {}\n", name));
+        for (address, entry) in &image.source_map.entries {
+            if entry.file == index {
+                output.push_str(&format!("LINE {} 0x{:04X}\n", entry.location.line, address));
+            }
+        }
+        output.push_str("ENDFILE\n");
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::{assembler::assemble, parser::parse_source};
+
+    use super::{read_symbol_file, to_noice, to_symbol_file, SymbolEntry};
+
+    #[test]
+    fn test_symbol_files() {
+        let instructions =
+            parse_source("ORG 2000H\nSTART: MVI C, 03H\nLOOP: DCR C\nJNZ LOOP\n").unwrap();
+        let image = assemble(&instructions);
+
+        let text = to_symbol_file(&image, Some("loop.asm"));
+        assert_eq!(
+            text,
+            "; address name [line file]\n2000 START 2 loop.asm\n2002 LOOP 3 loop.asm\n"
+        );
+        assert_eq!(
+            read_symbol_file(&text).unwrap()[1],
+            SymbolEntry {
+                name: "LOOP".to_string(),
+                address: 0x2002,
+                line: Some(3),
+                file: Some("loop.asm".to_string()),
+            }
+        );
+        assert!(read_symbol_file("2000").is_err());
+
+        assert_eq!(
+            to_noice(&image, Some("loop.asm")),
+            "DEF START 0x2000\nDEF LOOP 0x2002\nFILE loop.asm\nLINE 2 0x2000\nLINE 3 0x2002\nLINE 4 0x2003\nENDFILE\n"
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use logos::Logos;
+
+use super::{
+    parser::{parse_lines, ParseError},
+    token::Token,
+};
+
+/// Width of the mnemonic column, enough for `SECTION`.
+const MNEMONIC_WIDTH: usize = 8;
+/// The label column is at least this wide, so short programs still get an indent.
+const MIN_LABEL_WIDTH: usize = 8;
+
+#[derive(Debug)]
+pub enum FormatError {
+    Parse(ParseError),
+    /// Two labels that would be the same once upper-cased.
+    LabelCase(String, String),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::Parse(err) => write!(f, "{}", err),
+            FormatError::LabelCase(first, second) => write!(
+                f,
+                "Labels '{}' and '{}' differ only in case, so they can't be formatted",
+                first, second
+            ),
+        }
+    }
+}
+
+/// Upper-cases everything but comments, since the assembler only accepts upper case mnemonics,
+/// registers and hex digits. Labels are upper-cased too, unless two of them would clash.
+fn normalize_case(source: &str) -> Result<String, FormatError> {
+    let mut labels: HashMap<String, &str> = HashMap::new();
+    for token in Token::lexer(source) {
+        if let Ok(Token::Label(label)) = token {
+            match labels.insert(label.to_uppercase(), label) {
+                Some(first) if first != label => {
+                    return Err(FormatError::LabelCase(first.to_string(), label.to_string()))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let lines: Vec<String> = source
+        .lines()
+        .map(|line| match line.find(';') {
+            Some(index) => format!("{}{}", line[..index].to_uppercase(), &line[index..]),
+            None => line.to_uppercase(),
+        })
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+/// Re-emits `source` with labels, mnemonics, operands and comments in aligned columns, upper
+/// case code and numbers written the way the disassembler writes them (`0AH`, `2050H`).
+/// Comments are kept; runs of blank lines are collapsed to one.
+pub fn format_source(source: &str) -> Result<String, FormatError> {
+    let normalized = normalize_case(source)?;
+    let lines = parse_lines(&normalized).map_err(FormatError::Parse)?;
+
+    let label_width = lines
+        .iter()
+        .filter_map(|line| line.label.map(|label| label.len() + 2))
+        .max()
+        .unwrap_or(0)
+        .max(MIN_LABEL_WIDTH);
+    let rendered: Vec<Option<(String, String)>> = lines
+        .iter()
+        .map(|line| {
+            line.ins.as_ref().map(|ins| {
+                let text = ins.to_string();
+                match text.split_once(' ') {
+                    Some((mnemonic, operands)) => (mnemonic.to_string(), operands.to_string()),
+                    None => (text, String::new()),
+                }
+            })
+        })
+        .collect();
+    let operand_width = rendered
+        .iter()
+        .flatten()
+        .map(|(_, operands)| operands.len())
+        .max()
+        .unwrap_or(0);
+
+    let mut output: Vec<String> = Vec::new();
+    for (line, ins) in lines.iter().zip(&rendered) {
+        let mut text = String::new();
+        if let Some(label) = line.label {
+            text.push_str(&format!("{}:", label));
+        }
+        if let Some((mnemonic, operands)) = ins {
+            text = format!(
+                "{:<label$}{:<mnemonic$}{}",
+                text,
+                mnemonic,
+                operands,
+                label = label_width,
+                mnemonic = MNEMONIC_WIDTH
+            );
+        }
+        if let Some(comment) = line.comment {
+            text = if line.label.is_some() || ins.is_some() {
+                format!(
+                    "{:<width$} {}",
+                    text,
+                    comment,
+                    width = label_width + MNEMONIC_WIDTH + operand_width
+                )
+            } else if line.comment_column == 1 {
+                comment.to_string()
+            } else {
+                format!("{:<width$}{}", "", comment, width = label_width)
+            };
+        }
+        let text = text.trim_end().to_string();
+
+        let blank = text.is_empty();
+        if blank && output.last().is_none_or(|last| last.is_empty()) {
+            continue;
+        }
+        output.push(text);
+    }
+    while output.last().is_some_and(|last| last.is_empty()) {
+        output.pop();
+    }
+
+    let mut formatted = output.join("\n");
+    formatted.push('\n');
+    Ok(formatted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_source;
+
+    #[test]
+    fn test_format_source() {
+        let source = "; Count down\n\n\n  lxi h,2050h\nmvi c,0ah   ;counter\nLOOP:\n dcr c\n  jnz loop ; again\n\n  ; done\nhlt\n\n";
+        let formatted = format_source(source).unwrap();
+        assert_eq!(
+            formatted,
+            "; Count down\n\
+             \n\
+             \x20       LXI     H, 2050H\n\
+             \x20       MVI     C, 0AH   ;counter\n\
+             LOOP:\n\
+             \x20       DCR     C\n\
+             \x20       JNZ     LOOP     ; again\n\
+             \n\
+             \x20       ; done\n\
+             \x20       HLT\n"
+        );
+        assert_eq!(format_source(&formatted).unwrap(), formatted);
+        assert_eq!(
+            format_source("a: NOP\nA: NOP\n").unwrap_err().to_string(),
+            "Labels 'a' and 'A' differ only in case, so they can't be formatted"
+        );
+        assert_eq!(
+            format_source("LOOP: DCR A @ $\n").unwrap_err().to_string(),
+            "Unexpected token: Line 1, column 13: can't read '@'"
+        );
+    }
+}
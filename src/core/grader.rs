@@ -0,0 +1,314 @@
+use std::fmt;
+
+use super::{
+    assembler::assemble,
+    breakpoints::Condition,
+    harness::{check, parse_annotation, run_case, stop_failure, Annotation, DEFAULT_MAX_STEPS},
+    json::{parse_json, Json, JsonError},
+    parser::parse_source,
+};
+
+/// An assignment: the cases a submission is run against and what each is worth.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spec {
+    pub name: Option<String>,
+    pub cases: Vec<Case>,
+}
+
+/// One run of a submission from a fresh CPU.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Case {
+    pub name: String,
+    pub points: u64,
+    /// `Set` and `MaxSteps` annotations applied before running.
+    pub setup: Vec<Annotation>,
+    pub expect: Vec<Condition>,
+    /// The most T-states the run may take, if limited.
+    pub max_cycles: Option<u64>,
+}
+
+#[derive(Debug)]
+pub enum SpecError {
+    Json(JsonError),
+    /// A field that is missing or has the wrong type, by its path.
+    InvalidField(String),
+    /// A setup or expectation that doesn't parse, by its path and with its text.
+    InvalidAnnotation(String, String),
+}
+
+impl fmt::Display for SpecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpecError::Json(err) => write!(f, "{}", err),
+            SpecError::InvalidField(path) => write!(f, "Missing or invalid {}", path),
+            SpecError::InvalidAnnotation(path, text) => {
+                write!(f, "Invalid {} '{}'", path, text)
+            }
+        }
+    }
+}
+
+/// Reads an optional field that must have the type `read` expects when present.
+fn optional<'a, T>(
+    json: &'a Json,
+    name: &str,
+    path: &str,
+    read: impl Fn(&'a Json) -> Option<T>,
+) -> Result<Option<T>, SpecError> {
+    json.get(name)
+        .map(|value| {
+            read(value).ok_or_else(|| SpecError::InvalidField(format!("{}{}", path, name)))
+        })
+        .transpose()
+}
+
+/// Reads an array of annotation strings, each parsed by `parse`.
+fn annotations<T>(
+    json: &Json,
+    name: &str,
+    path: &str,
+    parse: impl Fn(&str) -> Option<Vec<T>>,
+) -> Result<Vec<T>, SpecError> {
+    let mut parsed = Vec::new();
+    for (index, item) in optional(json, name, path, Json::as_array)?
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+    {
+        let item_path = format!("{}{}[{}]", path, name, index);
+        let text = item
+            .as_str()
+            .ok_or_else(|| SpecError::InvalidField(item_path.clone()))?;
+        parsed.extend(
+            parse(text.trim())
+                .ok_or_else(|| SpecError::InvalidAnnotation(item_path, text.to_string()))?,
+        );
+    }
+    Ok(parsed)
+}
+
+/// Reads a spec such as
+///
+/// ```json
+/// {"name": "Add two numbers", "max_steps": 1000, "cases": [
+///   {"name": "carry", "points": 2, "setup": ["mem 2501 = FF 01"],
+///    "expect": ["mem 2503 = 00", "CY == 1"], "max_cycles": 200}
+/// ]}
+/// ```
+///
+/// where setup and expectations use the `;@` annotation syntax, and `max_steps` and `max_cycles`
+/// may be given for the whole spec or per case.
+pub fn read_spec(text: &str) -> Result<Spec, SpecError> {
+    let json = parse_json(text).map_err(SpecError::Json)?;
+    let name = optional(&json, "name", "", |name| name.as_str().map(String::from))?;
+    let max_steps = optional(&json, "max_steps", "", Json::as_u64)?.unwrap_or(DEFAULT_MAX_STEPS);
+    let max_cycles = optional(&json, "max_cycles", "", Json::as_u64)?;
+    let cases = json
+        .get("cases")
+        .and_then(Json::as_array)
+        .filter(|cases| !cases.is_empty())
+        .ok_or_else(|| SpecError::InvalidField("cases".to_string()))?;
+
+    let mut spec = Spec {
+        name,
+        cases: Vec::new(),
+    };
+    for (index, case) in cases.iter().enumerate() {
+        let path = format!("cases[{}].", index);
+        let mut setup = vec![Annotation::MaxSteps(
+            optional(case, "max_steps", &path, Json::as_u64)?.unwrap_or(max_steps),
+        )];
+        setup.extend(annotations(case, "setup", &path, |text| {
+            parse_annotation(text)
+                .filter(|parsed| parsed.iter().all(|a| matches!(a, Annotation::Set(..))))
+        })?);
+        let expect = annotations(case, "expect", &path, |text| {
+            let parsed = parse_annotation(&format!("expect {}", text))?;
+            parsed
+                .into_iter()
+                .map(|annotation| match annotation {
+                    Annotation::Expect(condition) => Some(condition),
+                    _ => None,
+                })
+                .collect()
+        })?;
+        if expect.is_empty() {
+            return Err(SpecError::InvalidField(format!("{}expect", path)));
+        }
+        spec.cases.push(Case {
+            name: optional(case, "name", &path, |name| name.as_str().map(String::from))?
+                .unwrap_or_else(|| format!("case {}", index + 1)),
+            points: optional(case, "points", &path, Json::as_u64)?.unwrap_or(1),
+            setup,
+            expect,
+            max_cycles: optional(case, "max_cycles", &path, Json::as_u64)?.or(max_cycles),
+        });
+    }
+    Ok(spec)
+}
+
+/// How a submission did in one case.
+#[derive(Debug)]
+pub struct CaseResult {
+    pub name: String,
+    pub points: u64,
+    pub steps: u64,
+    pub cycles: u64,
+    pub failures: Vec<String>,
+}
+
+impl CaseResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// How a submission did against a spec.
+#[derive(Debug)]
+pub struct Grade {
+    pub results: Vec<CaseResult>,
+    /// Why the submission couldn't be run at all, failing every case.
+    pub error: Option<String>,
+}
+
+impl Grade {
+    pub fn score(&self) -> u64 {
+        self.results
+            .iter()
+            .filter(|result| result.passed())
+            .map(|result| result.points)
+            .sum()
+    }
+}
+
+impl Spec {
+    pub fn total(&self) -> u64 {
+        self.cases.iter().map(|case| case.points).sum()
+    }
+}
+
+/// Assembles `source` and runs it once per case of `spec`, each from a fresh CPU.
+pub fn grade(source: &str, spec: &Spec) -> Grade {
+    let instructions = match parse_source(source) {
+        Ok(instructions) => instructions,
+        Err(err) => {
+            return Grade {
+                results: spec
+                    .cases
+                    .iter()
+                    .map(|case| CaseResult {
+                        name: case.name.clone(),
+                        points: case.points,
+                        steps: 0,
+                        cycles: 0,
+                        failures: vec![err.to_string()],
+                    })
+                    .collect(),
+                error: Some(err.to_string()),
+            }
+        }
+    };
+    let image = assemble(&instructions);
+
+    let results = spec
+        .cases
+        .iter()
+        .map(|case| {
+            let (cpu, stop, steps) = run_case(&image, &case.setup);
+            let mut failures: Vec<String> = stop_failure(&image, stop, steps).into_iter().collect();
+            if let Some(max_cycles) = case.max_cycles.filter(|max| cpu.cycles > *max) {
+                failures.push(format!(
+                    "Took {} T-states, more than the limit of {}",
+                    cpu.cycles, max_cycles
+                ));
+            }
+            failures.extend(
+                case.expect
+                    .iter()
+                    .filter_map(|condition| check(&cpu, condition)),
+            );
+            CaseResult {
+                name: case.name.clone(),
+                points: case.points,
+                steps,
+                cycles: cpu.cycles,
+                failures,
+            }
+        })
+        .collect();
+    Grade {
+        results,
+        error: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{grade, read_spec};
+
+    const SPEC: &str = r#"{
+        "name": "Add two numbers",
+        "cases": [
+            {"name": "small", "setup": ["mem 2501 = 02 03"], "expect": ["mem 2503 = 05"]},
+            {"name": "carry", "points": 2, "setup": ["mem 2501 = FF 01"],
+             "expect": ["mem 2503 = 00", "CY == 1"], "max_cycles": 100}
+        ]
+    }"#;
+
+    #[test]
+    fn test_read_spec() {
+        let spec = read_spec(SPEC).unwrap();
+        assert_eq!(spec.name.as_deref(), Some("Add two numbers"));
+        assert_eq!(spec.total(), 3);
+        assert_eq!(spec.cases[1].expect.len(), 2);
+        assert_eq!(spec.cases[1].max_cycles, Some(100));
+
+        assert_eq!(
+            read_spec(r#"{"cases": [{"expect": []}]}"#)
+                .unwrap_err()
+                .to_string(),
+            "Missing or invalid cases[0].expect"
+        );
+        assert_eq!(
+            read_spec(r#"{"cases": [{"setup": ["expect A = 1"], "expect": ["A = 1"]}]}"#)
+                .unwrap_err()
+                .to_string(),
+            "Invalid cases[0].setup[0] 'expect A = 1'"
+        );
+    }
+
+    #[test]
+    fn test_grade() {
+        let spec = read_spec(SPEC).unwrap();
+        let source = "LXI H, 2501H\nMOV A, M\nINX H\nADD M\nINX H\nMOV M, A\nHLT\n";
+        let grade_ = grade(source, &spec);
+        assert!(grade_.results.iter().all(|result| result.passed()));
+        assert_eq!(grade_.score(), 3);
+
+        // Each case starts from a fresh CPU, so a forgotten carry only fails the second.
+        let grade_ = grade(&source.replace("ADD M", "ADD M\nSTC\nCMC"), &spec);
+        assert_eq!(grade_.score(), 1);
+        assert_eq!(
+            grade_.results[1].failures,
+            vec!["expected CY == 1H, found 0H"]
+        );
+
+        // Storing the right answer doesn't count if the program then jumps somewhere wild.
+        let grade_ = grade(&source.replace("HLT", "JMP 3000H"), &spec);
+        assert_eq!(grade_.score(), 0);
+        assert_eq!(
+            grade_.results[0].failures,
+            vec!["Jumped outside the program to 3000H"]
+        );
+        let grade_ = grade(&source.replace("HLT\n", ""), &spec);
+        assert_eq!(grade_.score(), 3);
+
+        let grade_ = grade("LOOP: JMP LOOP", &spec);
+        assert_eq!(grade_.score(), 0);
+        assert!(grade_.results[1].failures[1].starts_with("Took "));
+
+        let grade_ = grade("MOV A", &spec);
+        assert!(grade_.error.is_some());
+        assert_eq!(grade_.score(), 0);
+    }
+}
@@ -0,0 +1,271 @@
+use std::fmt;
+
+use super::{
+    assembler::assemble,
+    breakpoints::{parse_number, Comparison, Condition, Operand},
+    cpu::{Cpu, Stop},
+    image::Image,
+    parser::{parse_source, ParseError},
+};
+
+/// How many instructions a program runs before it counts as stuck, unless it says otherwise.
+pub const DEFAULT_MAX_STEPS: u64 = 100_000;
+
+/// What a `;@` comment asks of a test run.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Annotation {
+    /// Set before running: `;@ mem 2501 = 99 39` or `;@ B = 05`.
+    Set(Operand, u16),
+    /// Checked once the program stops: `;@ expect A = D2`, `;@ expect mem 2503 = D2` or any
+    /// breakpoint condition such as `;@ expect CY == 1`.
+    Expect(Condition),
+    /// `;@ max-steps 5000`
+    MaxSteps(u64),
+}
+
+#[derive(Debug)]
+pub enum HarnessError {
+    /// The 1-based line of an annotation that doesn't parse, with its text.
+    InvalidAnnotation(usize, String),
+    Parse(ParseError),
+}
+
+impl fmt::Display for HarnessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HarnessError::InvalidAnnotation(line, text) => {
+                write!(f, "Line {}: invalid annotation '{}'", line, text)
+            }
+            HarnessError::Parse(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// Parses the text of one annotation after `;@`; a `mem` annotation with several bytes gives one
+/// per byte.
+pub fn parse_annotation(text: &str) -> Option<Vec<Annotation>> {
+    let (expect, text) = match text.strip_prefix("expect ") {
+        Some(rest) => (true, rest.trim()),
+        None => (false, text),
+    };
+    let annotation = |operand, value| {
+        if expect {
+            Annotation::Expect(Condition {
+                operand,
+                comparison: Comparison::Equal,
+                value,
+            })
+        } else {
+            Annotation::Set(operand, value)
+        }
+    };
+
+    if let Some(steps) = text.strip_prefix("max-steps ").filter(|_| !expect) {
+        return Some(vec![Annotation::MaxSteps(steps.trim().parse().ok()?)]);
+    }
+    if let Some(rest) = text.strip_prefix("mem ") {
+        let (address, bytes) = rest.split_once('=')?;
+        let address = parse_number(address.trim())?;
+        let bytes = bytes
+            .split([' ', ','])
+            .filter(|byte| !byte.is_empty())
+            .map(|byte| parse_number(byte).filter(|byte| *byte <= 0xFF))
+            .collect::<Option<Vec<u16>>>()?;
+        if bytes.is_empty() {
+            return None;
+        }
+        return Some(
+            bytes
+                .into_iter()
+                .enumerate()
+                .map(|(offset, byte)| {
+                    annotation(Operand::Memory(address.wrapping_add(offset as u16)), byte)
+                })
+                .collect(),
+        );
+    }
+    if let Some(condition) = Condition::parse(text).filter(|_| expect) {
+        return Some(vec![Annotation::Expect(condition)]);
+    }
+    let (operand, value) = text.split_once('=')?;
+    Some(vec![annotation(
+        Operand::parse(operand)?,
+        parse_number(value.trim())?,
+    )])
+}
+
+/// The annotations in the comments of `source` that start with `;@`, with their 1-based lines.
+pub fn parse_annotations(source: &str) -> Result<Vec<(usize, Annotation)>, HarnessError> {
+    let mut annotations = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let Some(text) = line
+            .split_once(';')
+            .and_then(|(_, comment)| comment.strip_prefix('@'))
+        else {
+            continue;
+        };
+        let text = text.trim();
+        let parsed = parse_annotation(text)
+            .ok_or_else(|| HarnessError::InvalidAnnotation(index + 1, text.to_string()))?;
+        annotations.extend(parsed.into_iter().map(|annotation| (index + 1, annotation)));
+    }
+    Ok(annotations)
+}
+
+/// How a program did against its annotations.
+#[derive(Debug)]
+pub struct Report {
+    pub stop: Stop,
+    pub steps: u64,
+    pub checks: usize,
+    pub failures: Vec<String>,
+}
+
+impl Report {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Runs `image` after applying the `Set` and `MaxSteps` annotations, returning the CPU with how
+/// and after how many steps it stopped.
+pub fn run_case<'a>(
+    image: &Image,
+    annotations: impl IntoIterator<Item = &'a Annotation>,
+) -> (Cpu, Stop, u64) {
+    let mut cpu = Cpu::new();
+    cpu.load_image(image);
+    let mut max_steps = DEFAULT_MAX_STEPS;
+    for annotation in annotations {
+        match annotation {
+            Annotation::Set(operand, value) => operand.set(&mut cpu, *value),
+            Annotation::MaxSteps(steps) => max_steps = *steps,
+            Annotation::Expect(_) => {}
+        }
+    }
+    let (stop, steps) = cpu.run(image, max_steps);
+    (cpu, stop, steps)
+}
+
+/// Why a run that stopped with `stop` didn't end the way a program should, by halting or
+/// running off its end.
+pub fn stop_failure(image: &Image, stop: Stop, steps: u64) -> Option<String> {
+    match stop {
+        Stop::Halted => None,
+        Stop::LeftProgram(address) if Some(address) == image.end_address() => None,
+        Stop::LeftProgram(address) => {
+            Some(format!("Jumped outside the program to {:04X}H", address))
+        }
+        Stop::UndefinedOpcode(address) => Some(format!(
+            "Undefined opcode at {}",
+            image.source_map.describe(address)
+        )),
+        Stop::StepLimit => Some(format!("Still running after {} steps", steps)),
+        stop => Some(format!("Stopped early: {:?}", stop)),
+    }
+}
+
+/// Describes how `condition` fails on `cpu`, if it does.
+pub fn check(cpu: &Cpu, condition: &Condition) -> Option<String> {
+    (!condition.holds(cpu)).then(|| {
+        format!(
+            "expected {}, found {:X}H",
+            condition,
+            condition.operand.get(cpu)
+        )
+    })
+}
+
+/// Assembles `source`, sets it up as its annotations say, runs it until it halts or leaves the
+/// program, and checks its expectations.
+pub fn run_annotated(source: &str) -> Result<Report, HarnessError> {
+    let annotations = parse_annotations(source)?;
+    let instructions = parse_source(source).map_err(HarnessError::Parse)?;
+    let image = assemble(&instructions);
+    let (cpu, stop, steps) = run_case(&image, annotations.iter().map(|(_, annotation)| annotation));
+
+    let mut failures: Vec<String> = stop_failure(&image, stop, steps).into_iter().collect();
+    let mut checks = 0;
+    for (line, annotation) in &annotations {
+        let Annotation::Expect(condition) = annotation else {
+            continue;
+        };
+        checks += 1;
+        if let Some(failure) = check(&cpu, condition) {
+            failures.push(format!("Line {}: {}", line, failure));
+        }
+    }
+    if checks == 0 {
+        failures.push("Nothing to check, add `;@ expect` annotations".to_string());
+    }
+    Ok(Report {
+        stop,
+        steps,
+        checks,
+        failures,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::{
+        breakpoints::{Comparison, Condition, Operand},
+        cpu::Registers,
+    };
+
+    use super::{parse_annotations, run_annotated, Annotation};
+
+    #[test]
+    fn test_annotations() {
+        let source = "MVI A, 01H ;@ mem 2501 = 99, 39\n;@ B = 5\n;@ expect mem 2503 = D2\n;@ expect CY != 1\n;@ max-steps 10\n";
+        assert_eq!(
+            parse_annotations(source).unwrap(),
+            vec![
+                (1, Annotation::Set(Operand::Memory(0x2501), 0x99)),
+                (1, Annotation::Set(Operand::Memory(0x2502), 0x39)),
+                (2, Annotation::Set(Operand::Register(Registers::RegB), 0x05)),
+                (
+                    3,
+                    Annotation::Expect(Condition {
+                        operand: Operand::Memory(0x2503),
+                        comparison: Comparison::Equal,
+                        value: 0xD2
+                    })
+                ),
+                (4, Annotation::Expect(Condition::parse("CY != 1").unwrap())),
+                (5, Annotation::MaxSteps(10)),
+            ]
+        );
+        assert_eq!(
+            parse_annotations("NOP\n;@ expect Q = 1")
+                .unwrap_err()
+                .to_string(),
+            "Line 2: invalid annotation 'expect Q = 1'"
+        );
+    }
+
+    #[test]
+    fn test_run_annotated() {
+        let source = "LDA 2501H ;@ mem 2501 = 40\nADD B ;@ B = 2\nHLT\n;@ expect A = 42\n";
+        let report = run_annotated(source).unwrap();
+        assert!(report.passed(), "{:?}", report.failures);
+        assert_eq!((report.checks, report.steps), (1, 3));
+
+        let report = run_annotated(&source.replace("= 42", "= 43")).unwrap();
+        assert_eq!(
+            report.failures,
+            vec!["Line 4: expected A == 43H, found 42H"]
+        );
+
+        let report = run_annotated("LOOP: JMP LOOP\n;@ max-steps 5\n;@ expect A = 0").unwrap();
+        assert_eq!(report.failures, vec!["Still running after 5 steps"]);
+        let report = run_annotated("HLT").unwrap();
+        assert!(!report.passed());
+
+        // Running off the end is fine, jumping somewhere else isn't.
+        let source = "MVI A, 05H\nSTA 2503H\n;@ expect mem 2503 = 05\n";
+        assert!(run_annotated(source).unwrap().passed());
+        let report = run_annotated(&format!("{}JMP 3000H\n", source)).unwrap();
+        assert_eq!(report.failures, vec!["Jumped outside the program to 3000H"]);
+    }
+}
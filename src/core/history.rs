@@ -0,0 +1,119 @@
+use std::collections::VecDeque;
+
+use super::{
+    breakpoints::Access,
+    cpu::{Cpu, FlagRegisters},
+};
+
+pub const DEFAULT_CAPACITY: usize = 10_000;
+
+/// The state one instruction is about to change: the registers and the old values of the memory
+/// and ports it writes.
+#[derive(Debug, Clone, PartialEq)]
+struct Delta {
+    pc: u16,
+    sp: u16,
+    registers: [u8; 7],
+    flags: u8,
+    interrupts_enabled: bool,
+    interrupt_mask: u8,
+    serial_output: bool,
+    cycles: u64,
+    memory: Vec<(u16, u8)>,
+    ports: Vec<(u8, u8)>,
+}
+
+impl Delta {
+    fn capture(cpu: &Cpu, accesses: &[Access]) -> Self {
+        Self {
+            pc: cpu.pc,
+            sp: cpu.sp,
+            registers: [cpu.accumulator, cpu.b, cpu.c, cpu.d, cpu.e, cpu.h, cpu.l],
+            flags: cpu.flags.to_byte(),
+            interrupts_enabled: cpu.interrupts_enabled,
+            interrupt_mask: cpu.interrupt_mask,
+            serial_output: cpu.serial_output,
+            cycles: cpu.cycles,
+            memory: accesses
+                .iter()
+                .filter_map(|access| match access {
+                    Access::Write(address) => Some((*address, cpu.read_memory(*address as usize))),
+                    _ => None,
+                })
+                .collect(),
+            ports: accesses
+                .iter()
+                .filter_map(|access| match access {
+                    Access::Out(port) => Some((*port, cpu.ports[*port as usize])),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    fn restore(self, cpu: &mut Cpu) {
+        // Undo in reverse so a byte written twice gets its oldest value
+        for (address, value) in self.memory.into_iter().rev() {
+            cpu.write_memory(address as usize, value);
+        }
+        for (port, value) in self.ports.into_iter().rev() {
+            cpu.ports[port as usize] = value;
+        }
+        [cpu.accumulator, cpu.b, cpu.c, cpu.d, cpu.e, cpu.h, cpu.l] = self.registers;
+        cpu.pc = self.pc;
+        cpu.sp = self.sp;
+        cpu.flags = FlagRegisters::from_byte(self.flags);
+        cpu.interrupts_enabled = self.interrupts_enabled;
+        cpu.interrupt_mask = self.interrupt_mask;
+        cpu.serial_output = self.serial_output;
+        cpu.cycles = self.cycles;
+    }
+}
+
+/// The last `capacity` instructions `Cpu::eval` executed, so they can be undone with
+/// `Cpu::step_back`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct History {
+    pub capacity: usize,
+    deltas: VecDeque<Delta>,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    /// Remembers the state before `cpu` executes an instruction making `accesses`.
+    pub fn record(&mut self, cpu: &Cpu, accesses: &[Access]) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.deltas.len() == self.capacity {
+            self.deltas.pop_front();
+        }
+        self.deltas.push_back(Delta::capture(cpu, accesses));
+    }
+
+    /// Puts `cpu` back into the state before the last recorded instruction, returning `false`
+    /// when there is nothing left to undo.
+    pub fn undo(&mut self, cpu: &mut Cpu) -> bool {
+        match self.deltas.pop_back() {
+            Some(delta) => {
+                delta.restore(cpu);
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::parser::Location;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Segment {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+}
+
+impl Segment {
+    pub fn contains(&self, address: u16) -> bool {
+        (address as usize) >= self.address as usize
+            && (address as usize) < self.address as usize + self.bytes.len()
+    }
+}
+
+/// The source of one assembled instruction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SourceEntry {
+    /// Index into `SourceMap::files`.
+    pub file: usize,
+    pub location: Location,
+    pub length: u16,
+}
+
+/// Maps addresses back to the source line and column that produced them.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SourceMap {
+    /// The source files, an empty name standing for source that didn't come from a file.
+    pub files: Vec<String>,
+    /// Every instruction, by the address of its first byte.
+    pub entries: BTreeMap<u16, SourceEntry>,
+}
+
+impl SourceMap {
+    /// Names the source of an image assembled from a single file.
+    pub fn name_source(&mut self, file: &str) {
+        match self.files.first_mut() {
+            Some(name) => *name = file.to_string(),
+            None => self.files.push(file.to_string()),
+        }
+    }
+
+    /// The file and location of the instruction that covers `address`.
+    pub fn lookup(&self, address: u16) -> Option<(&str, Location)> {
+        let (start, entry) = self.entries.range(..=address).next_back()?;
+        if (address - start) >= entry.length {
+            return None;
+        }
+        Some((self.files[entry.file].as_str(), entry.location))
+    }
+
+    /// The addresses of the instructions that start on `line` of `file`.
+    pub fn addresses(&self, file: &str, line: usize) -> Vec<u16> {
+        self.entries
+            .iter()
+            .filter(|(_, entry)| self.files[entry.file] == file && entry.location.line == line)
+            .map(|(address, _)| *address)
+            .collect()
+    }
+
+    /// Renders `address` as `file:line:column`, or just the address if it has no source.
+    pub fn describe(&self, address: u16) -> String {
+        match self.lookup(address) {
+            Some(("", location)) => format!("{:04X}H (line {})", address, location),
+            Some((file, location)) => format!("{:04X}H ({}:{})", address, file, location),
+            None => format!("{:04X}H", address),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Image {
+    pub segments: Vec<Segment>,
+    pub entry: u16,
+    pub symbols: HashMap<String, u16>,
+    /// The source line of every symbol, when the image was assembled from source text.
+    pub symbol_lines: HashMap<String, usize>,
+    pub source_map: SourceMap,
+}
+
+impl Image {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `bytes` at `address`, extending the previous segment when they are contiguous.
+    pub fn push(&mut self, address: u16, bytes: &[u8]) {
+        if let Some(segment) = self.segments.last_mut() {
+            if segment.address as usize + segment.bytes.len() == address as usize {
+                segment.bytes.extend_from_slice(bytes);
+                return;
+            }
+        }
+        self.segments.push(Segment {
+            address,
+            bytes: bytes.to_vec(),
+        });
+    }
+
+    pub fn contains(&self, address: u16) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| segment.contains(address))
+    }
+
+    pub fn len(&self) -> usize {
+        self.segments
+            .iter()
+            .map(|segment| segment.bytes.len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The address of the lowest segment, whatever order the segments were assembled in.
+    pub fn lowest_address(&self) -> Option<u16> {
+        self.segments.iter().map(|segment| segment.address).min()
+    }
+
+    /// The address just past the last segment assembled, where a program that runs off its
+    /// end stops.
+    pub fn end_address(&self) -> Option<u16> {
+        self.segments
+            .last()
+            .map(|segment| segment.address.wrapping_add(segment.bytes.len() as u16))
+    }
+
+    /// Every `(address, byte)` pair of the image, in segment order.
+    pub fn bytes(&self) -> impl Iterator<Item = (u16, u8)> + '_ {
+        self.segments.iter().flat_map(|segment| {
+            segment
+                .bytes
+                .iter()
+                .enumerate()
+                .map(|(offset, byte)| (segment.address.wrapping_add(offset as u16), *byte))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::{assembler::assemble, parser::parse_source};
+
+    use super::Image;
+
+    #[test]
+    fn test_push() {
+        let mut image = Image::new();
+        image.push(0x2000, &[0x3E, 0x99]);
+        image.push(0x2002, &[0x76]);
+        image.push(0x3000, &[0x00]);
+
+        assert_eq!(image.segments.len(), 2);
+        assert_eq!(image.segments[0].bytes, vec![0x3E, 0x99, 0x76]);
+        assert!(image.contains(0x2002));
+        assert!(!image.contains(0x2003));
+        assert!(image.contains(0x3000));
+        assert_eq!(image.len(), 4);
+    }
+
+    #[test]
+    fn test_source_map() {
+        let instructions = parse_source("ORG 2000H\nMVI A, 01H\n  LOOP: JMP LOOP\n").unwrap();
+        let mut image = assemble(&instructions);
+        image.source_map.name_source("loop.asm");
+
+        let (file, location) = image.source_map.lookup(0x2003).unwrap();
+        assert_eq!((file, location.line, location.column), ("loop.asm", 3, 3));
+        assert_eq!(image.source_map.lookup(0x2005), None);
+        assert_eq!(image.source_map.addresses("loop.asm", 2), vec![0x2000]);
+        assert_eq!(image.source_map.describe(0x2001), "2001H (loop.asm:2:1)");
+    }
+}
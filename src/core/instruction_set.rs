@@ -0,0 +1,322 @@
+use std::fmt;
+
+use Cycles::{Conditional, Fixed, Memory};
+use Operands::*;
+
+/// The operands an instruction takes, which also says where registers go in its opcode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operands {
+    /// No operands, or only implied ones like the accumulator.
+    Implied,
+    /// `ADD r`, with the register in bits 0-2.
+    Source,
+    /// `INR r`, with the register in bits 3-5.
+    Destination,
+    /// `MOV r1, r2`, with r1 in bits 3-5 and r2 in bits 0-2.
+    Move,
+    /// `MVI r, data`, with the register in bits 3-5.
+    Immediate,
+    /// `INX rp`, with the pair in bits 4-5.
+    Pair,
+    /// `LXI rp, data16`, with the pair in bits 4-5.
+    PairImmediate,
+    Data,
+    Port,
+    Address,
+    /// An address or a label.
+    Target,
+    /// `RST n`, with the vector in bits 3-5.
+    Vector,
+    /// A section name.
+    Name,
+    /// A label of another object.
+    Label,
+}
+
+impl Operands {
+    /// How the operands are written in the syntax summary.
+    pub fn syntax(&self) -> &'static str {
+        match self {
+            Operands::Implied => "",
+            Operands::Source | Operands::Destination => "r",
+            Operands::Move => "r1, r2",
+            Operands::Immediate => "r, data",
+            Operands::Pair => "rp",
+            Operands::PairImmediate => "rp, data16",
+            Operands::Data => "data",
+            Operands::Port => "port",
+            Operands::Address | Operands::Target => "address",
+            Operands::Vector => "n",
+            Operands::Name => "name",
+            Operands::Label => "label",
+        }
+    }
+}
+
+/// How many T-states an instruction takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cycles {
+    Fixed(u8),
+    /// With a register, then with `M`.
+    Memory(u8, u8),
+    /// When the condition fails, then when it holds.
+    Conditional(u8, u8),
+}
+
+impl Cycles {
+    /// The T-states taken, `long` being a memory operand or a condition that holds.
+    pub fn t_states(&self, long: bool) -> u8 {
+        match *self {
+            Cycles::Fixed(t_states) => t_states,
+            Cycles::Memory(short, long_t_states) | Cycles::Conditional(short, long_t_states) => {
+                if long {
+                    long_t_states
+                } else {
+                    short
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Cycles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cycles::Fixed(t_states) => write!(f, "{}", t_states),
+            Cycles::Memory(register, memory) => write!(f, "{} ({} with M)", register, memory),
+            Cycles::Conditional(not_taken, taken) => write!(f, "{}/{}", not_taken, taken),
+        }
+    }
+}
+
+/// What there is to know about one mnemonic.
+#[derive(Debug, PartialEq, Eq)]
+pub struct InstructionInfo {
+    pub mnemonic: &'static str,
+    pub operands: Operands,
+    /// The opcode with every register, pair or vector field 0.
+    pub opcode: u8,
+    /// Bytes, 0 for directives.
+    pub length: u8,
+    pub cycles: Cycles,
+    /// The flags it changes, e.g. `S Z AC P CY`, or `none`.
+    pub flags: &'static str,
+    pub description: &'static str,
+}
+
+impl InstructionInfo {
+    /// The mnemonic with its operands, e.g. `MOV r1, r2`.
+    pub fn syntax(&self) -> String {
+        match self.operands {
+            Operands::Implied => self.mnemonic.to_string(),
+            operands => format!("{} {}", self.mnemonic, operands.syntax()),
+        }
+    }
+
+    pub fn is_directive(&self) -> bool {
+        self.length == 0
+    }
+
+    /// The opcode in binary, with `DDD`, `SSS`, `RP` and `NNN` for its fields, followed by the
+    /// operand bytes, e.g. `00DDD110 data`. Empty for directives.
+    pub fn encoding(&self) -> String {
+        if self.is_directive() {
+            return String::new();
+        }
+        let mut bits: Vec<char> = format!("{:08b}", self.opcode).chars().collect();
+        let mut field = |range: std::ops::Range<usize>, name: &str| {
+            bits.splice(range, name.chars());
+        };
+        match self.operands {
+            Operands::Source => field(5..8, "SSS"),
+            Operands::Destination | Operands::Immediate => field(2..5, "DDD"),
+            Operands::Move => {
+                field(2..5, "DDD");
+                field(5..8, "SSS");
+            }
+            Operands::Pair | Operands::PairImmediate => field(2..4, "RP"),
+            Operands::Vector => field(2..5, "NNN"),
+            _ => {}
+        }
+        let opcode: String = bits.into_iter().collect();
+        match self.operands {
+            Operands::Immediate | Operands::Data => format!("{} data", opcode),
+            Operands::Port => format!("{} port", opcode),
+            Operands::PairImmediate | Operands::Address | Operands::Target => {
+                format!("{} low high", opcode)
+            }
+            _ => opcode,
+        }
+    }
+
+    /// A line of source using the instruction.
+    pub fn example(&self) -> String {
+        let operands = match self.operands {
+            Operands::Implied => return self.mnemonic.to_string(),
+            Operands::Source => "B",
+            Operands::Destination => "M",
+            Operands::Move => "A, B",
+            Operands::Immediate => "A, 32H",
+            Operands::Pair => "D",
+            Operands::PairImmediate => "H, 2050H",
+            Operands::Data => "0FH",
+            Operands::Port => "01H",
+            Operands::Address => "2050H",
+            Operands::Target => "LOOP",
+            Operands::Vector => "7",
+            Operands::Name => "DATA",
+            Operands::Label => "TOTAL",
+        };
+        format!("{} {}", self.mnemonic, operands)
+    }
+}
+
+const fn info(
+    mnemonic: &'static str,
+    operands: Operands,
+    opcode: u8,
+    length: u8,
+    cycles: Cycles,
+    flags: &'static str,
+    description: &'static str,
+) -> InstructionInfo {
+    InstructionInfo {
+        mnemonic,
+        operands,
+        opcode,
+        length,
+        cycles,
+        flags,
+        description,
+    }
+}
+
+const ALL: &str = "S Z AC P CY";
+const NONE: &str = "none";
+
+/// Every 8085 instruction, grouped the way the data sheet groups them.
+#[rustfmt::skip]
+pub static INSTRUCTIONS: &[InstructionInfo] = &[
+    info("MOV", Move, 0x40, 1, Memory(4, 7), NONE, "Copy register r2 into r1."),
+    info("MVI", Immediate, 0x06, 2, Memory(7, 10), NONE, "Load an immediate byte into a register."),
+    info("LXI", PairImmediate, 0x01, 3, Fixed(10), NONE, "Load an immediate word into a register pair."),
+    info("LDA", Address, 0x3A, 3, Fixed(13), NONE, "Load the accumulator from memory."),
+    info("STA", Address, 0x32, 3, Fixed(13), NONE, "Store the accumulator to memory."),
+    info("LHLD", Address, 0x2A, 3, Fixed(16), NONE, "Load L and H from two bytes of memory."),
+    info("SHLD", Address, 0x22, 3, Fixed(16), NONE, "Store L and H to two bytes of memory."),
+    info("LDAX", Pair, 0x0A, 1, Fixed(7), NONE, "Load the accumulator from the address in BC or DE."),
+    info("STAX", Pair, 0x02, 1, Fixed(7), NONE, "Store the accumulator to the address in BC or DE."),
+    info("XCHG", Implied, 0xEB, 1, Fixed(4), NONE, "Exchange DE and HL."),
+    info("ADD", Source, 0x80, 1, Memory(4, 7), ALL, "Add a register to the accumulator."),
+    info("ADC", Source, 0x88, 1, Memory(4, 7), ALL, "Add a register and the carry to the accumulator."),
+    info("ADI", Data, 0xC6, 2, Fixed(7), ALL, "Add an immediate byte to the accumulator."),
+    info("ACI", Data, 0xCE, 2, Fixed(7), ALL, "Add an immediate byte and the carry to the accumulator."),
+    info("SUB", Source, 0x90, 1, Memory(4, 7), ALL, "Subtract a register from the accumulator."),
+    info("SBB", Source, 0x98, 1, Memory(4, 7), ALL, "Subtract a register and the borrow from the accumulator."),
+    info("SUI", Data, 0xD6, 2, Fixed(7), ALL, "Subtract an immediate byte from the accumulator."),
+    info("SBI", Data, 0xDE, 2, Fixed(7), ALL, "Subtract an immediate byte and the borrow from the accumulator."),
+    info("INR", Destination, 0x04, 1, Memory(4, 10), "S Z AC P", "Increment a register."),
+    info("DCR", Destination, 0x05, 1, Memory(4, 10), "S Z AC P", "Decrement a register."),
+    info("INX", Pair, 0x03, 1, Fixed(6), NONE, "Increment a register pair."),
+    info("DCX", Pair, 0x0B, 1, Fixed(6), NONE, "Decrement a register pair."),
+    info("DAD", Pair, 0x09, 1, Fixed(10), "CY", "Add a register pair to HL."),
+    info("DAA", Implied, 0x27, 1, Fixed(4), ALL, "Adjust the accumulator to two BCD digits."),
+    info("ANA", Source, 0xA0, 1, Memory(4, 7), ALL, "AND a register with the accumulator."),
+    info("ANI", Data, 0xE6, 2, Fixed(7), ALL, "AND an immediate byte with the accumulator."),
+    info("XRA", Source, 0xA8, 1, Memory(4, 7), ALL, "Exclusive OR a register with the accumulator."),
+    info("XRI", Data, 0xEE, 2, Fixed(7), ALL, "Exclusive OR an immediate byte with the accumulator."),
+    info("ORA", Source, 0xB0, 1, Memory(4, 7), ALL, "OR a register with the accumulator."),
+    info("ORI", Data, 0xF6, 2, Fixed(7), ALL, "OR an immediate byte with the accumulator."),
+    info("CMP", Source, 0xB8, 1, Memory(4, 7), ALL, "Compare a register with the accumulator."),
+    info("CPI", Data, 0xFE, 2, Fixed(7), ALL, "Compare an immediate byte with the accumulator."),
+    info("RLC", Implied, 0x07, 1, Fixed(4), "CY", "Rotate the accumulator left."),
+    info("RRC", Implied, 0x0F, 1, Fixed(4), "CY", "Rotate the accumulator right."),
+    info("RAL", Implied, 0x17, 1, Fixed(4), "CY", "Rotate the accumulator left through the carry."),
+    info("RAR", Implied, 0x1F, 1, Fixed(4), "CY", "Rotate the accumulator right through the carry."),
+    info("CMA", Implied, 0x2F, 1, Fixed(4), NONE, "Complement the accumulator."),
+    info("CMC", Implied, 0x3F, 1, Fixed(4), "CY", "Complement the carry."),
+    info("STC", Implied, 0x37, 1, Fixed(4), "CY", "Set the carry."),
+    info("JMP", Target, 0xC3, 3, Fixed(10), NONE, "Jump."),
+    info("JNZ", Target, 0xC2, 3, Conditional(7, 10), NONE, "Jump if not zero."),
+    info("JZ", Target, 0xCA, 3, Conditional(7, 10), NONE, "Jump if zero."),
+    info("JNC", Target, 0xD2, 3, Conditional(7, 10), NONE, "Jump if no carry."),
+    info("JC", Target, 0xDA, 3, Conditional(7, 10), NONE, "Jump if carry."),
+    info("JPO", Target, 0xE2, 3, Conditional(7, 10), NONE, "Jump if parity odd."),
+    info("JPE", Target, 0xEA, 3, Conditional(7, 10), NONE, "Jump if parity even."),
+    info("JP", Target, 0xF2, 3, Conditional(7, 10), NONE, "Jump if plus."),
+    info("JM", Target, 0xFA, 3, Conditional(7, 10), NONE, "Jump if minus."),
+    info("CALL", Target, 0xCD, 3, Fixed(18), NONE, "Call a subroutine."),
+    info("CNZ", Target, 0xC4, 3, Conditional(9, 18), NONE, "Call if not zero."),
+    info("CZ", Target, 0xCC, 3, Conditional(9, 18), NONE, "Call if zero."),
+    info("CNC", Target, 0xD4, 3, Conditional(9, 18), NONE, "Call if no carry."),
+    info("CC", Target, 0xDC, 3, Conditional(9, 18), NONE, "Call if carry."),
+    info("CPO", Target, 0xE4, 3, Conditional(9, 18), NONE, "Call if parity odd."),
+    info("CPE", Target, 0xEC, 3, Conditional(9, 18), NONE, "Call if parity even."),
+    info("CP", Target, 0xF4, 3, Conditional(9, 18), NONE, "Call if plus."),
+    info("CM", Target, 0xFC, 3, Conditional(9, 18), NONE, "Call if minus."),
+    info("RET", Implied, 0xC9, 1, Fixed(10), NONE, "Return from a subroutine."),
+    info("RNZ", Implied, 0xC0, 1, Conditional(6, 12), NONE, "Return if not zero."),
+    info("RZ", Implied, 0xC8, 1, Conditional(6, 12), NONE, "Return if zero."),
+    info("RNC", Implied, 0xD0, 1, Conditional(6, 12), NONE, "Return if no carry."),
+    info("RC", Implied, 0xD8, 1, Conditional(6, 12), NONE, "Return if carry."),
+    info("RPO", Implied, 0xE0, 1, Conditional(6, 12), NONE, "Return if parity odd."),
+    info("RPE", Implied, 0xE8, 1, Conditional(6, 12), NONE, "Return if parity even."),
+    info("RP", Implied, 0xF0, 1, Conditional(6, 12), NONE, "Return if plus."),
+    info("RM", Implied, 0xF8, 1, Conditional(6, 12), NONE, "Return if minus."),
+    info("RST", Vector, 0xC7, 1, Fixed(12), NONE, "Call the restart vector n * 8."),
+    info("PCHL", Implied, 0xE9, 1, Fixed(6), NONE, "Jump to the address in HL."),
+    info("PUSH", Pair, 0xC5, 1, Fixed(12), NONE, "Push a register pair or PSW onto the stack."),
+    info("POP", Pair, 0xC1, 1, Fixed(10), "all with PSW", "Pop a register pair or PSW from the stack."),
+    info("XTHL", Implied, 0xE3, 1, Fixed(16), NONE, "Exchange HL with the top of the stack."),
+    info("SPHL", Implied, 0xF9, 1, Fixed(6), NONE, "Copy HL to SP."),
+    info("IN", Port, 0xDB, 2, Fixed(10), NONE, "Read a port into the accumulator."),
+    info("OUT", Port, 0xD3, 2, Fixed(10), NONE, "Write the accumulator to a port."),
+    info("EI", Implied, 0xFB, 1, Fixed(4), NONE, "Enable interrupts."),
+    info("DI", Implied, 0xF3, 1, Fixed(4), NONE, "Disable interrupts."),
+    info("HLT", Implied, 0x76, 1, Fixed(5), NONE, "Halt."),
+    info("NOP", Implied, 0x00, 1, Fixed(4), NONE, "Do nothing."),
+    info("RIM", Implied, 0x20, 1, Fixed(4), NONE, "Read the interrupt mask and serial input into the accumulator."),
+    info("SIM", Implied, 0x30, 1, Fixed(4), NONE, "Set the interrupt mask and serial output from the accumulator."),
+];
+
+/// The assembler directives, which take no bytes and no time.
+#[rustfmt::skip]
+pub static DIRECTIVES: &[InstructionInfo] = &[
+    info("ORG", Address, 0, 0, Fixed(0), NONE, "Continue assembling at an address."),
+    info("SECTION", Name, 0, 0, Fixed(0), NONE, "Continue assembling in a relocatable section."),
+    info("PUBLIC", Label, 0, 0, Fixed(0), NONE, "Make a label visible to other objects."),
+    info("EXTRN", Label, 0, 0, Fixed(0), NONE, "Use a label another object makes public."),
+];
+
+/// The instruction or directive spelled `mnemonic`, in upper case as the parser expects.
+pub fn lookup(mnemonic: &str) -> Option<&'static InstructionInfo> {
+    INSTRUCTIONS
+        .iter()
+        .chain(DIRECTIVES)
+        .find(|info| info.mnemonic == mnemonic)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::disassembler::decode;
+
+    use super::{lookup, INSTRUCTIONS};
+
+    #[test]
+    fn test_instruction_set() {
+        for info in INSTRUCTIONS {
+            // Field 0 is B, BC or vector 0, which every form accepts
+            let (ins, length) = decode(&[info.opcode, 0, 0], 0).unwrap();
+            assert_eq!(ins.mnemonic(), info.mnemonic);
+            assert_eq!(length, info.length, "{}", info.mnemonic);
+            assert_eq!(ins.info(), info);
+        }
+        assert_eq!(lookup("MOV").unwrap().syntax(), "MOV r1, r2");
+        assert_eq!(lookup("JNZ").unwrap().cycles.to_string(), "7/10");
+        assert!(lookup("ORG").unwrap().is_directive());
+        assert_eq!(lookup("MOV").unwrap().encoding(), "01DDDSSS");
+        assert_eq!(lookup("LXI").unwrap().encoding(), "00RP0001 low high");
+        assert_eq!(lookup("RST").unwrap().encoding(), "11NNN111");
+        assert_eq!(lookup("mov"), None);
+    }
+}
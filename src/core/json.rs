@@ -0,0 +1,437 @@
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+/// A JSON value, for the protocols and files the tools exchange with editors.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// Members in the order they were written.
+    Object(Vec<(String, Json)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonError {
+    UnexpectedEnd,
+    /// An unexpected character at this byte offset.
+    UnexpectedCharacter(usize),
+    TrailingCharacters(usize),
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::UnexpectedEnd => write!(f, "Unexpected end of JSON"),
+            JsonError::UnexpectedCharacter(offset) => {
+                write!(f, "Unexpected character at offset {} of JSON", offset)
+            }
+            JsonError::TrailingCharacters(offset) => {
+                write!(f, "Trailing characters at offset {} of JSON", offset)
+            }
+        }
+    }
+}
+
+impl Json {
+    /// Builds an object from `(name, value)` pairs.
+    pub fn object(members: Vec<(&str, Json)>) -> Json {
+        Json::Object(
+            members
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        )
+    }
+
+    /// The member `name` of an object.
+    pub fn get(&self, name: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|(member, _)| member == name)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Follows a path of object members, such as `["source", "path"]`.
+    pub fn path(&self, names: &[&str]) -> Option<&Json> {
+        names.iter().try_fold(self, |value, name| value.get(name))
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// A number that is a non-negative integer.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Json::Number(number) if *number >= 0.0 && number.fract() == 0.0 => Some(*number as u64),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Json::Number(number) if number.fract() == 0.0 => Some(*number as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&[(String, Json)]> {
+        match self {
+            Json::Object(members) => Some(members),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for Json {
+    fn from(text: &str) -> Self {
+        Json::String(text.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(text: String) -> Self {
+        Json::String(text)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Json::Bool(value)
+    }
+}
+
+impl From<u64> for Json {
+    fn from(value: u64) -> Self {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<i64> for Json {
+    fn from(value: i64) -> Self {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Self {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(values: Vec<Json>) -> Self {
+        Json::Array(values)
+    }
+}
+
+/// Writes the value compactly, on one line.
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(number) => write!(f, "{}", number),
+            Json::String(text) => write_string(f, text),
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (index, (name, value)) in members.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, name)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for character in text.chars() {
+        match character {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            character if (character as u32) < 0x20 => write!(f, "\\u{:04x}", character as u32)?,
+            character => write!(f, "{}", character)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    offset: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.offset).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.offset += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), JsonError> {
+        match self.peek() {
+            Some(next) if next == byte => {
+                self.offset += 1;
+                Ok(())
+            }
+            Some(_) => Err(JsonError::UnexpectedCharacter(self.offset)),
+            None => Err(JsonError::UnexpectedEnd),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Json) -> Result<Json, JsonError> {
+        if self.text[self.offset..].starts_with(keyword) {
+            self.offset += keyword.len();
+            Ok(value)
+        } else {
+            Err(JsonError::UnexpectedCharacter(self.offset))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, JsonError> {
+        self.skip_whitespace();
+        match self.peek().ok_or(JsonError::UnexpectedEnd)? {
+            b'n' => self.keyword("null", Json::Null),
+            b't' => self.keyword("true", Json::Bool(true)),
+            b'f' => self.keyword("false", Json::Bool(false)),
+            b'"' => Ok(Json::String(self.string()?)),
+            b'[' => {
+                self.offset += 1;
+                let mut values = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(b']') {
+                    self.offset += 1;
+                    return Ok(Json::Array(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b',') => self.offset += 1,
+                        _ => break,
+                    }
+                }
+                self.expect(b']')?;
+                Ok(Json::Array(values))
+            }
+            b'{' => {
+                self.offset += 1;
+                let mut members = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(b'}') {
+                    self.offset += 1;
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let name = self.string()?;
+                    self.skip_whitespace();
+                    self.expect(b':')?;
+                    members.push((name, self.value()?));
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b',') => self.offset += 1,
+                        _ => break,
+                    }
+                }
+                self.expect(b'}')?;
+                Ok(Json::Object(members))
+            }
+            b'-' | b'0'..=b'9' => self.number(),
+            _ => Err(JsonError::UnexpectedCharacter(self.offset)),
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, JsonError> {
+        let start = self.offset;
+        while matches!(
+            self.peek(),
+            Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')
+        ) {
+            self.offset += 1;
+        }
+        self.text[start..self.offset]
+            .parse()
+            .map(Json::Number)
+            .map_err(|_| JsonError::UnexpectedCharacter(start))
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.expect(b'"')?;
+        let mut text = String::new();
+        let mut characters = self.text[self.offset..].char_indices();
+        loop {
+            let (index, character) = characters.next().ok_or(JsonError::UnexpectedEnd)?;
+            match character {
+                '"' => {
+                    self.offset += index + 1;
+                    return Ok(text);
+                }
+                '\\' => {
+                    let (_, escape) = characters.next().ok_or(JsonError::UnexpectedEnd)?;
+                    let invalid = JsonError::UnexpectedCharacter(self.offset + index);
+                    text.push(match escape {
+                        '"' => '"',
+                        '\\' => '\\',
+                        '/' => '/',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'u' => {
+                            let digits: String =
+                                characters.by_ref().take(4).map(|(_, c)| c).collect();
+                            let code =
+                                u32::from_str_radix(&digits, 16).map_err(|_| invalid.clone())?;
+                            // Surrogate pairs aren't combined; they only appear for emoji
+                            char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+                        }
+                        _ => return Err(invalid),
+                    });
+                }
+                character => text.push(character),
+            }
+        }
+    }
+}
+
+pub fn parse_json(text: &str) -> Result<Json, JsonError> {
+    let mut parser = Parser { text, offset: 0 };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    match parser.offset < text.len() {
+        true => Err(JsonError::TrailingCharacters(parser.offset)),
+        false => Ok(value),
+    }
+}
+
+/// Reads one message framed by a `Content-Length` header, as the Debug Adapter and Language
+/// Server protocols send them, or `None` at the end of the input.
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            if length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let mut body = vec![0; length.unwrap_or(0)];
+    input.read_exact(&mut body)?;
+    Ok(Some(String::from_utf8_lossy(&body).to_string()))
+}
+
+pub fn write_message(output: &mut impl Write, message: &Json) -> io::Result<()> {
+    let message = message.to_string();
+    write!(
+        output,
+        "Content-Length: {}\r\n\r\n{}",
+        message.len(),
+        message
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_json, read_message, write_message, Json, JsonError};
+
+    #[test]
+    fn test_json() {
+        let text = r#"{"seq": 1, "arguments": {"lines": [3, -4.5], "ok": true, "name": "a\"b\\nA", "none": null}}"#;
+        let value = parse_json(text).unwrap();
+        assert_eq!(value.get("seq").and_then(Json::as_u64), Some(1));
+        let lines = value
+            .path(&["arguments", "lines"])
+            .unwrap()
+            .as_array()
+            .unwrap();
+        assert_eq!(lines[1], Json::Number(-4.5));
+        assert_eq!(lines[1].as_u64(), None);
+        assert_eq!(
+            value.path(&["arguments", "name"]).and_then(Json::as_str),
+            Some("a\"b\\nA")
+        );
+        assert_eq!(
+            value.to_string(),
+            r#"{"seq":1,"arguments":{"lines":[3,-4.5],"ok":true,"name":"a\"b\\nA","none":null}}"#
+        );
+        assert_eq!(parse_json(&value.to_string()), Ok(value));
+
+        let built = Json::object(vec![("text", "line\n".into()), ("items", vec![].into())]);
+        assert_eq!(built.to_string(), r#"{"text":"line\n","items":[]}"#);
+
+        assert_eq!(parse_json("[1, 2"), Err(JsonError::UnexpectedEnd));
+        assert_eq!(parse_json("[1 2]"), Err(JsonError::UnexpectedCharacter(3)));
+        assert_eq!(parse_json("{} x"), Err(JsonError::TrailingCharacters(3)));
+    }
+
+    #[test]
+    fn test_message() {
+        let mut output = Vec::new();
+        write_message(&mut output, &Json::object(vec![("id", 1u64.into())])).unwrap();
+        assert_eq!(output, b"Content-Length: 8\r\n\r\n{\"id\":1}");
+
+        output.extend(b"Content-Type: application/json\r\nContent-Length: 2\r\n\r\n[]");
+        let mut input = output.as_slice();
+        assert_eq!(read_message(&mut input).unwrap().unwrap(), "{\"id\":1}");
+        assert_eq!(read_message(&mut input).unwrap().unwrap(), "[]");
+        assert_eq!(read_message(&mut input).unwrap(), None);
+    }
+}
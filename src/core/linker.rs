@@ -0,0 +1,293 @@
+use std::{collections::HashMap, fmt};
+
+use super::{
+    image::{Image, SourceEntry},
+    object::{Object, RelocationTarget},
+};
+
+#[derive(Debug, PartialEq)]
+pub enum LinkError {
+    UndefinedSymbol(String, String),
+    DuplicateSymbol(String),
+    Overlap(String, String),
+    OutOfRange(String),
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkError::UndefinedSymbol(symbol, object) => {
+                write!(f, "{}: undefined symbol '{}'", object, symbol)
+            }
+            LinkError::DuplicateSymbol(symbol) => {
+                write!(f, "Symbol '{}' is PUBLIC in more than one object", symbol)
+            }
+            LinkError::Overlap(first, second) => write!(f, "{} overlaps {}", first, second),
+            LinkError::OutOfRange(section) => {
+                write!(f, "{} doesn't fit in the 64K address space", section)
+            }
+        }
+    }
+}
+
+/// Where the linker put one section of one object.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placement {
+    pub object: String,
+    pub section: String,
+    pub address: u16,
+    pub length: usize,
+}
+
+impl fmt::Display for Placement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}({}) at {:04X}H-{:04X}H",
+            self.object,
+            self.section,
+            self.address,
+            self.address as usize + self.length.max(1) - 1
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LinkMap {
+    pub placements: Vec<Placement>,
+    /// Every `PUBLIC` symbol with its address and the object that defines it.
+    pub symbols: Vec<(String, u16, String)>,
+}
+
+/// Places every relocatable section of `objects` and patches their relocations. Sections with
+/// the same name are laid out one after another in object order, starting at the base `bases`
+/// gives for that name, or right after the previous section name otherwise. `ORG` sections stay
+/// where they are. The entry point is the first section of the first object.
+pub fn link(objects: &[Object], bases: &[(String, u16)]) -> Result<(Image, LinkMap), LinkError> {
+    let mut names: Vec<&str> = Vec::new();
+    for object in objects {
+        for section in &object.sections {
+            if section.address.is_none() && !names.contains(&section.name.as_str()) {
+                names.push(&section.name);
+            }
+        }
+    }
+
+    // The load address of every section, indexed by object and section
+    let mut addresses: Vec<Vec<u16>> = objects
+        .iter()
+        .map(|object| {
+            object
+                .sections
+                .iter()
+                .map(|section| section.address.unwrap_or(0))
+                .collect()
+        })
+        .collect();
+    let mut map = LinkMap::default();
+    let mut next = 0usize;
+    for name in names {
+        if let Some((_, base)) = bases.iter().find(|(section, _)| section == name) {
+            next = *base as usize;
+        }
+        for (object_index, object) in objects.iter().enumerate() {
+            for (section_index, section) in object.sections.iter().enumerate() {
+                if section.address.is_some() || section.name != name {
+                    continue;
+                }
+                let placement = Placement {
+                    object: object.name.clone(),
+                    section: section.name.clone(),
+                    address: next as u16,
+                    length: section.bytes.len(),
+                };
+                if next + section.bytes.len() > 0x10000 {
+                    return Err(LinkError::OutOfRange(placement.to_string()));
+                }
+                addresses[object_index][section_index] = next as u16;
+                next += section.bytes.len();
+                map.placements.push(placement);
+            }
+        }
+    }
+    for object in objects {
+        for section in &object.sections {
+            if let Some(address) = section.address {
+                map.placements.push(Placement {
+                    object: object.name.clone(),
+                    section: section.name.clone(),
+                    address,
+                    length: section.bytes.len(),
+                });
+            }
+        }
+    }
+
+    let mut sorted: Vec<&Placement> = map.placements.iter().collect();
+    sorted.sort_by_key(|placement| placement.address);
+    for pair in sorted.windows(2) {
+        if pair[0].address as usize + pair[0].length > pair[1].address as usize {
+            return Err(LinkError::Overlap(pair[0].to_string(), pair[1].to_string()));
+        }
+    }
+
+    let mut globals: HashMap<&str, u16> = HashMap::new();
+    for (object_index, object) in objects.iter().enumerate() {
+        for symbol in &object.publics {
+            let address = addresses[object_index][symbol.section].wrapping_add(symbol.offset);
+            if globals.insert(&symbol.name, address).is_some() {
+                return Err(LinkError::DuplicateSymbol(symbol.name.clone()));
+            }
+            map.symbols
+                .push((symbol.name.clone(), address, object.name.clone()));
+        }
+    }
+    map.symbols.sort_by_key(|(_, address, _)| *address);
+
+    let mut sections: Vec<(u16, Vec<u8>)> = Vec::new();
+    for (object_index, object) in objects.iter().enumerate() {
+        let mut bytes: Vec<Vec<u8>> = object
+            .sections
+            .iter()
+            .map(|section| section.bytes.clone())
+            .collect();
+        for relocation in &object.relocations {
+            let value = match &relocation.target {
+                RelocationTarget::Section(section) => addresses[object_index][*section],
+                RelocationTarget::External(symbol) => {
+                    *globals.get(symbol.as_str()).ok_or_else(|| {
+                        LinkError::UndefinedSymbol(symbol.clone(), object.name.clone())
+                    })?
+                }
+            };
+            let bytes = &mut bytes[relocation.section];
+            let offset = relocation.offset as usize;
+            let stored = u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+            let [low, high] = stored.wrapping_add(value).to_le_bytes();
+            bytes[offset] = low;
+            bytes[offset + 1] = high;
+        }
+        for (section_index, bytes) in bytes.into_iter().enumerate() {
+            sections.push((addresses[object_index][section_index], bytes));
+        }
+    }
+
+    let mut image = Image::new();
+    image.entry = addresses
+        .first()
+        .and_then(|sections| sections.first())
+        .copied()
+        .unwrap_or(0);
+    sections.sort_by_key(|(address, _)| *address);
+    for (address, bytes) in sections.iter().filter(|(_, bytes)| !bytes.is_empty()) {
+        image.push(*address, bytes);
+    }
+    for (object_index, object) in objects.iter().enumerate() {
+        if object.lines.is_empty() {
+            continue;
+        }
+        let file = image.source_map.files.len();
+        image.source_map.files.push(object.name.clone());
+        for line in &object.lines {
+            let address = addresses[object_index][line.section].wrapping_add(line.offset);
+            image.source_map.entries.insert(
+                address,
+                SourceEntry {
+                    file,
+                    location: line.location,
+                    length: line.length,
+                },
+            );
+        }
+    }
+    image.symbols = globals
+        .into_iter()
+        .map(|(symbol, address)| (symbol.to_string(), address))
+        .collect();
+    Ok((image, map))
+}
+
+/// Renders a link map: where every section went and the address of every public symbol.
+pub fn to_map_file(map: &LinkMap) -> String {
+    let mut output = String::from("Sections:\n");
+    for placement in &map.placements {
+        output.push_str(&format!(
+            "  {:04X}H  {:04X}H  {:<8} {}\n",
+            placement.address, placement.length, placement.section, placement.object
+        ));
+    }
+    output.push_str("\nSymbols:\n");
+    for (symbol, address, object) in &map.symbols {
+        output.push_str(&format!("  {:04X}H  {:<8} {}\n", address, symbol, object));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::{assembler::assemble_object, object::Object, parser::parse_source};
+
+    use super::{link, to_map_file, LinkError};
+
+    fn object(name: &str, source: &str) -> Object {
+        assemble_object(&parse_source(source).unwrap(), name).unwrap()
+    }
+
+    #[test]
+    fn test_link() {
+        let main = object(
+            "main",
+            r#"
+            EXTRN DOUBLE
+            MVI A, 21H
+            LOOP: CALL DOUBLE
+            JNC LOOP
+            HLT
+            "#,
+        );
+        let library = object(
+            "library",
+            r#"
+            PUBLIC DOUBLE
+            DOUBLE: ADD A
+            RET
+            "#,
+        );
+
+        let (image, map) = link(
+            &[main.clone(), library.clone()],
+            &[("CODE".to_string(), 0x2000)],
+        )
+        .unwrap();
+        assert_eq!(image.entry, 0x2000);
+        assert_eq!(
+            image.segments[0].bytes,
+            vec![0x3E, 0x21, 0xCD, 0x09, 0x20, 0xD2, 0x02, 0x20, 0x76, 0x87, 0xC9]
+        );
+        assert_eq!(image.symbols["DOUBLE"], 0x2009);
+        assert_eq!(image.source_map.describe(0x2009), "2009H (library:3:13)");
+        assert_eq!(
+            to_map_file(&map),
+            "Sections:\n  2000H  0009H  CODE     main\n  2009H  0002H  CODE     library\n\n\
+             Symbols:\n  2009H  DOUBLE   library\n"
+        );
+
+        assert_eq!(
+            link(std::slice::from_ref(&main), &[]),
+            Err(LinkError::UndefinedSymbol(
+                "DOUBLE".to_string(),
+                "main".to_string()
+            ))
+        );
+        assert_eq!(
+            link(&[library.clone(), library], &[]),
+            Err(LinkError::DuplicateSymbol("DOUBLE".to_string()))
+        );
+
+        let fixed = object("fixed", "ORG 0004H\nNOP");
+        assert!(matches!(
+            link(&[main, fixed], &[]),
+            Err(LinkError::Overlap(_, _))
+        ));
+    }
+}
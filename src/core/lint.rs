@@ -0,0 +1,388 @@
+use std::fmt;
+
+use super::{
+    cpu::Registers,
+    parser::{Ins, Instruction, JumpTarget, Location},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LintCode {
+    FallthroughIntoData,
+    MissingHlt,
+    SelfMove,
+    ConditionWithoutFlags,
+    WriteToCode,
+    UnreachableCode,
+}
+
+impl LintCode {
+    pub const ALL: [LintCode; 6] = [
+        LintCode::FallthroughIntoData,
+        LintCode::MissingHlt,
+        LintCode::SelfMove,
+        LintCode::ConditionWithoutFlags,
+        LintCode::WriteToCode,
+        LintCode::UnreachableCode,
+    ];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            LintCode::FallthroughIntoData => "W001",
+            LintCode::MissingHlt => "W002",
+            LintCode::SelfMove => "W003",
+            LintCode::ConditionWithoutFlags => "W004",
+            LintCode::WriteToCode => "W005",
+            LintCode::UnreachableCode => "W006",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LintCode::FallthroughIntoData => "fallthrough-into-data",
+            LintCode::MissingHlt => "missing-hlt",
+            LintCode::SelfMove => "self-move",
+            LintCode::ConditionWithoutFlags => "condition-without-flags",
+            LintCode::WriteToCode => "write-to-code",
+            LintCode::UnreachableCode => "unreachable-code",
+        }
+    }
+
+    /// Looks a lint up by its code (`W003`) or name (`self-move`).
+    pub fn from(name: &str) -> Option<LintCode> {
+        LintCode::ALL
+            .into_iter()
+            .find(|code| code.code().eq_ignore_ascii_case(name) || code.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub code: LintCode,
+    pub message: String,
+    /// Index of the offending instruction.
+    pub index: usize,
+    pub location: Option<Location>,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "warning[{}]: ", self.code.code())?;
+        if let Some(location) = self.location {
+            write!(f, "{}: ", location)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Whether execution can continue with the next instruction.
+fn falls_through(ins: &Ins) -> bool {
+    !matches!(ins, Ins::Hlt | Ins::Jmp(_) | Ins::Ret | Ins::Pchl)
+}
+
+fn tests_flags(ins: &Ins) -> bool {
+    matches!(
+        ins,
+        Ins::Jnz(_)
+            | Ins::Jz(_)
+            | Ins::Jnc(_)
+            | Ins::Jc(_)
+            | Ins::Jpo(_)
+            | Ins::Jpe(_)
+            | Ins::Jp(_)
+            | Ins::Jm(_)
+            | Ins::Cnz(_)
+            | Ins::Cz(_)
+            | Ins::Cnc(_)
+            | Ins::Cc(_)
+            | Ins::Cpo(_)
+            | Ins::Cpe(_)
+            | Ins::Cp(_)
+            | Ins::Cm(_)
+            | Ins::Rnz
+            | Ins::Rz
+            | Ins::Rnc
+            | Ins::Rc
+            | Ins::Rpo
+            | Ins::Rpe
+            | Ins::Rp
+            | Ins::Rm
+    )
+}
+
+/// Instructions that leave every flag as it was, so a condition after them tests something older.
+fn keeps_flags(ins: &Ins) -> bool {
+    match ins {
+        Ins::Pop(register) => *register != Registers::RegPSW,
+        Ins::Mov(..)
+        | Ins::Mvi(..)
+        | Ins::Lxi(..)
+        | Ins::Lda(_)
+        | Ins::Sta(_)
+        | Ins::Lhld(_)
+        | Ins::Shld(_)
+        | Ins::Ldax(_)
+        | Ins::Stax(_)
+        | Ins::Xchg
+        | Ins::Inx(_)
+        | Ins::Dcx(_)
+        | Ins::Cma
+        | Ins::Push(_)
+        | Ins::Xthl
+        | Ins::Sphl
+        | Ins::In(_)
+        | Ins::Out(_)
+        | Ins::Ei
+        | Ins::Di
+        | Ins::Rim
+        | Ins::Sim
+        | Ins::Nop => true,
+        _ => false,
+    }
+}
+
+/// Checks `instructions` for common mistakes, skipping the lints in `allowed`.
+pub fn lint(instructions: &[Instruction], allowed: &[LintCode]) -> Vec<Warning> {
+    // The address of every instruction and the address ranges that hold code
+    let mut addresses = Vec::with_capacity(instructions.len());
+    let mut code: Vec<(u16, u16)> = Vec::new();
+    let mut address = 0u16;
+    for instruction in instructions {
+        if let Ins::Org(origin) = instruction.ins {
+            address = origin;
+        }
+        addresses.push(address);
+        let size = instruction.ins.size();
+        if size > 0 {
+            match code.last_mut() {
+                Some((_, end)) if *end == address => *end = address.wrapping_add(size),
+                _ => code.push((address, address.wrapping_add(size))),
+            }
+        }
+        address = address.wrapping_add(size);
+    }
+    let is_code = |address: u16| {
+        code.iter()
+            .any(|(start, end)| address >= *start && address < *end)
+    };
+    let data: Vec<u16> = instructions
+        .iter()
+        .filter_map(|instruction| match instruction.ins {
+            Ins::Lda(address) | Ins::Sta(address) | Ins::Lhld(address) | Ins::Shld(address) => {
+                Some(address)
+            }
+            Ins::Lxi(Registers::RegSP, _) => None,
+            Ins::Lxi(_, address) => Some(address),
+            _ => None,
+        })
+        .collect();
+
+    let mut warnings = Vec::new();
+    let mut warn = |code: LintCode, index: usize, message: String| {
+        if !allowed.contains(&code) {
+            warnings.push(Warning {
+                code,
+                message,
+                index,
+                location: instructions[index].location,
+            });
+        }
+    };
+
+    // Only instructions that produce code; directives don't interrupt the flow
+    let code_indices: Vec<usize> = (0..instructions.len())
+        .filter(|index| instructions[*index].ins.size() > 0)
+        .collect();
+    let last = code_indices.last().copied();
+
+    for (position, &index) in code_indices.iter().enumerate() {
+        let instruction = &instructions[index];
+        let ins = &instruction.ins;
+        let next_address = addresses[index].wrapping_add(ins.size());
+        let next = code_indices.get(position + 1).copied();
+        let previous = position
+            .checked_sub(1)
+            .map(|position| &instructions[code_indices[position]].ins);
+
+        if let Ins::Mov(destination, source) = ins {
+            if destination == source {
+                warn(
+                    LintCode::SelfMove,
+                    index,
+                    format!("{} moves {} to itself", ins, destination),
+                );
+            }
+        }
+
+        if let (true, None, Some(previous)) = (tests_flags(ins), instruction.label, previous) {
+            if keeps_flags(previous) {
+                warn(
+                    LintCode::ConditionWithoutFlags,
+                    index,
+                    format!(
+                        "{} tests flags that {} doesn't set; they still come from an earlier instruction",
+                        ins, previous
+                    ),
+                );
+            }
+        }
+
+        let written = match ins {
+            Ins::Sta(address) => vec![*address],
+            Ins::Shld(address) => vec![*address, address.wrapping_add(1)],
+            _ => Vec::new(),
+        };
+        if let Some(address) = written.into_iter().find(|address| is_code(*address)) {
+            warn(
+                LintCode::WriteToCode,
+                index,
+                format!(
+                    "{} overwrites the program's own code at {:04X}H",
+                    ins, address
+                ),
+            );
+        }
+
+        if !falls_through(ins) {
+            if let Some(next) = next.filter(|next| addresses[*next] == next_address) {
+                if instructions[next].label.is_none() && !is_jump_target(instructions, next) {
+                    warn(
+                        LintCode::UnreachableCode,
+                        next,
+                        format!("{} can't be reached after {}", instructions[next].ins, ins),
+                    );
+                }
+            }
+            continue;
+        }
+
+        let continues_here = next.is_some_and(|next| addresses[next] == next_address);
+        if continues_here {
+            continue;
+        }
+        if data.contains(&next_address) {
+            warn(
+                LintCode::FallthroughIntoData,
+                index,
+                format!(
+                    "execution continues after {} into data at {:04X}H",
+                    ins, next_address
+                ),
+            );
+        } else if Some(index) == last {
+            warn(
+                LintCode::MissingHlt,
+                index,
+                format!(
+                    "the program doesn't end with HLT, execution continues after {}",
+                    ins
+                ),
+            );
+        } else {
+            warn(
+                LintCode::FallthroughIntoData,
+                index,
+                format!(
+                    "execution continues after {} into {:04X}H, which isn't part of this block",
+                    ins, next_address
+                ),
+            );
+        }
+    }
+    warnings
+}
+
+/// Whether a jump or call elsewhere targets the address of instruction `index` by number.
+fn is_jump_target(instructions: &[Instruction], index: usize) -> bool {
+    let mut address = 0u16;
+    let mut target = None;
+    for (position, instruction) in instructions.iter().enumerate() {
+        if let Ins::Org(origin) = instruction.ins {
+            address = origin;
+        }
+        if position == index {
+            target = Some(address);
+            break;
+        }
+        address = address.wrapping_add(instruction.ins.size());
+    }
+    instructions
+        .iter()
+        .any(|instruction| match &instruction.ins {
+            Ins::Jmp(JumpTarget::Address(address))
+            | Ins::Jnz(JumpTarget::Address(address))
+            | Ins::Jz(JumpTarget::Address(address))
+            | Ins::Jnc(JumpTarget::Address(address))
+            | Ins::Jc(JumpTarget::Address(address))
+            | Ins::Jpo(JumpTarget::Address(address))
+            | Ins::Jpe(JumpTarget::Address(address))
+            | Ins::Jp(JumpTarget::Address(address))
+            | Ins::Jm(JumpTarget::Address(address))
+            | Ins::Call(JumpTarget::Address(address))
+            | Ins::Cnz(JumpTarget::Address(address))
+            | Ins::Cz(JumpTarget::Address(address))
+            | Ins::Cnc(JumpTarget::Address(address))
+            | Ins::Cc(JumpTarget::Address(address))
+            | Ins::Cpo(JumpTarget::Address(address))
+            | Ins::Cpe(JumpTarget::Address(address))
+            | Ins::Cp(JumpTarget::Address(address))
+            | Ins::Cm(JumpTarget::Address(address)) => Some(*address) == target,
+            _ => false,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::parser::parse_source;
+
+    use super::{lint, LintCode};
+
+    fn codes(source: &str, allowed: &[LintCode]) -> Vec<(&'static str, usize)> {
+        lint(&parse_source(source).unwrap(), allowed)
+            .iter()
+            .map(|warning| (warning.code.code(), warning.location.unwrap().line))
+            .collect()
+    }
+
+    #[test]
+    fn test_lint() {
+        let source = r#"ORG 2000H
+            LXI B, 0005H
+            MOV A, A
+            LOOP: DCX B
+            JNZ LOOP
+            STA 2001H
+            JMP DONE
+            MVI A, 01H
+            DONE: LDA 2050H
+            ORG 2040H
+            NOP
+            ORG 2030H
+            MOV B, A"#;
+        assert_eq!(
+            codes(source, &[]),
+            vec![
+                ("W003", 3),
+                ("W004", 5),
+                ("W005", 6),
+                ("W006", 8),
+                ("W001", 9),
+                ("W001", 11),
+                ("W002", 13),
+            ]
+        );
+        assert_eq!(
+            codes(source, &[LintCode::SelfMove, LintCode::UnreachableCode]),
+            vec![
+                ("W004", 5),
+                ("W005", 6),
+                ("W001", 9),
+                ("W001", 11),
+                ("W002", 13)
+            ]
+        );
+        assert_eq!(codes("MVI A, 01H\nSTA 2050H\nHLT", &[]), vec![]);
+        assert_eq!(codes("MVI A, 01H\nSTA 0005H", &[]), vec![("W001", 2)]);
+        assert_eq!(LintCode::from("self-move"), Some(LintCode::SelfMove));
+        assert_eq!(LintCode::from("w006"), Some(LintCode::UnreachableCode));
+    }
+}
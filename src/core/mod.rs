@@ -1,6 +1,24 @@
 pub mod assembler;
+pub mod breakpoints;
+pub mod coverage;
 pub mod cpu;
+pub mod disassembler;
+pub mod formats;
+pub mod formatter;
+pub mod grader;
+pub mod harness;
+pub mod history;
+pub mod image;
+pub mod instruction_set;
+pub mod json;
 pub mod language;
+pub mod linker;
+pub mod lint;
+pub mod object;
 pub mod parser;
+pub mod profile;
+pub mod reference;
+pub mod snapshot;
 pub mod syntax_highlighting;
 pub mod token;
+pub mod trace;
@@ -0,0 +1,353 @@
+use std::fmt;
+
+use super::parser::Location;
+
+/// The section code goes into until the first `SECTION` or `ORG`.
+pub const DEFAULT_SECTION: &str = "CODE";
+/// The name given to sections placed by `ORG`, which the linker doesn't move.
+pub const ABSOLUTE_SECTION: &str = "ABS";
+
+const MAGIC: &str = "OBJ8085";
+const VERSION: u32 = 1;
+const BYTES_PER_RECORD: usize = 16;
+
+#[derive(Debug, PartialEq)]
+pub enum ObjectError {
+    UndefinedSymbol(String),
+    MissingHeader,
+    UnsupportedVersion(String),
+    InvalidRecord(usize),
+}
+
+impl fmt::Display for ObjectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjectError::UndefinedSymbol(symbol) => {
+                write!(f, "Symbol '{}' is neither defined nor EXTRN", symbol)
+            }
+            ObjectError::MissingHeader => write!(f, "Not an object file"),
+            ObjectError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported object file version {}", version)
+            }
+            ObjectError::InvalidRecord(line) => write!(f, "Line {}: invalid record", line),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub name: String,
+    /// The load address of an `ORG` section; relocatable sections are placed by the linker.
+    pub address: Option<u16>,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RelocationTarget {
+    /// Add the load address of a section of the same object.
+    Section(usize),
+    /// Add the address of a symbol another object makes `PUBLIC`.
+    External(String),
+}
+
+/// A little endian address at `offset` in `section` that the linker has to adjust.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relocation {
+    pub section: usize,
+    pub offset: u16,
+    pub target: RelocationTarget,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub section: usize,
+    pub offset: u16,
+}
+
+/// The source location of `length` bytes at `offset` in `section`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineEntry {
+    pub section: usize,
+    pub offset: u16,
+    pub length: u16,
+    pub location: Location,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Object {
+    pub name: String,
+    pub sections: Vec<Section>,
+    pub relocations: Vec<Relocation>,
+    pub publics: Vec<Symbol>,
+    pub externals: Vec<String>,
+    pub lines: Vec<LineEntry>,
+}
+
+impl Object {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Self::default()
+        }
+    }
+}
+
+/// Writes `object` in the text object format:
+///
+/// ```text
+/// OBJ8085 1
+/// NAME <name>
+/// SECTION <name> REL|<address> <length>
+/// DATA <section> <offset> <bytes>...
+/// RELOC <section> <offset> SECTION <section> | EXTERN <symbol>
+/// PUBLIC <symbol> <section> <offset>
+/// EXTERN <symbol>
+/// LINE <section> <offset> <length> <line> <column>
+/// ```
+///
+/// Sections are numbered in the order of their `SECTION` records, and all numbers except the
+/// section indices, lines and columns are hex.
+pub fn to_object_file(object: &Object) -> String {
+    let mut output = format!("{} {}\nNAME {}\n", MAGIC, VERSION, object.name);
+
+    for section in &object.sections {
+        let placement = match section.address {
+            Some(address) => format!("{:04X}", address),
+            None => "REL".to_string(),
+        };
+        output.push_str(&format!(
+            "SECTION {} {} {:04X}\n",
+            section.name,
+            placement,
+            section.bytes.len()
+        ));
+    }
+    for (index, section) in object.sections.iter().enumerate() {
+        for (chunk_index, chunk) in section.bytes.chunks(BYTES_PER_RECORD).enumerate() {
+            let bytes: Vec<String> = chunk.iter().map(|byte| format!("{:02X}", byte)).collect();
+            output.push_str(&format!(
+                "DATA {} {:04X} {}\n",
+                index,
+                chunk_index * BYTES_PER_RECORD,
+                bytes.join(" ")
+            ));
+        }
+    }
+    for relocation in &object.relocations {
+        let target = match &relocation.target {
+            RelocationTarget::Section(section) => format!("SECTION {}", section),
+            RelocationTarget::External(symbol) => format!("EXTERN {}", symbol),
+        };
+        output.push_str(&format!(
+            "RELOC {} {:04X} {}\n",
+            relocation.section, relocation.offset, target
+        ));
+    }
+    for symbol in &object.publics {
+        output.push_str(&format!(
+            "PUBLIC {} {} {:04X}\n",
+            symbol.name, symbol.section, symbol.offset
+        ));
+    }
+    for symbol in &object.externals {
+        output.push_str(&format!("EXTERN {}\n", symbol));
+    }
+    for line in &object.lines {
+        output.push_str(&format!(
+            "LINE {} {:04X} {:X} {} {}\n",
+            line.section, line.offset, line.length, line.location.line, line.location.column
+        ));
+    }
+    output
+}
+
+fn parse_record(object: &mut Object, fields: &[&str]) -> Option<()> {
+    let hex = |field: &str| u16::from_str_radix(field, 16).ok();
+    let section = |field: &str, object: &Object| {
+        field
+            .parse::<usize>()
+            .ok()
+            .filter(|index| *index < object.sections.len())
+    };
+
+    match fields {
+        ["SECTION", name, placement, length] => {
+            let address = match *placement {
+                "REL" => None,
+                address => Some(hex(address)?),
+            };
+            object.sections.push(Section {
+                name: name.to_string(),
+                address,
+                bytes: vec![0; hex(length)? as usize],
+            });
+        }
+        ["DATA", index, offset, bytes @ ..] => {
+            let index = section(index, object)?;
+            let offset = hex(offset)? as usize;
+            let section = &mut object.sections[index];
+            let end = offset + bytes.len();
+            if end > section.bytes.len() {
+                return None;
+            }
+            for (target, byte) in section.bytes[offset..end].iter_mut().zip(bytes) {
+                *target = u8::from_str_radix(byte, 16).ok()?;
+            }
+        }
+        ["RELOC", index, offset, kind, target] => {
+            let index = section(index, object)?;
+            let offset = hex(offset)?;
+            if offset as usize + 2 > object.sections[index].bytes.len() {
+                return None;
+            }
+            let target = match *kind {
+                "SECTION" => RelocationTarget::Section(section(target, object)?),
+                "EXTERN" => RelocationTarget::External(target.to_string()),
+                _ => return None,
+            };
+            object.relocations.push(Relocation {
+                section: index,
+                offset,
+                target,
+            });
+        }
+        ["PUBLIC", name, index, offset] => {
+            let index = section(index, object)?;
+            object.publics.push(Symbol {
+                name: name.to_string(),
+                section: index,
+                offset: hex(offset)?,
+            });
+        }
+        ["EXTERN", name] => object.externals.push(name.to_string()),
+        ["LINE", index, offset, length, line, column] => {
+            let index = section(index, object)?;
+            object.lines.push(LineEntry {
+                section: index,
+                offset: hex(offset)?,
+                length: hex(length)?,
+                location: Location {
+                    line: line.parse().ok()?,
+                    column: column.parse().ok()?,
+                },
+            });
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+pub fn read_object_file(source: &str) -> Result<Object, ObjectError> {
+    let mut lines = source.lines().enumerate();
+    match lines
+        .next()
+        .map(|(_, line)| line.split_whitespace().collect::<Vec<_>>())
+    {
+        Some(header) if header.len() == 2 && header[0] == MAGIC => {
+            if header[1] != VERSION.to_string() {
+                return Err(ObjectError::UnsupportedVersion(header[1].to_string()));
+            }
+        }
+        _ => return Err(ObjectError::MissingHeader),
+    }
+
+    let mut object = Object::default();
+    for (index, line) in lines {
+        let number = index + 1;
+        if let Some(name) = line.strip_prefix("NAME ") {
+            object.name = name.to_string();
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.is_empty() {
+            continue;
+        }
+        parse_record(&mut object, &fields).ok_or(ObjectError::InvalidRecord(number))?;
+    }
+    Ok(object)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::{assembler::assemble_object, parser::parse_source};
+
+    use super::{read_object_file, to_object_file, ObjectError, RelocationTarget, Symbol};
+
+    fn assemble_source(source: &str) -> Result<super::Object, ObjectError> {
+        assemble_object(&parse_source(source).unwrap(), "test")
+    }
+
+    #[test]
+    fn test_assemble_object() {
+        let object = assemble_source(
+            r#"
+            PUBLIC START
+            EXTRN PRINT
+            START: MVI A, 01H
+            LOOP: CALL PRINT
+            JMP LOOP
+            SECTION DATA
+            NOP
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(object.sections.len(), 2);
+        assert_eq!(
+            object.sections[0].bytes,
+            vec![0x3E, 0x01, 0xCD, 0x00, 0x00, 0xC3, 0x02, 0x00]
+        );
+        assert_eq!(object.sections[0].address, None);
+        assert_eq!(object.sections[1].name, "DATA");
+        assert_eq!(
+            object.publics,
+            vec![Symbol {
+                name: "START".to_string(),
+                section: 0,
+                offset: 0
+            }]
+        );
+
+        let mut targets: Vec<_> = object
+            .relocations
+            .iter()
+            .map(|relocation| (relocation.offset, relocation.target.clone()))
+            .collect();
+        targets.sort_by_key(|(offset, _)| *offset);
+        assert_eq!(
+            targets,
+            vec![
+                (3, RelocationTarget::External("PRINT".to_string())),
+                (6, RelocationTarget::Section(0)),
+            ]
+        );
+
+        assert_eq!(
+            assemble_source("JMP NOWHERE"),
+            Err(ObjectError::UndefinedSymbol("NOWHERE".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_object_file() {
+        let object = assemble_source(
+            r#"
+            PUBLIC NEXT
+            JMP NEXT
+            ORG 2000H
+            NEXT: HLT
+            "#,
+        )
+        .unwrap();
+        assert_eq!(object.sections[1].address, Some(0x2000));
+        assert!(object.relocations.is_empty());
+
+        assert_eq!(read_object_file(&to_object_file(&object)), Ok(object));
+        assert_eq!(
+            read_object_file("OBJ8085 1\nDATA 0 0000 00\n"),
+            Err(ObjectError::InvalidRecord(2))
+        );
+        assert_eq!(read_object_file("hello"), Err(ObjectError::MissingHeader));
+    }
+}
@@ -1,20 +1,17 @@
-use crate::{cpu::Registers, token::Token};
+use crate::{
+    cpu::Registers,
+    instruction_set::{lookup, InstructionInfo, Operands},
+    token::Token,
+};
+use logos::Logos;
 use std::fmt;
-static MNEMONICS: &[&str] = &[
-    "ACI", "ADC", "ADD", "ADI", "ANA", "ANI", "CALL", "CC", "CM", "CMA", "CMC", "CMP", "CNC",
-    "CNZ", "CP", "CPE", "CPI", "CPO", "CZ", "DAA", "DAD", "DCR", "DCX", "DI", "EI", "HLT", "IN",
-    "INR", "INX", "JC", "JM", "JMP", "JNC", "JNZ", "JP", "JPE", "JPO", "JZ", "LDA", "LDAX", "LHLD",
-    "LXI", "MOV", "MVI", "NOP", "ORA", "ORI", "OUT", "PCHL", "POP", "PUSH", "RAL", "RAR", "RC",
-    "RET", "RLC", "RM", "RNC", "RNZ", "RP", "RPE", "RPO", "RRC", "RST", "RZ", "SBB", "SBI", "SHLD",
-    "SPHL", "STA", "STAX", "STC", "SUB", "SUI", "XCHG", "XRA", "XRI", "XTHL",
-];
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum JumpTarget<'a> {
     Address(u16),
     Label(&'a str),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Ins<'a> {
     Mov(Registers, Registers),
     Mvi(Registers, u8),
@@ -37,6 +34,292 @@ pub enum Ins<'a> {
     Sta(u16),
     Ani(u8),
     Hlt,
+    Stax(Registers),
+    Ldax(Registers),
+    Dad(Registers),
+    Dcx(Registers),
+    Aci(u8),
+    Sui(u8),
+    Sbb(Registers),
+    Sbi(u8),
+    Ana(Registers),
+    Xra(Registers),
+    Xri(u8),
+    Ora(Registers),
+    Ori(u8),
+    Cpi(u8),
+    Cma,
+    Cmc,
+    Stc,
+    Daa,
+    Rlc,
+    Rrc,
+    Ral,
+    Rar,
+    Jz(JumpTarget<'a>),
+    Jc(JumpTarget<'a>),
+    Jpo(JumpTarget<'a>),
+    Jpe(JumpTarget<'a>),
+    Jp(JumpTarget<'a>),
+    Jm(JumpTarget<'a>),
+    Call(JumpTarget<'a>),
+    Cnz(JumpTarget<'a>),
+    Cz(JumpTarget<'a>),
+    Cnc(JumpTarget<'a>),
+    Cc(JumpTarget<'a>),
+    Cpo(JumpTarget<'a>),
+    Cpe(JumpTarget<'a>),
+    Cp(JumpTarget<'a>),
+    Cm(JumpTarget<'a>),
+    Ret,
+    Rnz,
+    Rz,
+    Rnc,
+    Rc,
+    Rpo,
+    Rpe,
+    Rp,
+    Rm,
+    Rst(u8),
+    Push(Registers),
+    Pop(Registers),
+    Xthl,
+    Sphl,
+    Pchl,
+    In(u8),
+    Out(u8),
+    Ei,
+    Di,
+    Rim,
+    Sim,
+    Nop,
+    Org(u16),
+    Section(&'a str),
+    Public(&'a str),
+    Extrn(&'a str),
+}
+
+impl fmt::Display for JumpTarget<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JumpTarget::Address(address) => write!(f, "{}", format_address(*address)),
+            JumpTarget::Label(label) => write!(f, "{}", label),
+        }
+    }
+}
+
+/// Formats a byte the way the lexer reads it back: hex with an `H` suffix and a leading zero when
+/// the first digit is a letter.
+pub fn format_number(value: u8) -> String {
+    let digits = format!("{:02X}H", value);
+    if digits.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        format!("0{}", digits)
+    } else {
+        digits
+    }
+}
+
+pub fn format_address(address: u16) -> String {
+    let digits = format!("{:04X}H", address);
+    if digits.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        format!("0{}", digits)
+    } else {
+        digits
+    }
+}
+
+impl Ins<'_> {
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Ins::Mov(..) => "MOV",
+            Ins::Mvi(..) => "MVI",
+            Ins::Jmp(..) => "JMP",
+            Ins::Add(..) => "ADD",
+            Ins::Adi(..) => "ADI",
+            Ins::Adc(..) => "ADC",
+            Ins::Sub(..) => "SUB",
+            Ins::Lxi(..) => "LXI",
+            Ins::Lda(..) => "LDA",
+            Ins::Inx(..) => "INX",
+            Ins::Inr(..) => "INR",
+            Ins::Dcr(..) => "DCR",
+            Ins::Jnz(..) => "JNZ",
+            Ins::Lhld(..) => "LHLD",
+            Ins::Xchg => "XCHG",
+            Ins::Shld(..) => "SHLD",
+            Ins::Jnc(..) => "JNC",
+            Ins::Cmp(..) => "CMP",
+            Ins::Sta(..) => "STA",
+            Ins::Ani(..) => "ANI",
+            Ins::Hlt => "HLT",
+            Ins::Stax(..) => "STAX",
+            Ins::Ldax(..) => "LDAX",
+            Ins::Dad(..) => "DAD",
+            Ins::Dcx(..) => "DCX",
+            Ins::Aci(..) => "ACI",
+            Ins::Sui(..) => "SUI",
+            Ins::Sbb(..) => "SBB",
+            Ins::Sbi(..) => "SBI",
+            Ins::Ana(..) => "ANA",
+            Ins::Xra(..) => "XRA",
+            Ins::Xri(..) => "XRI",
+            Ins::Ora(..) => "ORA",
+            Ins::Ori(..) => "ORI",
+            Ins::Cpi(..) => "CPI",
+            Ins::Cma => "CMA",
+            Ins::Cmc => "CMC",
+            Ins::Stc => "STC",
+            Ins::Daa => "DAA",
+            Ins::Rlc => "RLC",
+            Ins::Rrc => "RRC",
+            Ins::Ral => "RAL",
+            Ins::Rar => "RAR",
+            Ins::Jz(..) => "JZ",
+            Ins::Jc(..) => "JC",
+            Ins::Jpo(..) => "JPO",
+            Ins::Jpe(..) => "JPE",
+            Ins::Jp(..) => "JP",
+            Ins::Jm(..) => "JM",
+            Ins::Call(..) => "CALL",
+            Ins::Cnz(..) => "CNZ",
+            Ins::Cz(..) => "CZ",
+            Ins::Cnc(..) => "CNC",
+            Ins::Cc(..) => "CC",
+            Ins::Cpo(..) => "CPO",
+            Ins::Cpe(..) => "CPE",
+            Ins::Cp(..) => "CP",
+            Ins::Cm(..) => "CM",
+            Ins::Ret => "RET",
+            Ins::Rnz => "RNZ",
+            Ins::Rz => "RZ",
+            Ins::Rnc => "RNC",
+            Ins::Rc => "RC",
+            Ins::Rpo => "RPO",
+            Ins::Rpe => "RPE",
+            Ins::Rp => "RP",
+            Ins::Rm => "RM",
+            Ins::Rst(..) => "RST",
+            Ins::Push(..) => "PUSH",
+            Ins::Pop(..) => "POP",
+            Ins::Xthl => "XTHL",
+            Ins::Sphl => "SPHL",
+            Ins::Pchl => "PCHL",
+            Ins::In(..) => "IN",
+            Ins::Out(..) => "OUT",
+            Ins::Ei => "EI",
+            Ins::Di => "DI",
+            Ins::Rim => "RIM",
+            Ins::Sim => "SIM",
+            Ins::Nop => "NOP",
+            Ins::Org(..) => "ORG",
+            Ins::Section(..) => "SECTION",
+            Ins::Public(..) => "PUBLIC",
+            Ins::Extrn(..) => "EXTRN",
+        }
+    }
+
+    /// The instruction set entry for the mnemonic.
+    pub fn info(&self) -> &'static InstructionInfo {
+        lookup(self.mnemonic()).expect("every mnemonic is in the instruction set")
+    }
+
+    /// The number of bytes the instruction assembles to, 0 for directives.
+    pub fn size(&self) -> u16 {
+        self.info().length as u16
+    }
+}
+
+impl fmt::Display for Ins<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ins::Mov(destination, source) => write!(f, "MOV {}, {}", destination, source),
+            Ins::Mvi(register, value) => write!(f, "MVI {}, {}", register, format_number(*value)),
+            Ins::Lxi(register, value) => {
+                write!(f, "LXI {}, {}", register, format_address(*value))
+            }
+            Ins::Lda(address) => write!(f, "LDA {}", format_address(*address)),
+            Ins::Sta(address) => write!(f, "STA {}", format_address(*address)),
+            Ins::Lhld(address) => write!(f, "LHLD {}", format_address(*address)),
+            Ins::Shld(address) => write!(f, "SHLD {}", format_address(*address)),
+            Ins::Org(address) => write!(f, "ORG {}", format_address(*address)),
+            Ins::Section(name) => write!(f, "SECTION {}", name),
+            Ins::Public(name) => write!(f, "PUBLIC {}", name),
+            Ins::Extrn(name) => write!(f, "EXTRN {}", name),
+            Ins::Stax(register) => write!(f, "STAX {}", register),
+            Ins::Ldax(register) => write!(f, "LDAX {}", register),
+            Ins::Inx(register) => write!(f, "INX {}", register),
+            Ins::Dcx(register) => write!(f, "DCX {}", register),
+            Ins::Dad(register) => write!(f, "DAD {}", register),
+            Ins::Push(register) => write!(f, "PUSH {}", register),
+            Ins::Pop(register) => write!(f, "POP {}", register),
+            Ins::Inr(register) => write!(f, "INR {}", register),
+            Ins::Dcr(register) => write!(f, "DCR {}", register),
+            Ins::Add(register) => write!(f, "ADD {}", register),
+            Ins::Adc(register) => write!(f, "ADC {}", register),
+            Ins::Sub(register) => write!(f, "SUB {}", register),
+            Ins::Sbb(register) => write!(f, "SBB {}", register),
+            Ins::Ana(register) => write!(f, "ANA {}", register),
+            Ins::Xra(register) => write!(f, "XRA {}", register),
+            Ins::Ora(register) => write!(f, "ORA {}", register),
+            Ins::Cmp(register) => write!(f, "CMP {}", register),
+            Ins::Adi(value) => write!(f, "ADI {}", format_number(*value)),
+            Ins::Aci(value) => write!(f, "ACI {}", format_number(*value)),
+            Ins::Sui(value) => write!(f, "SUI {}", format_number(*value)),
+            Ins::Sbi(value) => write!(f, "SBI {}", format_number(*value)),
+            Ins::Ani(value) => write!(f, "ANI {}", format_number(*value)),
+            Ins::Xri(value) => write!(f, "XRI {}", format_number(*value)),
+            Ins::Ori(value) => write!(f, "ORI {}", format_number(*value)),
+            Ins::Cpi(value) => write!(f, "CPI {}", format_number(*value)),
+            Ins::In(port) => write!(f, "IN {}", format_number(*port)),
+            Ins::Out(port) => write!(f, "OUT {}", format_number(*port)),
+            Ins::Rst(vector) => write!(f, "RST {}", vector),
+            Ins::Jmp(target) => write!(f, "JMP {}", target),
+            Ins::Jnz(target) => write!(f, "JNZ {}", target),
+            Ins::Jz(target) => write!(f, "JZ {}", target),
+            Ins::Jnc(target) => write!(f, "JNC {}", target),
+            Ins::Jc(target) => write!(f, "JC {}", target),
+            Ins::Jpo(target) => write!(f, "JPO {}", target),
+            Ins::Jpe(target) => write!(f, "JPE {}", target),
+            Ins::Jp(target) => write!(f, "JP {}", target),
+            Ins::Jm(target) => write!(f, "JM {}", target),
+            Ins::Call(target) => write!(f, "CALL {}", target),
+            Ins::Cnz(target) => write!(f, "CNZ {}", target),
+            Ins::Cz(target) => write!(f, "CZ {}", target),
+            Ins::Cnc(target) => write!(f, "CNC {}", target),
+            Ins::Cc(target) => write!(f, "CC {}", target),
+            Ins::Cpo(target) => write!(f, "CPO {}", target),
+            Ins::Cpe(target) => write!(f, "CPE {}", target),
+            Ins::Cp(target) => write!(f, "CP {}", target),
+            Ins::Cm(target) => write!(f, "CM {}", target),
+            Ins::Xchg => write!(f, "XCHG"),
+            Ins::Hlt => write!(f, "HLT"),
+            Ins::Cma => write!(f, "CMA"),
+            Ins::Cmc => write!(f, "CMC"),
+            Ins::Stc => write!(f, "STC"),
+            Ins::Daa => write!(f, "DAA"),
+            Ins::Rlc => write!(f, "RLC"),
+            Ins::Rrc => write!(f, "RRC"),
+            Ins::Ral => write!(f, "RAL"),
+            Ins::Rar => write!(f, "RAR"),
+            Ins::Ret => write!(f, "RET"),
+            Ins::Rnz => write!(f, "RNZ"),
+            Ins::Rz => write!(f, "RZ"),
+            Ins::Rnc => write!(f, "RNC"),
+            Ins::Rc => write!(f, "RC"),
+            Ins::Rpo => write!(f, "RPO"),
+            Ins::Rpe => write!(f, "RPE"),
+            Ins::Rp => write!(f, "RP"),
+            Ins::Rm => write!(f, "RM"),
+            Ins::Xthl => write!(f, "XTHL"),
+            Ins::Sphl => write!(f, "SPHL"),
+            Ins::Pchl => write!(f, "PCHL"),
+            Ins::Ei => write!(f, "EI"),
+            Ins::Di => write!(f, "DI"),
+            Ins::Rim => write!(f, "RIM"),
+            Ins::Sim => write!(f, "SIM"),
+            Ins::Nop => write!(f, "NOP"),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -58,21 +341,79 @@ impl fmt::Display for ParseError {
     }
 }
 
+/// A 1-based position in the source text, counting columns in characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Instruction<'a> {
     pub label: Option<&'a str>,
     pub ins: Ins<'a>,
+    /// Where the instruction (or its label) starts, when it was parsed from source text.
+    pub location: Option<Location>,
 }
 
 pub fn create_instruction(ins: Ins) -> Instruction {
-    Instruction { label: None, ins }
+    Instruction {
+        label: None,
+        ins,
+        location: None,
+    }
 }
 
+/// Where a single register goes: `MOV`, `MVI` and the arithmetic and logic instructions.
+const SINGLE_REGISTERS: &[Registers] = &[
+    Registers::RegA,
+    Registers::RegB,
+    Registers::RegC,
+    Registers::RegD,
+    Registers::RegE,
+    Registers::RegH,
+    Registers::RegL,
+    Registers::RegM,
+];
+/// `LXI`, `INX`, `DCX` and `DAD`.
+const PAIRS: &[Registers] = &[
+    Registers::RegB,
+    Registers::RegD,
+    Registers::RegH,
+    Registers::RegSP,
+];
+/// `LDAX` and `STAX`.
+const INDIRECT_PAIRS: &[Registers] = &[Registers::RegB, Registers::RegD];
+/// `PUSH` and `POP`.
+const STACK_PAIRS: &[Registers] = &[
+    Registers::RegB,
+    Registers::RegD,
+    Registers::RegH,
+    Registers::RegPSW,
+];
+
+/// The next register, which must be one of `allowed`.
 fn next_register<'a>(
     tokens_iter: &mut impl Iterator<Item = Token<'a>>,
+    allowed: &[Registers],
 ) -> Result<Registers, ParseError> {
     match tokens_iter.next() {
-        Some(Token::Register(register)) => Ok(register),
+        Some(Token::Register(register)) if allowed.contains(&register) => Ok(register),
+        Some(Token::Register(register)) => {
+            let names: Vec<String> = allowed.iter().map(Registers::to_string).collect();
+            Err(ParseError::UnexpectedToken(format!(
+                "Expected {} or {}, but received {}",
+                names[..names.len() - 1].join(", "),
+                names[names.len() - 1],
+                register
+            )))
+        }
         Some(token) => Err(ParseError::UnexpectedToken(format!(
             "Expected register, but received {:?}",
             token
@@ -83,7 +424,9 @@ fn next_register<'a>(
 
 fn next_number<'a>(tokens_iter: &mut impl Iterator<Item = Token<'a>>) -> Result<u8, ParseError> {
     match tokens_iter.next() {
-        Some(Token::Number(value)) => Ok(value),
+        Some(Token::Number(value)) => u8::try_from(value).map_err(|_| {
+            ParseError::UnexpectedToken(format!("Expected byte, but received {:X}H", value))
+        }),
         Some(token) => Err(ParseError::UnexpectedToken(format!(
             "Expected number, but received {:?}",
             token
@@ -92,9 +435,16 @@ fn next_number<'a>(tokens_iter: &mut impl Iterator<Item = Token<'a>>) -> Result<
     }
 }
 
+/// A number that must fit in 16 bits.
+fn to_address(value: u32) -> Result<u16, ParseError> {
+    u16::try_from(value).map_err(|_| {
+        ParseError::UnexpectedToken(format!("Expected address, but received {:X}H", value))
+    })
+}
+
 fn next_address<'a>(tokens_iter: &mut impl Iterator<Item = Token<'a>>) -> Result<u16, ParseError> {
     match tokens_iter.next() {
-        Some(Token::Address(address)) => Ok(address),
+        Some(Token::Number(value)) => to_address(value),
         Some(token) => Err(ParseError::UnexpectedToken(format!(
             "Expected address, but received {:?}",
             token
@@ -103,12 +453,23 @@ fn next_address<'a>(tokens_iter: &mut impl Iterator<Item = Token<'a>>) -> Result
     }
 }
 
+fn next_name<'a>(tokens_iter: &mut impl Iterator<Item = Token<'a>>) -> Result<&'a str, ParseError> {
+    match tokens_iter.next() {
+        Some(Token::Word(name)) => Ok(name),
+        Some(token) => Err(ParseError::UnexpectedToken(format!(
+            "Expected name, but received {:?}",
+            token
+        ))),
+        None => Err(ParseError::MissingToken),
+    }
+}
+
 fn next_jump_target<'a>(
     tokens_iter: &mut impl Iterator<Item = Token<'a>>,
 ) -> Result<JumpTarget<'a>, ParseError> {
     match tokens_iter.next() {
         Some(Token::Word(label)) => Ok(JumpTarget::Label(label)),
-        Some(Token::Address(address)) => Ok(JumpTarget::Address(address)),
+        Some(Token::Number(value)) => Ok(JumpTarget::Address(to_address(value)?)),
         Some(token) => Err(ParseError::UnexpectedToken(format!(
             "Expected address or label, but received {:?}",
             token
@@ -121,112 +482,188 @@ pub fn parse_instruction<'a>(
     word: &str,
     tokens_iter: &mut impl Iterator<Item = Token<'a>>,
 ) -> Result<Instruction<'a>, ParseError> {
-    match word {
-        "MVI" => {
-            let register = next_register(tokens_iter)?;
-            let value = next_number(tokens_iter)?;
-            Ok(create_instruction(Ins::Mvi(register, value)))
-        }
-        "JMP" => {
-            let target = next_jump_target(tokens_iter)?;
-            Ok(create_instruction(Ins::Jmp(target)))
-        }
-        "MOV" => {
-            let dest = next_register(tokens_iter)?;
-            let source = next_register(tokens_iter)?;
-            Ok(create_instruction(Ins::Mov(dest, source)))
+    let Some(info) = lookup(word) else {
+        return Err(ParseError::UnimplementedInstruction(format!(
+            "Instruction {} hasn't been implemented yet",
+            word
+        )));
+    };
+    let ins = match info.operands {
+        Operands::Implied => match word {
+            "XCHG" => Ins::Xchg,
+            "HLT" => Ins::Hlt,
+            "CMA" => Ins::Cma,
+            "CMC" => Ins::Cmc,
+            "STC" => Ins::Stc,
+            "DAA" => Ins::Daa,
+            "RLC" => Ins::Rlc,
+            "RRC" => Ins::Rrc,
+            "RAL" => Ins::Ral,
+            "RAR" => Ins::Rar,
+            "RET" => Ins::Ret,
+            "RNZ" => Ins::Rnz,
+            "RZ" => Ins::Rz,
+            "RNC" => Ins::Rnc,
+            "RC" => Ins::Rc,
+            "RPO" => Ins::Rpo,
+            "RPE" => Ins::Rpe,
+            "RP" => Ins::Rp,
+            "RM" => Ins::Rm,
+            "XTHL" => Ins::Xthl,
+            "SPHL" => Ins::Sphl,
+            "PCHL" => Ins::Pchl,
+            "EI" => Ins::Ei,
+            "DI" => Ins::Di,
+            "RIM" => Ins::Rim,
+            "SIM" => Ins::Sim,
+            "NOP" => Ins::Nop,
+            word => unreachable!("{} takes operands", word),
+        },
+        Operands::Source | Operands::Destination => {
+            let register = next_register(tokens_iter, SINGLE_REGISTERS)?;
+            match word {
+                "ADD" => Ins::Add(register),
+                "ADC" => Ins::Adc(register),
+                "SUB" => Ins::Sub(register),
+                "SBB" => Ins::Sbb(register),
+                "ANA" => Ins::Ana(register),
+                "XRA" => Ins::Xra(register),
+                "ORA" => Ins::Ora(register),
+                "CMP" => Ins::Cmp(register),
+                "INR" => Ins::Inr(register),
+                "DCR" => Ins::Dcr(register),
+                word => unreachable!("{} doesn't take a register", word),
+            }
         }
-        "ADD" => {
-            let register = next_register(tokens_iter)?;
-            Ok(create_instruction(Ins::Add(register)))
+        Operands::Move => {
+            let dest = next_register(tokens_iter, SINGLE_REGISTERS)?;
+            let source = next_register(tokens_iter, SINGLE_REGISTERS)?;
+            // Its encoding is HLT's
+            if (dest, source) == (Registers::RegM, Registers::RegM) {
+                return Err(ParseError::UnexpectedToken(
+                    "MOV M, M isn't an 8085 instruction".to_string(),
+                ));
+            }
+            Ins::Mov(dest, source)
         }
-        "ADI" => {
+        Operands::Immediate => {
+            let register = next_register(tokens_iter, SINGLE_REGISTERS)?;
             let value = next_number(tokens_iter)?;
-            Ok(create_instruction(Ins::Adi(value)))
-        }
-        "SUB" => {
-            let register = next_register(tokens_iter)?;
-            Ok(create_instruction(Ins::Sub(register)))
-        }
-        "LXI" => {
-            let register = next_register(tokens_iter)?;
-            let address = next_address(tokens_iter)?;
-            Ok(create_instruction(Ins::Lxi(register, address)))
-        }
-        "INX" => {
-            let register = next_register(tokens_iter)?;
-            Ok(create_instruction(Ins::Inx(register)))
-        }
-        "INR" => {
-            let register = next_register(tokens_iter)?;
-            Ok(create_instruction(Ins::Inr(register)))
-        }
-        "DCR" => {
-            let register = next_register(tokens_iter)?;
-            Ok(create_instruction(Ins::Dcr(register)))
-        }
-        "JNZ" => {
-            let target = next_jump_target(tokens_iter)?;
-            Ok(create_instruction(Ins::Jnz(target)))
-        }
-        "CMP" => {
-            let register = next_register(tokens_iter)?;
-            Ok(create_instruction(Ins::Cmp(register)))
-        }
-        "JNC" => {
-            let target = next_jump_target(tokens_iter)?;
-            Ok(create_instruction(Ins::Jnc(target)))
-        }
-        "STA" => {
-            let address = next_address(tokens_iter)?;
-            Ok(create_instruction(Ins::Sta(address)))
+            Ins::Mvi(register, value)
         }
-        "LHLD" => {
-            let address = next_address(tokens_iter)?;
-            Ok(create_instruction(Ins::Lhld(address)))
+        Operands::Pair => {
+            let allowed = match word {
+                "LDAX" | "STAX" => INDIRECT_PAIRS,
+                "PUSH" | "POP" => STACK_PAIRS,
+                _ => PAIRS,
+            };
+            let register = next_register(tokens_iter, allowed)?;
+            match word {
+                "LDAX" => Ins::Ldax(register),
+                "STAX" => Ins::Stax(register),
+                "INX" => Ins::Inx(register),
+                "DCX" => Ins::Dcx(register),
+                "DAD" => Ins::Dad(register),
+                "PUSH" => Ins::Push(register),
+                "POP" => Ins::Pop(register),
+                word => unreachable!("{} doesn't take a register pair", word),
+            }
         }
-        "SHLD" => {
+        Operands::PairImmediate => {
+            let register = next_register(tokens_iter, PAIRS)?;
             let address = next_address(tokens_iter)?;
-            Ok(create_instruction(Ins::Shld(address)))
+            Ins::Lxi(register, address)
         }
-        "XCHG" => Ok(create_instruction(Ins::Xchg)),
-        "ADC" => {
-            let register = next_register(tokens_iter)?;
-            Ok(create_instruction(Ins::Adc(register)))
+        Operands::Data | Operands::Port => {
+            let value = next_number(tokens_iter)?;
+            match word {
+                "ADI" => Ins::Adi(value),
+                "ACI" => Ins::Aci(value),
+                "SUI" => Ins::Sui(value),
+                "SBI" => Ins::Sbi(value),
+                "ANI" => Ins::Ani(value),
+                "XRI" => Ins::Xri(value),
+                "ORI" => Ins::Ori(value),
+                "CPI" => Ins::Cpi(value),
+                "IN" => Ins::In(value),
+                "OUT" => Ins::Out(value),
+                word => unreachable!("{} doesn't take a byte", word),
+            }
         }
-        "LDA" => {
+        Operands::Address => {
             let address = next_address(tokens_iter)?;
-            Ok(create_instruction(Ins::Lda(address)))
+            match word {
+                "LDA" => Ins::Lda(address),
+                "STA" => Ins::Sta(address),
+                "LHLD" => Ins::Lhld(address),
+                "SHLD" => Ins::Shld(address),
+                "ORG" => Ins::Org(address),
+                word => unreachable!("{} doesn't take an address", word),
+            }
         }
-        "ANI" => {
-            let value = next_number(tokens_iter)?;
-            Ok(create_instruction(Ins::Ani(value)))
+        Operands::Target => {
+            let target = next_jump_target(tokens_iter)?;
+            match word {
+                "JMP" => Ins::Jmp(target),
+                "JNZ" => Ins::Jnz(target),
+                "JZ" => Ins::Jz(target),
+                "JNC" => Ins::Jnc(target),
+                "JC" => Ins::Jc(target),
+                "JPO" => Ins::Jpo(target),
+                "JPE" => Ins::Jpe(target),
+                "JP" => Ins::Jp(target),
+                "JM" => Ins::Jm(target),
+                "CALL" => Ins::Call(target),
+                "CNZ" => Ins::Cnz(target),
+                "CZ" => Ins::Cz(target),
+                "CNC" => Ins::Cnc(target),
+                "CC" => Ins::Cc(target),
+                "CPO" => Ins::Cpo(target),
+                "CPE" => Ins::Cpe(target),
+                "CP" => Ins::Cp(target),
+                "CM" => Ins::Cm(target),
+                word => unreachable!("{} doesn't jump", word),
+            }
         }
-        "HLT" => Ok(create_instruction(Ins::Hlt)),
-        remaining => Err(ParseError::UnimplementedInstruction(format!(
-            "Instruction {} hasn't been implemented yet",
-            remaining
-        ))),
-    }
+        Operands::Vector => match next_number(tokens_iter)? {
+            vector if vector < 8 => Ins::Rst(vector),
+            vector => {
+                return Err(ParseError::UnexpectedToken(format!(
+                    "Expected restart vector 0-7, but received {}",
+                    vector
+                )))
+            }
+        },
+        Operands::Name => Ins::Section(next_name(tokens_iter)?),
+        Operands::Label => match word {
+            "PUBLIC" => Ins::Public(next_name(tokens_iter)?),
+            "EXTRN" => Ins::Extrn(next_name(tokens_iter)?),
+            word => unreachable!("{} doesn't take a label", word),
+        },
+    };
+    Ok(create_instruction(ins))
 }
-pub fn parse(tokens: Vec<Token>) -> Result<Vec<Instruction>, ParseError> {
-    let mut tokens = tokens.into_iter();
+fn parse_located<'a>(
+    tokens: impl Iterator<Item = (Token<'a>, Option<Location>)>,
+) -> Result<Vec<Instruction<'a>>, ParseError> {
+    let mut tokens = tokens.filter(|(token, _)| !matches!(token, Token::Comment(_)));
     let mut instructions = Vec::new();
 
-    while let Some(token) = tokens.next() {
+    while let Some((token, location)) = tokens.next() {
+        let mut operands = tokens.by_ref().map(|(token, _)| token);
         match token {
             Token::Label(label) => {
-                if let Some(Token::Word(word)) = tokens.next() {
-                    let mut instruction = parse_instruction(word, &mut tokens)?;
+                if let Some(Token::Word(word)) = operands.next() {
+                    let mut instruction = parse_instruction(word, &mut operands)?;
                     instruction.label = Some(label);
+                    instruction.location = location;
                     instructions.push(instruction);
                 }
             }
-            Token::Word(word) => {
-                if MNEMONICS.contains(&word) {
-                    let instruction = parse_instruction(word, &mut tokens)?;
-                    instructions.push(instruction);
-                }
+            Token::Word(word) if lookup(word).is_some() => {
+                let mut instruction = parse_instruction(word, &mut operands)?;
+                instruction.location = location;
+                instructions.push(instruction);
             }
             _ => {}
         }
@@ -234,15 +671,120 @@ pub fn parse(tokens: Vec<Token>) -> Result<Vec<Instruction>, ParseError> {
     Ok(instructions)
 }
 
-#[macro_export]
+pub fn parse(tokens: Vec<Token>) -> Result<Vec<Instruction>, ParseError> {
+    parse_located(tokens.into_iter().map(|token| (token, None)))
+}
+
+/// Lexes `source`, recording where each token starts. Text the lexer can't read is kept as an
+/// error holding that text.
+fn located_tokens(source: &str) -> Vec<(Result<Token<'_>, &str>, Location)> {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(source.match_indices('\n').map(|(index, _)| index + 1))
+        .collect();
+    let locate = |offset: usize| {
+        let line = line_starts.partition_point(|start| *start <= offset);
+        Location {
+            line,
+            column: source[line_starts[line - 1]..offset].chars().count() + 1,
+        }
+    };
+
+    Token::lexer(source)
+        .spanned()
+        .map(|(token, span)| {
+            let location = locate(span.start);
+            (token.map_err(|_| &source[span]), location)
+        })
+        .collect()
+}
+
+/// Lexes and parses `source`, dropping text the lexer can't read and recording where every
+/// instruction starts.
+pub fn parse_source(source: &str) -> Result<Vec<Instruction<'_>>, ParseError> {
+    parse_located(
+        located_tokens(source)
+            .into_iter()
+            .filter_map(|(token, location)| Some((token.ok()?, Some(location)))),
+    )
+}
+
+/// One line of source text, as written.
+#[derive(Debug, PartialEq)]
+pub struct SourceLine<'a> {
+    pub label: Option<&'a str>,
+    pub ins: Option<Ins<'a>>,
+    /// The comment including its `;`.
+    pub comment: Option<&'a str>,
+    /// The column the comment starts at.
+    pub comment_column: usize,
+}
+
+/// Parses `source` line by line, keeping comments, blank lines and labels on lines of their
+/// own, which `parse_source` folds away. Unlike `parse_source`, it fails on text the lexer
+/// can't read rather than dropping it.
+pub fn parse_lines(source: &str) -> Result<Vec<SourceLine<'_>>, ParseError> {
+    let mut lines: Vec<SourceLine> = source
+        .lines()
+        .map(|_| SourceLine {
+            label: None,
+            ins: None,
+            comment: None,
+            comment_column: 0,
+        })
+        .collect();
+    let mut tokens = Vec::new();
+    for (token, location) in located_tokens(source) {
+        match token {
+            Ok(token) => tokens.push((token, location)),
+            Err(text) => {
+                return Err(ParseError::UnexpectedToken(format!(
+                    "Line {}, column {}: can't read '{}'",
+                    location.line, location.column, text
+                )))
+            }
+        }
+    }
+
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some((token, location)) = tokens.next() {
+        let line = &mut lines[location.line - 1];
+        match token {
+            Token::Label(label) => line.label = Some(label),
+            Token::Comment(comment) => {
+                line.comment = Some(comment.trim_end());
+                line.comment_column = location.column;
+            }
+            Token::Word(word) if lookup(word).is_some() => {
+                // Operands are the tokens up to the end of the line or a comment
+                let mut operands = std::iter::from_fn(|| {
+                    tokens
+                        .next_if(|(token, next)| {
+                            next.line == location.line && !matches!(token, Token::Comment(_))
+                        })
+                        .map(|(token, _)| token)
+                });
+                line.ins = Some(parse_instruction(word, &mut operands)?.ins);
+            }
+            token => {
+                return Err(ParseError::UnexpectedToken(format!(
+                    "Line {}: unexpected {:?}",
+                    location.line, token
+                )))
+            }
+        }
+    }
+    Ok(lines)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::core::{
-        parser::{Ins, Instruction},
+        cpu::Registers,
+        parser::{Ins, Instruction, JumpTarget},
         token::Token,
     };
 
-    use super::parse;
+    use super::{parse, parse_source, Location};
 
     use logos::Logos;
     macro_rules! parse_code {
@@ -267,20 +809,94 @@ mod tests {
                 Instruction {
                     label: None,
                     ins: Ins::Lxi(super::Registers::RegH, 0x2050),
+                    location: None,
                 },
                 Instruction {
                     label: None,
                     ins: Ins::Mvi(super::Registers::RegB, 0x01),
+                    location: None,
                 },
                 Instruction {
                     label: None,
                     ins: Ins::Mvi(super::Registers::RegC, 0x0A),
+                    location: None,
                 },
                 Instruction {
                     label: Some("X"),
                     ins: Ins::Mov(super::Registers::RegM, super::Registers::RegB),
+                    location: None,
                 }
             ]
         );
     }
+
+    #[test]
+    fn test_locations() {
+        let instructions = parse_source("MVI A, 01H ; one\n\n  X: DCR A\n\tJNZ X\n").unwrap();
+        let locations: Vec<_> = instructions
+            .iter()
+            .map(|instruction| instruction.location.unwrap())
+            .collect();
+        assert_eq!(
+            locations,
+            vec![
+                Location { line: 1, column: 1 },
+                Location { line: 3, column: 3 },
+                Location { line: 4, column: 2 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_registers() {
+        for source in [
+            "MOV A, SP",
+            "INX A",
+            "LDAX H",
+            "PUSH SP",
+            "POP SP",
+            "ADD PSW",
+            "MVI SP, 01H",
+            "LXI A, 2000H",
+            "DAD PSW",
+            "STAX SP",
+            "MOV M, M",
+        ] {
+            assert!(parse_source(source).is_err(), "{}", source);
+        }
+        assert_eq!(
+            parse_source("LDAX H").unwrap_err().to_string(),
+            "Unexpected token: Expected B or D, but received H"
+        );
+        assert_eq!(
+            parse_source("INX A").unwrap_err().to_string(),
+            "Unexpected token: Expected B, D, H or SP, but received A"
+        );
+        assert!(parse_source("PUSH PSW\nPOP H\nLXI SP, 2000H\nMOV M, A").is_ok());
+    }
+
+    #[test]
+    fn test_number_range() {
+        // A literal is one token whatever its length, so three digits aren't split in two.
+        assert_eq!(
+            parse_source("LXI H, 100H\nJMP 123H").unwrap()[0].ins,
+            Ins::Lxi(Registers::RegH, 0x0100)
+        );
+        assert_eq!(
+            parse_source("JMP 123H").unwrap()[0].ins,
+            Ins::Jmp(JumpTarget::Address(0x0123))
+        );
+        assert_eq!(
+            parse_source("MVI A, 0FFH").unwrap()[0].ins,
+            Ins::Mvi(Registers::RegA, 0xFF)
+        );
+        assert_eq!(
+            parse_source("MVI A, 100H").unwrap_err().to_string(),
+            "Unexpected token: Expected byte, but received 100H"
+        );
+        assert_eq!(
+            parse_source("LDA 10000H").unwrap_err().to_string(),
+            "Unexpected token: Expected address, but received 10000H"
+        );
+    }
 }
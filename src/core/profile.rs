@@ -0,0 +1,265 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+use super::image::Image;
+
+/// How many of the hottest instructions `Profiler::report` lists.
+pub const HOT_SPOTS: usize = 10;
+
+/// A change of routine made by an instruction that was taken.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transfer {
+    /// `CALL`, a conditional call or `RST`.
+    Call,
+    /// `RET` or a conditional return.
+    Return,
+}
+
+/// Calls along one edge of the call graph.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CallEdge {
+    pub calls: u64,
+    /// T-states from the calls to their returns, including the calls and returns themselves.
+    pub t_states: u64,
+}
+
+/// Counts executions and T-states per address and calls per call site and target, as
+/// `Cpu::eval` reports them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Profiler {
+    /// How often the instruction at each address ran and the T-states it took in total.
+    pub instructions: BTreeMap<u16, (u64, u64)>,
+    /// Calls by the address of the calling instruction and the address called.
+    pub calls: BTreeMap<(u16, u16), CallEdge>,
+    /// Calls that haven't returned yet, with the T-states when they were made.
+    stack: Vec<((u16, u16), u64)>,
+}
+
+/// Maps addresses to the label-delimited routines of an image.
+struct Routines<'a> {
+    /// The labels inside the image by address, the first by name where several share one.
+    starts: BTreeMap<u16, &'a str>,
+}
+
+impl<'a> Routines<'a> {
+    fn new(image: &'a Image) -> Self {
+        let mut starts: BTreeMap<u16, &str> = BTreeMap::new();
+        for (name, address) in &image.symbols {
+            if !image.contains(*address) {
+                continue;
+            }
+            let start = starts.entry(*address).or_insert(name);
+            if name.as_str() < *start {
+                *start = name;
+            }
+        }
+        Self { starts }
+    }
+
+    /// The label `address` follows, or `(start)` for code before the first label.
+    fn name(&self, address: u16) -> &'a str {
+        self.starts
+            .range(..=address)
+            .next_back()
+            .map_or("(start)", |(_, name)| *name)
+    }
+}
+
+fn percent(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    part as f64 * 100.0 / total as f64
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts the instruction at `address` taking `t_states`, and the call or return it made.
+    /// `pc` and `cycles` are the CPU's after the instruction.
+    pub fn record(
+        &mut self,
+        address: u16,
+        t_states: u64,
+        transfer: Option<Transfer>,
+        pc: u16,
+        cycles: u64,
+    ) {
+        let (count, total) = self.instructions.entry(address).or_insert((0, 0));
+        *count += 1;
+        *total += t_states;
+        match transfer {
+            Some(Transfer::Call) => {
+                self.calls.entry((address, pc)).or_default().calls += 1;
+                self.stack.push(((address, pc), cycles - t_states));
+            }
+            Some(Transfer::Return) => {
+                if let Some((edge, start)) = self.stack.pop() {
+                    self.calls.entry(edge).or_default().t_states += cycles - start;
+                }
+            }
+            None => {}
+        }
+    }
+
+    pub fn executed(&self) -> u64 {
+        self.instructions.values().map(|(count, _)| count).sum()
+    }
+
+    pub fn t_states(&self) -> u64 {
+        self.instructions
+            .values()
+            .map(|(_, t_states)| t_states)
+            .sum()
+    }
+
+    /// Executions and T-states of each label-delimited routine of `image`, hottest first.
+    pub fn routines<'a>(&self, image: &'a Image) -> Vec<(&'a str, u64, u64)> {
+        let routines = Routines::new(image);
+        let mut totals: HashMap<&str, (u64, u64)> = HashMap::new();
+        for (address, (count, t_states)) in &self.instructions {
+            let total = totals.entry(routines.name(*address)).or_insert((0, 0));
+            total.0 += count;
+            total.1 += t_states;
+        }
+        let mut totals: Vec<(&str, u64, u64)> = totals
+            .into_iter()
+            .map(|(name, (count, t_states))| (name, count, t_states))
+            .collect();
+        totals.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(b.0)));
+        totals
+    }
+
+    /// Calls between the routines of `image`, by caller and callee, most T-states first.
+    pub fn call_graph<'a>(&self, image: &'a Image) -> Vec<(&'a str, &'a str, CallEdge)> {
+        let routines = Routines::new(image);
+        let mut edges: BTreeMap<(&str, &str), CallEdge> = BTreeMap::new();
+        for ((site, target), edge) in &self.calls {
+            let total = edges
+                .entry((routines.name(*site), routines.name(*target)))
+                .or_default();
+            total.calls += edge.calls;
+            total.t_states += edge.t_states;
+        }
+        let mut edges: Vec<(&str, &str, CallEdge)> = edges
+            .into_iter()
+            .map(|((caller, callee), edge)| (caller, callee, edge))
+            .collect();
+        edges.sort_by_key(|(_, _, edge)| std::cmp::Reverse(edge.t_states));
+        edges
+    }
+
+    /// The totals, then routines, the `HOT_SPOTS` hottest instructions and the call graph,
+    /// each with its share of the T-states.
+    pub fn report(&self, image: &Image) -> String {
+        let total = self.t_states();
+        let mut output = format!(
+            "{} instructions, {} T-states\n\nRoutines\n{:<20} {:>10} {:>12} {:>7}\n",
+            self.executed(),
+            total,
+            "Routine",
+            "Count",
+            "T-states",
+            "%"
+        );
+        for (name, count, t_states) in self.routines(image) {
+            writeln!(
+                output,
+                "{:<20} {:>10} {:>12} {:>6.1}%",
+                name,
+                count,
+                t_states,
+                percent(t_states, total)
+            )
+            .unwrap();
+        }
+
+        let mut hot: Vec<(&u16, &(u64, u64))> = self.instructions.iter().collect();
+        hot.sort_by(|a, b| b.1 .1.cmp(&a.1 .1).then(a.0.cmp(b.0)));
+        write!(
+            output,
+            "\nHot spots\n{:>10} {:>12} {:>7}  Instruction\n",
+            "Count", "T-states", "%"
+        )
+        .unwrap();
+        for (address, (count, t_states)) in hot.into_iter().take(HOT_SPOTS) {
+            writeln!(
+                output,
+                "{:>10} {:>12} {:>6.1}%  {}",
+                count,
+                t_states,
+                percent(*t_states, total),
+                image.source_map.describe(*address)
+            )
+            .unwrap();
+        }
+
+        let edges = self.call_graph(image);
+        if !edges.is_empty() {
+            write!(
+                output,
+                "\nCalls\n{:<30} {:>10} {:>12} {:>10}\n",
+                "Caller -> Callee", "Calls", "T-states", "Per call"
+            )
+            .unwrap();
+            for (caller, callee, edge) in edges {
+                writeln!(
+                    output,
+                    "{:<30} {:>10} {:>12} {:>10}",
+                    format!("{} -> {}", caller, callee),
+                    edge.calls,
+                    edge.t_states,
+                    edge.t_states / edge.calls.max(1)
+                )
+                .unwrap();
+            }
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CallEdge, Profiler};
+
+    #[test]
+    fn test_profiler() {
+        let source = "MVI B, 02H\nCALL DELAY\nCALL DELAY\nHLT\nDELAY: MVI C, 03H\nLOOP: DCR C\nJNZ LOOP\nRET\n";
+        let (mut cpu, image) = crate::execute_code(source);
+        cpu.profiler = Some(Profiler::new());
+        cpu.run(&image, 100);
+        let profiler = cpu.profiler.unwrap();
+
+        assert_eq!(profiler.t_states(), cpu.cycles);
+        assert_eq!(profiler.executed(), 4 + 2 * (2 + 2 * 3));
+        // DCR C runs three times per call, for 4 T-states each.
+        assert_eq!(profiler.instructions[&0x000B], (6, 24));
+
+        let routines = profiler.routines(&image);
+        let names: Vec<&str> = routines.iter().map(|(name, _, _)| *name).collect();
+        assert_eq!(names, vec!["LOOP", "(start)", "DELAY"]);
+        // MVI B, two CALLs and HLT.
+        assert_eq!(routines[1].2, 7 + 18 + 18 + 5);
+
+        // Each call takes the CALL, MVI C, three DCRs, two taken and one not taken JNZ, and RET.
+        let per_call = 18 + 7 + 3 * 4 + 2 * 10 + 7 + 10;
+        assert_eq!(
+            profiler.call_graph(&image),
+            vec![(
+                "(start)",
+                "DELAY",
+                CallEdge {
+                    calls: 2,
+                    t_states: 2 * per_call
+                }
+            )]
+        );
+
+        let report = profiler.report(&image);
+        assert!(report.starts_with(&format!("20 instructions, {} T-states\n", cpu.cycles)));
+        assert!(report.contains("(start) -> DELAY"));
+        assert!(report.contains("000BH (line 6:1)"));
+    }
+}
@@ -0,0 +1,55 @@
+use std::fmt::Write;
+
+use super::instruction_set::{DIRECTIVES, INSTRUCTIONS};
+
+const INTRODUCTION: &str = "\
+# Instruction reference
+
+Generated from `src/core/instruction_set.rs` by `cargo run --bin docs`. `cargo test` fails when \
+this file and the code disagree.
+
+The encoding shows the opcode in binary, followed by the operand bytes; words are stored low byte \
+first. Registers go in `DDD` and `SSS` as B 000, C 001, D 010, E 011, H 100, L 101, M 110 and \
+A 111. Register pairs go in `RP` as B 00, D 01, H 10 and SP 11, or PSW 11 for `PUSH` and `POP`. \
+`NNN` is the restart vector.
+
+Cycles are T-states. `4 (7 with M)` means 7 when an operand is the memory at HL, and `7/10` means \
+7 when the condition fails and 10 when it holds. Flags are the ones the instruction changes.
+";
+
+/// The instruction reference in DOCUMENTATION.md, as Markdown.
+pub fn instruction_reference() -> String {
+    let mut text = String::from(INTRODUCTION);
+    text.push_str("\n## Instructions\n\n");
+    text.push_str("| Syntax | Encoding | Bytes | Cycles | Flags | Description | Example |\n");
+    text.push_str("| --- | --- | --- | --- | --- | --- | --- |\n");
+    for info in INSTRUCTIONS {
+        writeln!(
+            text,
+            "| `{}` | `{}` | {} | {} | {} | {} | `{}` |",
+            info.syntax(),
+            info.encoding(),
+            info.length,
+            info.cycles,
+            info.flags,
+            info.description,
+            info.example()
+        )
+        .unwrap();
+    }
+
+    text.push_str("\n## Directives\n\n");
+    text.push_str("| Syntax | Description | Example |\n");
+    text.push_str("| --- | --- | --- |\n");
+    for info in DIRECTIVES {
+        writeln!(
+            text,
+            "| `{}` | {} | `{}` |",
+            info.syntax(),
+            info.description,
+            info.example()
+        )
+        .unwrap();
+    }
+    text
+}
@@ -0,0 +1,216 @@
+use std::fmt;
+
+use super::{
+    cpu::{Cpu, FlagRegisters},
+    history::History,
+};
+
+const MAGIC: &str = "SNAP8085";
+const VERSION: u32 = 1;
+const BYTES_PER_RECORD: usize = 16;
+
+#[derive(Debug, PartialEq)]
+pub enum SnapshotError {
+    MissingHeader,
+    UnsupportedVersion(String),
+    InvalidRecord(usize),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::MissingHeader => write!(f, "Not a snapshot file"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported snapshot version {}", version)
+            }
+            SnapshotError::InvalidRecord(line) => write!(f, "Line {}: invalid record", line),
+        }
+    }
+}
+
+/// Writes the machine state of `cpu` in the text snapshot format:
+///
+/// ```text
+/// SNAP8085 1
+/// PC <address>
+/// SP <address>
+/// REGS <A> <B> <C> <D> <E> <H> <L>
+/// FLAGS <byte>
+/// INTERRUPTS <enabled> <mask>
+/// SOD <level>
+/// CYCLES <count>
+/// PORT <port> <byte>
+/// MEM <address> <bytes>...
+/// ```
+///
+/// All numbers except the cycle count and the `0`/`1` of `INTERRUPTS` and `SOD` are hex. Only
+/// ports and 16 byte rows of memory that aren't zero are written. Breakpoints, watchpoints, the
+/// tracer and the history belong to the debugger and aren't saved.
+pub fn to_snapshot(cpu: &Cpu) -> String {
+    let mut output = format!("{} {}\n", MAGIC, VERSION);
+    output.push_str(&format!("PC {:04X}\nSP {:04X}\n", cpu.pc, cpu.sp));
+    output.push_str(&format!(
+        "REGS {:02X} {:02X} {:02X} {:02X} {:02X} {:02X} {:02X}\n",
+        cpu.accumulator, cpu.b, cpu.c, cpu.d, cpu.e, cpu.h, cpu.l
+    ));
+    output.push_str(&format!("FLAGS {:02X}\n", cpu.flags.to_byte()));
+    output.push_str(&format!(
+        "INTERRUPTS {} {:02X}\n",
+        cpu.interrupts_enabled as u8, cpu.interrupt_mask
+    ));
+    output.push_str(&format!("SOD {}\n", cpu.serial_output as u8));
+    output.push_str(&format!("CYCLES {}\n", cpu.cycles));
+
+    for (port, value) in cpu.ports.iter().enumerate() {
+        if *value != 0 {
+            output.push_str(&format!("PORT {:02X} {:02X}\n", port, value));
+        }
+    }
+    for (index, chunk) in cpu.memory.chunks(BYTES_PER_RECORD).enumerate() {
+        if chunk.iter().any(|byte| *byte != 0) {
+            let bytes: Vec<String> = chunk.iter().map(|byte| format!("{:02X}", byte)).collect();
+            output.push_str(&format!(
+                "MEM {:04X} {}\n",
+                index * BYTES_PER_RECORD,
+                bytes.join(" ")
+            ));
+        }
+    }
+    output
+}
+
+fn parse_record(cpu: &mut Cpu, fields: &[&str]) -> Option<()> {
+    let word = |field: &str| u16::from_str_radix(field, 16).ok();
+    let byte = |field: &str| u8::from_str_radix(field, 16).ok();
+    let level = |field: &str| match field {
+        "0" => Some(false),
+        "1" => Some(true),
+        _ => None,
+    };
+
+    match fields {
+        ["PC", address] => cpu.pc = word(address)?,
+        ["SP", address] => cpu.sp = word(address)?,
+        ["REGS", registers @ ..] if registers.len() == 7 => {
+            let mut values = [0; 7];
+            for (value, register) in values.iter_mut().zip(registers) {
+                *value = byte(register)?;
+            }
+            [cpu.accumulator, cpu.b, cpu.c, cpu.d, cpu.e, cpu.h, cpu.l] = values;
+        }
+        ["FLAGS", flags] => cpu.flags = FlagRegisters::from_byte(byte(flags)?),
+        ["INTERRUPTS", enabled, mask] => {
+            cpu.interrupts_enabled = level(enabled)?;
+            cpu.interrupt_mask = byte(mask)?;
+        }
+        ["SOD", output] => cpu.serial_output = level(output)?,
+        ["CYCLES", cycles] => cpu.cycles = cycles.parse().ok()?,
+        ["PORT", port, value] => cpu.ports[byte(port)? as usize] = byte(value)?,
+        ["MEM", address, bytes @ ..] => {
+            let address = word(address)? as usize;
+            if address + bytes.len() > cpu.memory.len() {
+                return None;
+            }
+            for (offset, value) in bytes.iter().enumerate() {
+                cpu.write_memory(address + offset, byte(value)?);
+            }
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+/// Reads a snapshot into a new `Cpu`; state the snapshot doesn't mention keeps its reset value.
+pub fn read_snapshot(source: &str) -> Result<Cpu, SnapshotError> {
+    let mut lines = source.lines().enumerate();
+    match lines
+        .next()
+        .map(|(_, line)| line.split_whitespace().collect::<Vec<_>>())
+    {
+        Some(header) if header.len() == 2 && header[0] == MAGIC => {
+            if header[1] != VERSION.to_string() {
+                return Err(SnapshotError::UnsupportedVersion(header[1].to_string()));
+            }
+        }
+        _ => return Err(SnapshotError::MissingHeader),
+    }
+
+    let mut cpu = Cpu::new();
+    for (index, line) in lines {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.is_empty() {
+            continue;
+        }
+        parse_record(&mut cpu, &fields).ok_or(SnapshotError::InvalidRecord(index + 1))?;
+    }
+    Ok(cpu)
+}
+
+/// Replaces the machine state of `cpu` with a snapshot, keeping its breakpoints, watchpoints,
+/// tracer, coverage and profiler. The history is cleared, as it can't be undone past the restore.
+pub fn restore_snapshot(cpu: &mut Cpu, source: &str) -> Result<(), SnapshotError> {
+    let mut restored = read_snapshot(source)?;
+    restored.breakpoints = std::mem::take(&mut cpu.breakpoints);
+    restored.watchpoints = std::mem::take(&mut cpu.watchpoints);
+    restored.tracer = cpu.tracer.take();
+    restored.coverage = cpu.coverage.take();
+    restored.profiler = cpu.profiler.take();
+    restored.history = cpu
+        .history
+        .as_ref()
+        .map(|history| History::new(history.capacity));
+    *cpu = restored;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::{breakpoints::Breakpoint, cpu::Cpu, history::History};
+
+    use super::{read_snapshot, restore_snapshot, to_snapshot, SnapshotError};
+
+    #[test]
+    fn test_snapshot() {
+        let (mut cpu, image) =
+            crate::execute_code("LXI SP, 2400H\nMVI A, 0F0H\nADI 20H\nSTA 2050H\nOUT 03H\nEI\nHLT");
+        cpu.run(&image, 100);
+        let snapshot = to_snapshot(&cpu);
+        assert!(snapshot.starts_with("SNAP8085 1\nPC 000E\nSP 2400\n"));
+        assert!(snapshot.contains("FLAGS 03\n"));
+        assert!(snapshot.contains("INTERRUPTS 1 07\n"));
+        assert!(snapshot.contains("PORT 03 10\n"));
+        assert!(snapshot.contains("MEM 2050 10 00 00"));
+
+        let restored = read_snapshot(&snapshot).unwrap();
+        assert_eq!(to_snapshot(&restored), snapshot);
+        assert_eq!(restored.flags, cpu.flags);
+        assert_eq!(restored.cycles, cpu.cycles);
+        assert_eq!(restored.ports, cpu.ports);
+        assert!(restored.memory == cpu.memory);
+
+        let mut debugged = Cpu::new();
+        debugged.breakpoints.push(Breakpoint {
+            address: 0x0005,
+            condition: None,
+        });
+        debugged.history = Some(History::new(10));
+        debugged.run(&image, 1);
+        restore_snapshot(&mut debugged, &snapshot).unwrap();
+        assert_eq!(debugged.pc, 0x000E);
+        assert_eq!(debugged.breakpoints.len(), 1);
+        assert!(debugged.history.as_ref().unwrap().is_empty());
+
+        assert_eq!(
+            read_snapshot("PC 0000").err(),
+            Some(SnapshotError::MissingHeader)
+        );
+        assert_eq!(
+            read_snapshot("SNAP8085 2\n").err(),
+            Some(SnapshotError::UnsupportedVersion("2".to_string()))
+        );
+        assert_eq!(
+            read_snapshot("SNAP8085 1\nPC 0000\nMEM FFFF 01 02\n").err(),
+            Some(SnapshotError::InvalidRecord(3))
+        );
+    }
+}
@@ -1,22 +1,26 @@
 use eframe::egui::{text::LayoutJob, Color32, TextFormat};
 
+use super::instruction_set::{DIRECTIVES, INSTRUCTIONS};
+
 #[derive(Debug)]
 pub struct Language {
     keywords: std::collections::BTreeSet<&'static str>,
 }
 
+impl Default for Language {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Language {
     pub fn new() -> Self {
         Self {
-            keywords: [
-                "mov", "mvi", "lxi", "lda", "sta", "lhld", "shld", "stax", "xchg", "add", "adi",
-                "sub", "sui", "inr", "dcr", "inx", "dcx", "dad", "ana", "ani", "ora", "ori", "xra",
-                "xri", "cma", "rlc", "rrc", "ral", "rar", "push", "pop", "jmp", "jnz", "jz", "jnc",
-                "jc", "call", "ret", "rst", "pchl", "sphl", "xthl", "out", "in", "ei", "di", "rim",
-                "sim", "hlt", "nop",
-            ]
-            .into_iter()
-            .collect(),
+            keywords: INSTRUCTIONS
+                .iter()
+                .chain(DIRECTIVES)
+                .map(|info| info.mnemonic)
+                .collect(),
         }
     }
 
@@ -25,7 +29,8 @@ impl Language {
     }
 }
 
-pub fn highlight(mut text: &str) -> LayoutJob {
+/// Highlights `text`, giving `current_line` (1-based) a background to mark it as executing.
+pub fn highlight(mut text: &str, current_line: Option<usize>) -> LayoutJob {
     let language = Language::new();
     let mut job = LayoutJob::default();
     let font_id = eframe::egui::FontId::monospace(17.0);
@@ -36,53 +41,46 @@ pub fn highlight(mut text: &str) -> LayoutJob {
     // TokenType::Punctuation => TextFormat::simple(font_id.clone(), Color32::LIGHT_GRAY),
     // TokenType::Whitespace => TextFormat::simple(font_id.clone(), Color32::TRANSPARENT),
 
+    let mut line = 1;
+    let format = |color: Color32, line: usize| {
+        let mut format = TextFormat::simple(font_id.clone(), color);
+        if current_line == Some(line) {
+            format.background = Color32::from_rgb(60, 60, 30);
+        }
+        format
+    };
+
     while !text.is_empty() {
         if text.starts_with(";") {
             let end = text.find("\n").unwrap_or(text.len());
-            job.append(
-                &text[..end],
-                0.0,
-                TextFormat::simple(font_id.clone(), Color32::from_gray(120)),
-            );
+            job.append(&text[..end], 0.0, format(Color32::from_gray(120), line));
             text = &text[end..];
         } else if text.starts_with(|c: char| c.is_ascii_alphanumeric()) {
             let end = text[1..]
                 .find(|c: char| !c.is_ascii_alphanumeric())
                 .map_or_else(|| text.len(), |i| i + 1);
             let word = &text[..end];
-            if language.is_keyword(&word.to_lowercase()) {
-                job.append(
-                    word,
-                    0.0,
-                    TextFormat::simple(font_id.clone(), Color32::from_rgb(109, 147, 226)),
-                );
+            if language.is_keyword(&word.to_uppercase()) {
+                job.append(word, 0.0, format(Color32::from_rgb(109, 147, 226), line));
             } else {
-                job.append(
-                    word,
-                    0.0,
-                    TextFormat::simple(font_id.clone(), Color32::from_rgb(255, 100, 100)),
-                );
+                job.append(word, 0.0, format(Color32::from_rgb(255, 100, 100), line));
             }
             text = &text[end..];
+        } else if text.starts_with('\n') {
+            job.append("\n", 0.0, format(Color32::TRANSPARENT, line));
+            line += 1;
+            text = &text[1..];
         } else if text.starts_with(|c: char| c.is_ascii_whitespace()) {
             let end = text[1..]
-                .find(|c: char| !c.is_ascii_whitespace())
+                .find(|c: char| !c.is_ascii_whitespace() || c == '\n')
                 .map_or_else(|| text.len(), |i| i + 1);
-            job.append(
-                &text[..end],
-                0.0,
-                TextFormat::simple(font_id.clone(), Color32::TRANSPARENT),
-            );
+            job.append(&text[..end], 0.0, format(Color32::TRANSPARENT, line));
             text = &text[end..];
         } else {
             let mut it = text.char_indices();
             it.next();
             let end = it.next().map_or(text.len(), |(idx, _chr)| idx);
-            job.append(
-                &text[..end],
-                0.0,
-                TextFormat::simple(font_id.clone(), Color32::LIGHT_GRAY),
-            );
+            job.append(&text[..end], 0.0, format(Color32::LIGHT_GRAY, line));
             text = &text[end..];
         }
     }
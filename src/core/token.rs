@@ -1,5 +1,3 @@
-use std::fmt::{write, Display};
-
 use super::cpu::Registers;
 use logos::Logos;
 
@@ -13,18 +11,16 @@ pub enum Token<'a> {
     #[regex(r"[a-zA-Z]{1,8}", |lex| lex.slice())]
     Word(&'a str),
 
-    #[regex(r"[ABCDEHLM]", |lex|  Registers::from(lex.slice()),priority=2)]
+    #[regex(r"[ABCDEHLM]|SP|PSW", |lex|  Registers::from(lex.slice()),priority=3)]
     Register(Registers),
 
-    #[regex(r"[;].*", logos::skip)]
-    Comment,
+    #[regex(r"[;].*", |lex| lex.slice())]
+    Comment(&'a str),
 
     #[regex(r"[a-zA-z]+:", |lex| lex.slice().trim_end_matches(':'))]
     Label(&'a str),
 
-    #[regex(r"[0-9A-F]{2}", |lex| u8::from_str_radix(lex.slice(), 16).unwrap())]
-    Number(u8),
-
-    #[regex(r"[0-9]{4}", |lex| u16::from_str_radix(lex.slice(), 16).unwrap())]
-    Address(u16),
+    /// A hex literal of any length; the parser checks it fits the operand.
+    #[regex(r"[0-9][0-9A-F]*H?", |lex| u32::from_str_radix(lex.slice().trim_end_matches('H'), 16).ok())]
+    Number(u32),
 }
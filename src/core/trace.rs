@@ -0,0 +1,231 @@
+use super::{
+    cpu::{Cpu, FlagRegisters},
+    json::Json,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraceFormat {
+    Text,
+    JsonLines,
+}
+
+impl TraceFormat {
+    pub fn from(name: &str) -> Option<TraceFormat> {
+        match name.to_lowercase().as_str() {
+            "text" | "txt" => Some(TraceFormat::Text),
+            "json" | "jsonl" => Some(TraceFormat::JsonLines),
+            _ => None,
+        }
+    }
+}
+
+/// One executed instruction, with the registers and flags as it left them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub ins: String,
+    pub a: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+    pub flags: FlagRegisters,
+    pub cycles: u64,
+    /// Every `(address, value)` the instruction stored to memory.
+    pub writes: Vec<(u16, u8)>,
+}
+
+impl TraceEntry {
+    pub fn new(
+        cpu: &Cpu,
+        address: u16,
+        bytes: Vec<u8>,
+        ins: String,
+        writes: Vec<(u16, u8)>,
+    ) -> Self {
+        Self {
+            address,
+            bytes,
+            ins,
+            a: cpu.accumulator,
+            b: cpu.b,
+            c: cpu.c,
+            d: cpu.d,
+            e: cpu.e,
+            h: cpu.h,
+            l: cpu.l,
+            sp: cpu.sp,
+            pc: cpu.pc,
+            flags: cpu.flags.clone(),
+            cycles: cpu.cycles,
+            writes,
+        }
+    }
+
+    /// `2000: 3E 05     MVI A, 05H       A=05 B=00 ... PC=2002 F=-Z-P- T=7 [2050]=05`, flags
+    /// being `S Z A(C) P C(Y)` or `-` when clear.
+    pub fn to_text(&self) -> String {
+        let bytes: Vec<String> = self
+            .bytes
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        let flags: String = [
+            (self.flags.sign, 'S'),
+            (self.flags.zero, 'Z'),
+            (self.flags.auxiliary_carry, 'A'),
+            (self.flags.parity, 'P'),
+            (self.flags.carry, 'C'),
+        ]
+        .iter()
+        .map(|(set, letter)| if *set { *letter } else { '-' })
+        .collect();
+        let mut line = format!(
+            "{:04X}: {:<9} {:<16} A={:02X} B={:02X} C={:02X} D={:02X} E={:02X} H={:02X} L={:02X} SP={:04X} PC={:04X} F={} T={}",
+            self.address,
+            bytes.join(" "),
+            self.ins,
+            self.a,
+            self.b,
+            self.c,
+            self.d,
+            self.e,
+            self.h,
+            self.l,
+            self.sp,
+            self.pc,
+            flags,
+            self.cycles
+        );
+        for (address, value) in &self.writes {
+            line.push_str(&format!(" [{:04X}]={:02X}", address, value));
+        }
+        line
+    }
+
+    /// The entry as a JSON object on one line; numbers are written as numbers.
+    pub fn to_json(&self) -> String {
+        let bytes: Vec<Json> = self
+            .bytes
+            .iter()
+            .map(|byte| u64::from(*byte).into())
+            .collect();
+        let writes: Vec<Json> = self
+            .writes
+            .iter()
+            .map(|(address, value)| {
+                Json::object(vec![
+                    ("address", u64::from(*address).into()),
+                    ("value", u64::from(*value).into()),
+                ])
+            })
+            .collect();
+        Json::object(vec![
+            ("address", u64::from(self.address).into()),
+            ("bytes", bytes.into()),
+            ("ins", self.ins.as_str().into()),
+            ("a", u64::from(self.a).into()),
+            ("b", u64::from(self.b).into()),
+            ("c", u64::from(self.c).into()),
+            ("d", u64::from(self.d).into()),
+            ("e", u64::from(self.e).into()),
+            ("h", u64::from(self.h).into()),
+            ("l", u64::from(self.l).into()),
+            ("sp", u64::from(self.sp).into()),
+            ("pc", u64::from(self.pc).into()),
+            ("flags", u64::from(self.flags.to_byte()).into()),
+            ("cycles", self.cycles.into()),
+            ("writes", writes.into()),
+        ])
+        .to_string()
+    }
+
+    pub fn format(&self, format: TraceFormat) -> String {
+        match format {
+            TraceFormat::Text => self.to_text(),
+            TraceFormat::JsonLines => self.to_json(),
+        }
+    }
+}
+
+/// Collects a `TraceEntry` for every instruction `Cpu::eval` executes inside `ranges` (or
+/// anywhere when there are none). Front ends take the entries as they go.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tracer {
+    /// Inclusive address ranges of the instructions to trace.
+    pub ranges: Vec<(u16, u16)>,
+    pub entries: Vec<TraceEntry>,
+}
+
+impl Tracer {
+    pub fn new(ranges: Vec<(u16, u16)>) -> Self {
+        Self {
+            ranges,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn traces(&self, address: u16) -> bool {
+        self.ranges.is_empty()
+            || self
+                .ranges
+                .iter()
+                .any(|(start, end)| (*start..=*end).contains(&address))
+    }
+
+    /// Formats the collected entries, one per line, and forgets them.
+    pub fn take(&mut self, format: TraceFormat) -> String {
+        let mut output = String::new();
+        for entry in self.entries.drain(..) {
+            output.push_str(&entry.format(format));
+            output.push('\n');
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::cpu::Cpu;
+
+    use super::{TraceFormat, Tracer};
+
+    #[test]
+    fn test_trace() {
+        let (mut cpu, image) =
+            crate::execute_code("MVI A, 05H\nSTA 2050H\nJMP 0010H\nORG 0010H\nHLT");
+        cpu.tracer = Some(Tracer::new(vec![(0x0000, 0x000F)]));
+        cpu.run(&image, 10);
+        let tracer = cpu.tracer.as_mut().unwrap();
+        assert_eq!(tracer.entries.len(), 3);
+        assert_eq!(tracer.entries[1].writes, vec![(0x2050, 0x05)]);
+
+        assert_eq!(
+            tracer.entries[0].to_text(),
+            "0000: 3E 05     MVI A, 05H       A=05 B=00 C=00 D=00 E=00 H=00 L=00 SP=0000 PC=0002 F=----- T=7"
+        );
+        assert_eq!(
+            tracer.entries[1].to_json(),
+            "{\"address\":2,\"bytes\":[50,80,32],\"ins\":\"STA 2050H\",\"a\":5,\"b\":0,\"c\":0,\"d\":0,\"e\":0,\"h\":0,\"l\":0,\"sp\":0,\"pc\":5,\"flags\":2,\"cycles\":20,\"writes\":[{\"address\":8272,\"value\":5}]}"
+        );
+        assert_eq!(tracer.take(TraceFormat::Text).lines().count(), 3);
+        assert!(tracer.entries.is_empty());
+        assert!(Cpu::new().tracer.is_none());
+    }
+
+    #[test]
+    fn test_trace_self_modifying_code() {
+        // STA overwrites its own opcode with NOP
+        let (mut cpu, image) = crate::execute_code("MVI A, 00H\nSTA 0002H\nHLT");
+        cpu.tracer = Some(Tracer::new(Vec::new()));
+        cpu.run(&image, 10);
+        let tracer = cpu.tracer.as_ref().unwrap();
+        assert_eq!(tracer.entries[1].bytes, vec![0x32, 0x02, 0x00]);
+        assert_eq!(tracer.entries[1].writes, vec![(0x0002, 0x00)]);
+    }
+}
@@ -1,19 +1,42 @@
-use logos::Logos;
 use std::fs;
 use std::time::Duration;
 
-use eframe::egui::{include_image, CentralPanel, Context, Image, TextEdit, TextStyle, Vec2};
+use eframe::egui::{self, include_image, Button, CentralPanel, Context, TextEdit, TextStyle, Vec2};
 
-use crate::gui::{memory::render_memory, registers::render_registers};
+use crate::gui::{memory::render_memory, messages::render_messages, registers::render_registers};
 
-use crate::core::{assembler::assemble, cpu::Cpu, parser::parse, token::Token};
+use crate::core::{
+    assembler::assemble,
+    cpu::{Cpu, Stop},
+    history::{self, History},
+    image::Image,
+    lint::lint,
+    parser::parse_source,
+};
 use crate::syntax_highlighting;
 
+/// How many instructions Run executes before giving up.
+const MAX_RUN_STEPS: u64 = 1_000_000;
+
 pub struct Application {
     pub source: String,
     pub address: Vec<(String, String)>,
     pub cpu: Cpu,
-    assembled_instructions: Vec<u8>,
+    image: Image,
+    /// The source line of the last instruction that ran, or the next one while debugging.
+    current_line: Option<usize>,
+    /// Whether the program is being stepped through.
+    debugging: bool,
+    /// Whether the program being stepped through has halted or faulted.
+    stopped: bool,
+    /// Errors and lint warnings from the last assembly, and why the program stopped.
+    pub messages: Vec<String>,
+}
+
+impl Default for Application {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Application {
@@ -22,7 +45,11 @@ impl Application {
             source: String::new(),
             address: vec![(String::new(), String::new()); 0xFFFF],
             cpu: Cpu::new(),
-            assembled_instructions: vec![],
+            image: Image::new(),
+            current_line: None,
+            debugging: false,
+            stopped: false,
+            messages: Vec::new(),
         }
     }
 
@@ -36,11 +63,16 @@ impl Application {
         self.cpu.reset_pc();
         self.cpu.reset_flags();
         self.address = vec![(String::new(), String::new()); 0xFFFF];
-        self.assembled_instructions = vec![];
+        self.image = Image::new();
+        self.current_line = None;
+        self.cpu.history = None;
+        self.debugging = false;
+        self.stopped = false;
+        self.messages.clear();
     }
 
-    fn evaluate(&mut self) {
-        let assembled_count = self.assembled_instructions.iter().len();
+    /// Copies the memory editor into memory and points `pc` at the program.
+    fn load(&mut self) {
         for (address, value) in self.address.iter() {
             if let (Ok(address), Ok(value)) = (
                 u16::from_str_radix(address, 16),
@@ -49,13 +81,12 @@ impl Application {
                 self.cpu.write_memory(address as usize, value);
             }
         }
+        self.cpu.pc = self.image.entry;
+    }
 
-        while let Some(pc) = self.cpu.eval() {
-            if pc as usize >= assembled_count {
-                break;
-            }
-        }
-
+    /// Fills the memory editor with the non-zero bytes of memory.
+    fn show_memory(&mut self) {
+        self.address = vec![(String::new(), String::new()); 0xFFFF];
         let non_zero_entries: Vec<(usize, &u8)> = self
             .cpu
             .memory
@@ -68,24 +99,91 @@ impl Application {
             self.address[index].0 = format!("{:04X}", address);
             self.address[index].1 = format!("{:02X}", value);
         }
+    }
+
+    /// Runs the program until it stops, giving up after `MAX_RUN_STEPS` so an endless loop
+    /// can't freeze the window.
+    fn evaluate(&mut self) {
+        self.load();
+        let (stop, _) = self.cpu.run(&self.image, MAX_RUN_STEPS);
+        self.messages.push(stop.describe(&self.cpu, &self.image));
+        // `pc` is past a HLT, so look up the HLT itself.
+        let last = match stop {
+            Stop::Halted => self.cpu.pc.wrapping_sub(1),
+            _ => self.cpu.pc,
+        };
+        self.current_line = self
+            .image
+            .source_map
+            .lookup(last)
+            .map(|(_, location)| location.line);
+
+        self.show_memory();
         self.cpu.print_memory();
     }
 
+    /// Assembles the program and stops before its first instruction, recording every step so it
+    /// can be undone.
+    fn debug(&mut self) {
+        self.assemble();
+        self.load();
+        self.cpu.history = Some(History::new(history::DEFAULT_CAPACITY));
+        self.debugging = true;
+        self.show_position();
+    }
+
+    /// Highlights the next instruction and refreshes the memory editor.
+    fn show_position(&mut self) {
+        self.current_line = self
+            .image
+            .source_map
+            .lookup(self.cpu.pc)
+            .map(|(_, location)| location.line);
+        self.show_memory();
+    }
+
+    fn step(&mut self) {
+        let (stop, _) = self.cpu.run(&self.image, 1);
+        if stop != Stop::StepLimit {
+            self.messages.push(stop.describe(&self.cpu, &self.image));
+            self.stopped = true;
+        }
+        self.show_position();
+    }
+
+    fn step_back(&mut self) {
+        if self.cpu.step_back() {
+            self.stopped = false;
+        }
+        self.show_position();
+    }
+
+    /// Undoes every recorded instruction; the GUI has no breakpoints for `run_back` to stop at.
+    fn rewind(&mut self) {
+        let (_, steps) = self.cpu.run_back();
+        if steps > 0 {
+            self.stopped = false;
+        }
+        self.show_position();
+    }
+
     fn assemble(&mut self) {
         self.reset();
-        let lexer = Token::lexer(&self.source);
-        let tokens: Vec<_> = lexer.filter_map(|token| token.ok()).collect();
-        let instructions = parse(tokens);
+        let instructions = parse_source(&self.source);
         match instructions {
             Ok(instructions) => {
-                self.assembled_instructions = assemble(&instructions);
+                self.messages = lint(&instructions, &[])
+                    .iter()
+                    .map(|warning| warning.to_string())
+                    .collect();
+                self.image = assemble(&instructions);
 
-                for (i, instruction) in self.assembled_instructions.iter().enumerate() {
-                    self.address[i].0 = format!("{:04X}", i);
-                    self.address[i].1 = format!("{:02X}", instruction);
+                for (i, (address, value)) in self.image.bytes().enumerate() {
+                    self.address[i].0 = format!("{:04X}", address);
+                    self.address[i].1 = format!("{:02X}", value);
                 }
             }
-            Err(err) => eprintln!("{}", err),
+            Err(err) => self.messages.push(err.to_string()),
         }
     }
 }
@@ -105,31 +203,53 @@ impl eframe::App for Application {
 
                     if ui.button("Save").clicked() {}
                 });
-                ui.menu_button("Edit", |ui| {});
-                ui.menu_button("Debug", |ui| {});
+                ui.menu_button("Edit", |_ui| {});
+                ui.menu_button("Debug", |_ui| {});
             });
         });
         // set height and width
-        let debug_icon = Image::new(include_image!("../icons/bug-play.svg"))
+        let debug_icon = egui::Image::new(include_image!("../icons/bug-play.svg"))
             .fit_to_exact_size(Vec2::new(40.0, 40.0));
 
-        let play_icon = Image::new(include_image!("../icons/play.svg"))
+        let play_icon = egui::Image::new(include_image!("../icons/play.svg"))
             .fit_to_exact_size(Vec2::new(40.0, 40.0));
 
         TopBottomPanel::top("secondary_panel").show(ctx, |ui| {
             menu::bar(ui, |ui| {
-                let response = ui.menu_image_button(play_icon, |ui| {});
+                let response = ui.menu_image_button(play_icon, |_ui| {});
                 if response.response.clicked() {
                     self.assemble();
                     self.evaluate();
                 }
 
-                ui.menu_image_button(debug_icon, |ui| {})
+                let response = ui.menu_image_button(debug_icon, |_ui| {});
+                if response.response.clicked() {
+                    self.debug();
+                }
+
+                if self.debugging {
+                    let history = self
+                        .cpu
+                        .history
+                        .as_ref()
+                        .is_some_and(|history| !history.is_empty());
+                    if ui.add_enabled(!self.stopped, Button::new("Step")).clicked() {
+                        self.step();
+                    }
+                    if ui.add_enabled(history, Button::new("Step back")).clicked() {
+                        self.step_back();
+                    }
+                    if ui.add_enabled(history, Button::new("Rewind")).clicked() {
+                        self.rewind();
+                    }
+                }
             });
         });
+        render_messages(ctx, self);
         CentralPanel::default().show(ctx, |ui| {
+            let current_line = self.current_line;
             let mut layouter = |ui: &eframe::egui::Ui, text: &str, wrap_width: f32| {
-                let mut layout_job = syntax_highlighting::highlight(&text);
+                let mut layout_job = syntax_highlighting::highlight(text, current_line);
                 layout_job.wrap.max_width = wrap_width;
                 ui.fonts(|f| f.layout_job(layout_job))
             };
@@ -0,0 +1,20 @@
+use eframe::egui::{Color32, Context, ScrollArea, TopBottomPanel};
+
+use super::application::Application;
+
+const HEIGHT: f32 = 80.;
+
+/// Lists the assembler's errors and lint warnings, and why the program stopped, under the
+/// source.
+pub fn render_messages(ctx: &Context, state: &Application) {
+    if state.messages.is_empty() {
+        return;
+    }
+    TopBottomPanel::bottom("messages").show(ctx, |ui| {
+        ScrollArea::vertical().max_height(HEIGHT).show(ui, |ui| {
+            for message in &state.messages {
+                ui.colored_label(Color32::from_rgb(230, 180, 80), message);
+            }
+        });
+    });
+}
@@ -1,4 +1,5 @@
 pub mod application;
 pub mod devices;
 pub mod memory;
+pub mod messages;
 pub mod registers;
@@ -1,23 +1,16 @@
-use logos::Logos;
 pub mod core;
 pub mod gui;
 pub mod tests;
-use core::{assembler::assemble, cpu::Cpu, parser::parse, token::Token, *};
+use core::{assembler::assemble, cpu::Cpu, image::Image, parser::parse_source, *};
 
-pub fn execute_code(code: &str) -> (Cpu, usize) {
-    let lex = Token::lexer(code);
-    let tokens: Vec<_> = lex.filter_map(|token| token.ok()).collect();
-    let instructions = parse(tokens);
+pub fn execute_code(code: &str) -> (Cpu, Image) {
+    let instructions = parse_source(code);
     match instructions {
         Ok(instructions) => {
-            let assembled_instructions = assemble(&instructions);
+            let image = assemble(&instructions);
             let mut cpu = Cpu::new();
-
-            let assembled_count = assembled_instructions.iter().len();
-            for (index, inst) in assembled_instructions.iter().enumerate() {
-                cpu.write_memory(index, *inst);
-            }
-            (cpu, assembled_count)
+            cpu.load_image(&image);
+            (cpu, image)
         }
         Err(err) => panic!("{}", err),
     }
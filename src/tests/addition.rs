@@ -9,12 +9,12 @@ fn test_add_8_bits() {
     MOV M, A      ; "Store result at 2503H"  
     HLT           ; "Stop"  
     "#;
-    let (mut cpu, assembled_count) = crate::execute_code(source);
+    let (mut cpu, image) = crate::execute_code(source);
     cpu.write_memory(0x2501, 0x99);
     cpu.write_memory(0x2502, 0x39);
 
     while let Some(pc) = cpu.eval() {
-        if pc as usize >= assembled_count {
+        if !image.contains(pc) {
             break;
         }
     }
@@ -47,14 +47,14 @@ fn test_add_16_bits() {
     HLT          ; "Stop"    
     "#;
 
-    let (mut cpu, assembled_count) = crate::execute_code(source);
+    let (mut cpu, image) = crate::execute_code(source);
     cpu.write_memory(0x2501, 0x15);
     cpu.write_memory(0x2502, 0x1C);
     cpu.write_memory(0x2503, 0xB7);
     cpu.write_memory(0x2504, 0x5A);
 
     while let Some(pc) = cpu.eval() {
-        if pc as usize >= assembled_count {
+        if !image.contains(pc) {
             break;
         }
     }
@@ -21,7 +21,7 @@ STA 2300H               ; Store the largest value at 2300.
 HLT                     ; Terminate program execution.
 "#;
 
-    let (mut cpu, assembled_count) = crate::execute_code(source);
+    let (mut cpu, image) = crate::execute_code(source);
     cpu.write_memory(0x2200, 0x04);
     cpu.write_memory(0x2201, 0x01);
     cpu.write_memory(0x2202, 0x05);
@@ -29,7 +29,7 @@ HLT                     ; Terminate program execution.
     cpu.write_memory(0x2204, 0x08);
 
     while let Some(pc) = cpu.eval() {
-        if pc as usize >= assembled_count {
+        if !image.contains(pc) {
             break;
         }
     }
@@ -0,0 +1,39 @@
+use crate::core::{
+    assembler::assemble, instruction_set::INSTRUCTIONS, parser::parse_source,
+    reference::instruction_reference,
+};
+
+#[test]
+fn test_documentation_is_current() {
+    assert!(
+        include_str!("../../DOCUMENTATION.md") == instruction_reference(),
+        "DOCUMENTATION.md is out of date, run `cargo run --bin docs`"
+    );
+}
+
+#[test]
+fn test_examples_assemble() {
+    for info in INSTRUCTIONS {
+        let example = info.example();
+        let instructions =
+            parse_source(&example).unwrap_or_else(|err| panic!("{}: {}", example, err));
+        assert_eq!(instructions[0].ins.info(), info, "{}", example);
+
+        let image = assemble(&instructions);
+        let bytes: Vec<u8> = image.bytes().map(|(_, byte)| byte).collect();
+        assert_eq!(bytes.len(), info.length as usize, "{}", example);
+        // The example's fields are the only bits the encoding leaves open
+        let fixed: String = info.encoding()[..8]
+            .chars()
+            .zip(format!("{:08b}", bytes[0]).chars())
+            .map(|(pattern, bit)| {
+                if pattern.is_ascii_digit() {
+                    bit
+                } else {
+                    pattern
+                }
+            })
+            .collect();
+        assert_eq!(fixed, info.encoding()[..8], "{}", example);
+    }
+}
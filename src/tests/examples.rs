@@ -0,0 +1,26 @@
+use std::fs;
+
+use crate::core::harness::run_annotated;
+
+#[test]
+fn test_examples() {
+    let directory = concat!(env!("CARGO_MANIFEST_DIR"), "/examples");
+    let mut paths: Vec<_> = fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    for path in paths {
+        let source = fs::read_to_string(&path).unwrap();
+        let report =
+            run_annotated(&source).unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+        assert!(
+            report.passed(),
+            "{}: {}",
+            path.display(),
+            report.failures.join("; ")
+        );
+    }
+}
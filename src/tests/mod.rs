@@ -1,3 +1,11 @@
 mod addition;
 mod comparison;
+#[cfg(test)]
+mod documentation;
+#[cfg(test)]
+mod examples;
+#[cfg(test)]
+mod round_trip;
+#[cfg(test)]
+mod running;
 mod subtraction;
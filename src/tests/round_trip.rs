@@ -0,0 +1,243 @@
+use logos::Logos;
+
+use crate::core::{
+    assembler::assemble,
+    cpu::{Cpu, Registers},
+    disassembler::decode,
+    parser::{create_instruction, parse, Ins, JumpTarget},
+    token::Token,
+};
+
+const REGISTERS: [Registers; 8] = [
+    Registers::RegB,
+    Registers::RegC,
+    Registers::RegD,
+    Registers::RegE,
+    Registers::RegH,
+    Registers::RegL,
+    Registers::RegM,
+    Registers::RegA,
+];
+const PAIRS: [Registers; 4] = [
+    Registers::RegB,
+    Registers::RegD,
+    Registers::RegH,
+    Registers::RegSP,
+];
+const STACK_PAIRS: [Registers; 4] = [
+    Registers::RegB,
+    Registers::RegD,
+    Registers::RegH,
+    Registers::RegPSW,
+];
+const ADDRESSES: [u16; 6] = [0x0000, 0x00FF, 0x2050, 0x8000, 0xABCD, 0xFFFF];
+
+/// Every encodable instruction, with every register combination and immediate byte and a sample of
+/// addresses.
+fn all_instructions() -> Vec<Ins<'static>> {
+    let mut all = vec![
+        Ins::Xchg,
+        Ins::Hlt,
+        Ins::Cma,
+        Ins::Cmc,
+        Ins::Stc,
+        Ins::Daa,
+        Ins::Rlc,
+        Ins::Rrc,
+        Ins::Ral,
+        Ins::Rar,
+        Ins::Ret,
+        Ins::Rnz,
+        Ins::Rz,
+        Ins::Rnc,
+        Ins::Rc,
+        Ins::Rpo,
+        Ins::Rpe,
+        Ins::Rp,
+        Ins::Rm,
+        Ins::Xthl,
+        Ins::Sphl,
+        Ins::Pchl,
+        Ins::Ei,
+        Ins::Di,
+        Ins::Rim,
+        Ins::Sim,
+        Ins::Nop,
+    ];
+
+    for destination in REGISTERS {
+        for source in REGISTERS {
+            if destination != Registers::RegM || source != Registers::RegM {
+                all.push(Ins::Mov(destination, source));
+            }
+        }
+    }
+    for register in REGISTERS {
+        all.extend([
+            Ins::Add(register),
+            Ins::Adc(register),
+            Ins::Sub(register),
+            Ins::Sbb(register),
+            Ins::Ana(register),
+            Ins::Xra(register),
+            Ins::Ora(register),
+            Ins::Cmp(register),
+            Ins::Inr(register),
+            Ins::Dcr(register),
+        ]);
+        all.extend((0..=255).map(|value| Ins::Mvi(register, value)));
+    }
+    for register in PAIRS {
+        all.extend([Ins::Inx(register), Ins::Dcx(register), Ins::Dad(register)]);
+        all.extend(ADDRESSES.map(|address| Ins::Lxi(register, address)));
+    }
+    for register in STACK_PAIRS {
+        all.extend([Ins::Push(register), Ins::Pop(register)]);
+    }
+    for register in [Registers::RegB, Registers::RegD] {
+        all.extend([Ins::Ldax(register), Ins::Stax(register)]);
+    }
+    for value in 0..=255 {
+        all.extend([
+            Ins::Adi(value),
+            Ins::Aci(value),
+            Ins::Sui(value),
+            Ins::Sbi(value),
+            Ins::Ani(value),
+            Ins::Xri(value),
+            Ins::Ori(value),
+            Ins::Cpi(value),
+            Ins::In(value),
+            Ins::Out(value),
+        ]);
+    }
+    for address in ADDRESSES {
+        let target = JumpTarget::Address(address);
+        all.extend([
+            Ins::Lda(address),
+            Ins::Sta(address),
+            Ins::Lhld(address),
+            Ins::Shld(address),
+            Ins::Jmp(target),
+            Ins::Jnz(target),
+            Ins::Jz(target),
+            Ins::Jnc(target),
+            Ins::Jc(target),
+            Ins::Jpo(target),
+            Ins::Jpe(target),
+            Ins::Jp(target),
+            Ins::Jm(target),
+            Ins::Call(target),
+            Ins::Cnz(target),
+            Ins::Cz(target),
+            Ins::Cnc(target),
+            Ins::Cc(target),
+            Ins::Cpo(target),
+            Ins::Cpe(target),
+            Ins::Cp(target),
+            Ins::Cm(target),
+        ]);
+    }
+    all.extend((0..8).map(Ins::Rst));
+    all
+}
+
+fn encode(ins: &Ins<'static>) -> Vec<u8> {
+    let image = assemble(&vec![create_instruction(ins.clone())]);
+    image.bytes().map(|(_, byte)| byte).collect()
+}
+
+/// The address control may continue at after `ins`, other than the next instruction.
+fn branch_target(ins: &Ins, cpu: &Cpu) -> Option<u16> {
+    let stack_top =
+        u16::from_le_bytes([cpu.memory[cpu.sp as usize], cpu.memory[cpu.sp as usize + 1]]);
+    match ins {
+        Ins::Jmp(JumpTarget::Address(address))
+        | Ins::Jnz(JumpTarget::Address(address))
+        | Ins::Jz(JumpTarget::Address(address))
+        | Ins::Jnc(JumpTarget::Address(address))
+        | Ins::Jc(JumpTarget::Address(address))
+        | Ins::Jpo(JumpTarget::Address(address))
+        | Ins::Jpe(JumpTarget::Address(address))
+        | Ins::Jp(JumpTarget::Address(address))
+        | Ins::Jm(JumpTarget::Address(address))
+        | Ins::Call(JumpTarget::Address(address))
+        | Ins::Cnz(JumpTarget::Address(address))
+        | Ins::Cz(JumpTarget::Address(address))
+        | Ins::Cnc(JumpTarget::Address(address))
+        | Ins::Cc(JumpTarget::Address(address))
+        | Ins::Cpo(JumpTarget::Address(address))
+        | Ins::Cpe(JumpTarget::Address(address))
+        | Ins::Cp(JumpTarget::Address(address))
+        | Ins::Cm(JumpTarget::Address(address)) => Some(*address),
+        Ins::Ret
+        | Ins::Rnz
+        | Ins::Rz
+        | Ins::Rnc
+        | Ins::Rc
+        | Ins::Rpo
+        | Ins::Rpe
+        | Ins::Rp
+        | Ins::Rm => Some(stack_top),
+        Ins::Rst(vector) => Some(*vector as u16 * 8),
+        Ins::Pchl => Some(u16::from_be_bytes([cpu.h, cpu.l])),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_assemble_disassemble() {
+    for ins in all_instructions() {
+        let bytes = encode(&ins);
+        assert!(!bytes.is_empty(), "{} wasn't encoded", ins);
+
+        let (decoded, length) =
+            decode(&bytes, 0).unwrap_or_else(|| panic!("{} didn't decode", ins));
+        assert_eq!(decoded, ins, "{:02X?}", bytes);
+        assert_eq!(length as usize, bytes.len(), "{}", ins);
+        assert_eq!(ins.size() as usize, bytes.len(), "{}", ins);
+    }
+}
+
+#[test]
+fn test_render_parse() {
+    for ins in all_instructions() {
+        let source = ins.to_string();
+        let tokens: Vec<_> = Token::lexer(&source)
+            .filter_map(|token| token.ok())
+            .collect();
+        let parsed = parse(tokens).unwrap_or_else(|err| panic!("{}: {}", source, err));
+        assert_eq!(parsed.len(), 1, "{}", source);
+        assert_eq!(parsed[0].ins, ins, "{}", source);
+    }
+}
+
+#[test]
+fn test_execution_length() {
+    for ins in all_instructions() {
+        let bytes = encode(&ins);
+        let mut cpu = Cpu::new();
+        cpu.pc = 0x2000;
+        cpu.sp = 0x3000;
+        cpu.h = 0x40;
+        cpu.l = 0x10;
+        cpu.memory[0x3000] = 0x34;
+        cpu.memory[0x3001] = 0x12;
+        cpu.memory[0x2000..0x2000 + bytes.len()].copy_from_slice(&bytes);
+
+        let target = branch_target(&ins, &cpu);
+        let next = 0x2000 + bytes.len() as u16;
+        cpu.eval();
+
+        assert!(cpu.cycles > 0, "{} took no cycles", ins);
+        assert!(
+            cpu.pc == next || Some(cpu.pc) == target,
+            "{} left pc at {:04X}",
+            ins,
+            cpu.pc
+        );
+        if target.is_none() {
+            assert_eq!(cpu.pc, next, "{}", ins);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use crate::core::{
+    breakpoints::{Access, Breakpoint, Condition, WatchKind, Watched, Watchpoint},
+    cpu::{Registers, Stop},
+    history::History,
+};
+
+#[test]
+fn test_run() {
+    let (mut cpu, image) = crate::execute_code("MVI A, 01H\nHLT");
+    assert_eq!(cpu.run(&image, 100), (Stop::Halted, 2));
+    assert_eq!(cpu.accumulator, 0x01);
+
+    let (mut cpu, image) = crate::execute_code("MVI A, 01H\nINR A");
+    assert_eq!(cpu.run(&image, 100), (Stop::LeftProgram(0x0003), 2));
+
+    let (mut cpu, image) = crate::execute_code("LOOP: JMP LOOP");
+    assert_eq!(cpu.run(&image, 100), (Stop::StepLimit, 100));
+
+    let (mut cpu, image) = crate::execute_code("NOP\nNOP");
+    cpu.write_memory(0x0001, 0xCB);
+    assert_eq!(cpu.run(&image, 100), (Stop::UndefinedOpcode(0x0001), 1));
+}
+
+#[test]
+fn test_breakpoints_and_watchpoints() {
+    let source = r#"
+    MVI C, 03H
+    LXI H, 2050H
+    LOOP: MOV M, C
+    INX H
+    DCR C
+    JNZ LOOP
+    OUT 01H
+    HLT
+    "#;
+    let (mut cpu, image) = crate::execute_code(source);
+    let loop_address = image.symbols["LOOP"];
+    cpu.breakpoints.push(Breakpoint {
+        address: loop_address,
+        condition: Condition::parse("C == 1"),
+    });
+    assert_eq!(cpu.run(&image, 100), (Stop::Breakpoint(loop_address), 10));
+    assert_eq!(cpu.c, 0x01);
+
+    cpu.breakpoints.clear();
+    cpu.watchpoints.push(Watchpoint {
+        watched: Watched::Memory(0x2052, 0x2052),
+        kind: WatchKind::Write,
+    });
+    cpu.watchpoints.push(Watchpoint {
+        watched: Watched::Port(0x01, 0x01),
+        kind: WatchKind::Write,
+    });
+    assert_eq!(
+        cpu.run(&image, 100),
+        (Stop::Watchpoint(Access::Write(0x2052)), 1)
+    );
+    assert_eq!(
+        cpu.run(&image, 100),
+        (Stop::Watchpoint(Access::Out(0x01)), 4)
+    );
+    assert_eq!(cpu.run(&image, 100), (Stop::Halted, 1));
+}
+
+#[test]
+fn test_step_back() {
+    let source = r#"
+    LXI SP, 2400H
+    LXI H, 2050H
+    MVI M, 11H
+    CALL STORE
+    XTHL
+    OUT 01H
+    HLT
+    STORE: SHLD 2060H
+    PUSH B
+    POP D
+    MVI A, 22H
+    STA 2050H
+    RET
+    "#;
+    let (mut cpu, image) = crate::execute_code(source);
+    cpu.history = Some(History::new(100));
+    let initial = cpu.clone();
+    assert_eq!(cpu.run(&image, 100), (Stop::Halted, 13));
+    assert_eq!(cpu.ports[1], 0x22);
+
+    // Undo the two last instructions, then everything
+    assert!(cpu.step_back());
+    assert!(cpu.step_back());
+    assert_eq!(cpu.pc, 0x000C);
+    assert_eq!(cpu.run_back(), (false, 11));
+    assert!(!cpu.step_back());
+    assert_eq!(cpu.pc, initial.pc);
+    assert_eq!(cpu.sp, initial.sp);
+    assert_eq!(cpu.cycles, 0);
+    assert_eq!(cpu.pair(Registers::RegH), 0);
+    assert_eq!(cpu.ports, initial.ports);
+    assert!(cpu.memory == initial.memory);
+
+    // Run back to a breakpoint, and forget what doesn't fit
+    cpu.run(&image, 100);
+    cpu.breakpoints.push(Breakpoint {
+        address: image.symbols["STORE"],
+        condition: None,
+    });
+    assert_eq!(cpu.run_back(), (true, 9));
+    let mut short = initial.clone();
+    short.history = Some(History::new(2));
+    short.run(&image, 100);
+    assert_eq!(short.run_back(), (false, 2));
+}
@@ -12,12 +12,12 @@ INX H          ;  "H-L points 4002H"
 MOV M, A       ;  "Store result at 2503H"  
 HLT            ;  "Stop"  
  "#;
-    let (mut cpu, assembled_count) = crate::execute_code(source);
+    let (mut cpu, image) = crate::execute_code(source);
     cpu.write_memory(0x2501, 0x49);
     cpu.write_memory(0x2502, 0x32);
 
     while let Some(pc) = cpu.eval() {
-        if pc as usize >= assembled_count {
+        if !image.contains(pc) {
             break;
         }
     }